@@ -218,7 +218,7 @@ impl AnimationEngine {
                             now.duration_since(active.start_time).as_millis() as u64;
 
                         let blink_cycle = millis_since_epoch / frame.blink_rate_ms;
-                        let show_text = blink_cycle % 2 == 0;
+                        let show_text = blink_cycle.is_multiple_of(2);
 
                         log_debug!("ANIMATION: BLINK DEBUG - since_start: {}ms, cycle: {}, rate: {}ms, show: {}", 
                                   millis_since_epoch, blink_cycle, frame.blink_rate_ms, show_text);
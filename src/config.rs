@@ -0,0 +1,292 @@
+use crate::state::{AppState, LockProfile};
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Actions that can be rebound in `keybindings`, with their default keys.
+/// `quit` and `help` work in every tab, `git_` actions in the Git Stage tab and
+/// the rest in the File Guardian.
+pub const DEFAULT_KEYBINDINGS: &[(&str, &str)] = &[
+    ("quit", "q"),
+    ("help", "?"),
+    ("toggle_lock", "Space"),
+    ("toggle_expand", "Enter"),
+    ("toggle_allow_create", "c"),
+    ("toggle_hidden", "h"),
+    ("refresh", "r"),
+    ("toggle_animations", "a"),
+    ("quest_mark", "m"),
+    ("quest", "Q"),
+    ("git_stage_hunk", "s"),
+    ("git_unstage_hunk", "u"),
+    ("git_line_mode", "v"),
+    ("git_discard", "d"),
+    ("git_commit", "c"),
+    ("git_diff_target", "t"),
+    ("git_side_by_side", "b"),
+    ("git_review", "R"),
+    ("git_revert_locked", "x"),
+    ("git_accept_locked", "a"),
+    ("git_review_base", "g"),
+    ("git_ignore_whitespace", "w"),
+];
+
+/// The tab whose keys `action` is read with, or `None` for every tab.
+fn keybinding_tab(action: &str) -> Option<&'static str> {
+    match action {
+        "quit" | "help" => None,
+        action if action.starts_with("git_") => Some("git"),
+        _ => Some("files"),
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeName {
+    #[default]
+    Desert,
+    Ocean,
+    Mono,
+}
+
+/// How icaros treats a change that touches a locked path.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum EnforcementMode {
+    /// Report the violation but let the operation proceed.
+    Warn,
+    /// Refuse the operation unless explicitly overridden.
+    #[default]
+    Block,
+}
+
+/// Preferences that can be set globally and overridden per repository in `.icaros`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct Preferences {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub animations: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub theme: Option<ThemeName>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enforcement: Option<EnforcementMode>,
+    /// Action name to key, e.g. `toggle_lock: l`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub keybindings: BTreeMap<String, String>,
+}
+
+impl Preferences {
+    pub fn is_empty(&self) -> bool {
+        *self == Preferences::default()
+    }
+}
+
+/// Contents of the user-level `config.yaml`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserConfig {
+    #[serde(flatten)]
+    pub preferences: Preferences,
+    /// Extra ignore patterns applied on top of every repository's own list.
+    pub ignore_patterns: Vec<String>,
+    /// Profile applied when a repository has no `.icaros` yet.
+    pub default_profile: Option<String>,
+    pub profiles: BTreeMap<String, LockProfile>,
+    pub log_file: Option<PathBuf>,
+    pub templates_dir: Option<PathBuf>,
+    /// Directory of standalone profile files shared across repositories.
+    pub profile_library: Option<PathBuf>,
+}
+
+impl UserConfig {
+    /// `$ICAROS_CONFIG`, or `config.yaml` in the platform config directory
+    /// (`~/.config/icaros/config.yaml` on Linux).
+    pub fn path() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os("ICAROS_CONFIG") {
+            return Some(PathBuf::from(path));
+        }
+        dirs::config_dir().map(|dir| dir.join("icaros").join("config.yaml"))
+    }
+
+    pub fn load() -> Result<Self> {
+        match Self::path() {
+            Some(path) if path.exists() => Self::load_from_file(&path),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    pub fn load_from_file(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        let mut config: Self = serde_yaml::from_str(&content)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))?;
+        config.log_file = config.log_file.map(expand_home);
+        config.templates_dir = config.templates_dir.map(expand_home);
+        config.profile_library = config.profile_library.map(expand_home);
+        Ok(config)
+    }
+}
+
+/// Expand a leading `~` to the home directory.
+fn expand_home(path: PathBuf) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path,
+    }
+}
+
+/// Effective settings after layering the repository's `.icaros` over the user config.
+#[derive(Debug, Clone, Serialize)]
+pub struct Settings {
+    pub animations: bool,
+    pub theme: ThemeName,
+    pub enforcement: EnforcementMode,
+    pub keybindings: BTreeMap<String, String>,
+    /// Actions bound to the same key where both are read, as `(key, first, second)`.
+    #[serde(skip)]
+    pub duplicate_keybindings: Vec<(String, String, String)>,
+    pub ignore_patterns: Vec<String>,
+    pub default_profile: Option<String>,
+    pub log_file: PathBuf,
+    pub templates_dir: Option<PathBuf>,
+    pub profile_library: Option<PathBuf>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::resolve(&UserConfig::default(), None)
+    }
+}
+
+impl Settings {
+    /// Resolve settings for the repository owning `state_file`.
+    /// A broken user config is skipped here; `UserConfig::load` reports it.
+    pub fn load(state_file: &Path) -> Self {
+        let user = UserConfig::load().unwrap_or_default();
+        let repo = AppState::load_from_file(state_file).ok();
+        Self::resolve(&user, repo.as_ref())
+    }
+
+    pub fn resolve(user: &UserConfig, repo: Option<&AppState>) -> Self {
+        let global = &user.preferences;
+        let local = repo.map(|state| &state.settings);
+
+        let mut keybindings: BTreeMap<String, String> = DEFAULT_KEYBINDINGS
+            .iter()
+            .map(|(action, key)| (action.to_string(), key.to_string()))
+            .collect();
+        for layer in std::iter::once(global).chain(local) {
+            for (action, key) in &layer.keybindings {
+                if let Some(binding) = keybindings.get_mut(action) {
+                    binding.clone_from(key);
+                }
+            }
+        }
+        let mut duplicate_keybindings = Vec::new();
+        for (index, (first, key)) in keybindings.iter().enumerate() {
+            for (second, other) in keybindings.iter().skip(index + 1) {
+                let (tab, other_tab) = (keybinding_tab(first), keybinding_tab(second));
+                if key == other && (tab.is_none() || other_tab.is_none() || tab == other_tab) {
+                    duplicate_keybindings.push((key.clone(), first.clone(), second.clone()));
+                }
+            }
+        }
+
+        let mut ignore_patterns = repo
+            .map(|state| state.ignore_patterns.clone())
+            .unwrap_or_else(crate::state::default_ignore_patterns);
+        for pattern in &user.ignore_patterns {
+            if !ignore_patterns.contains(pattern) {
+                ignore_patterns.push(pattern.clone());
+            }
+        }
+
+        Self {
+            animations: local
+                .and_then(|p| p.animations)
+                .or(global.animations)
+                .unwrap_or(true),
+            theme: local
+                .and_then(|p| p.theme)
+                .or(global.theme)
+                .unwrap_or_default(),
+            enforcement: local
+                .and_then(|p| p.enforcement)
+                .or(global.enforcement)
+                .unwrap_or_default(),
+            keybindings,
+            duplicate_keybindings,
+            ignore_patterns,
+            default_profile: user.default_profile.clone(),
+            log_file: user
+                .log_file
+                .clone()
+                .unwrap_or_else(|| PathBuf::from("logs/unified.log")),
+            templates_dir: user
+                .templates_dir
+                .clone()
+                .or_else(|| dirs::config_dir().map(|dir| dir.join("icaros").join("prompts"))),
+            // Next to the config file by default, so `$ICAROS_CONFIG` moves it too
+            profile_library: user.profile_library.clone().or_else(|| {
+                UserConfig::path().and_then(|path| Some(path.parent()?.join("profiles")))
+            }),
+        }
+    }
+
+    /// Keybinding actions in `user` or `repo` that icaros does not know about.
+    pub fn unknown_keybindings(user: &UserConfig, repo: Option<&AppState>) -> Vec<String> {
+        let mut unknown: Vec<String> = std::iter::once(&user.preferences)
+            .chain(repo.map(|state| &state.settings))
+            .flat_map(|layer| layer.keybindings.keys())
+            .filter(|action| !DEFAULT_KEYBINDINGS.iter().any(|(known, _)| known == action))
+            .cloned()
+            .collect();
+        unknown.sort();
+        unknown.dedup();
+        unknown
+    }
+
+    pub fn key_for(&self, action: &str) -> &str {
+        self.keybindings
+            .get(action)
+            .map(String::as_str)
+            .unwrap_or("")
+    }
+}
+
+/// State for a repository without an `.icaros`, seeded from the user's default profile.
+pub fn new_repo_state(root_path: PathBuf, user: &UserConfig) -> AppState {
+    let mut state = AppState::new(root_path);
+    if let Some(name) = &user.default_profile {
+        match user.profiles.get(name) {
+            Some(profile) => {
+                state.profiles.insert(name.clone(), profile.clone());
+                state.switch_to_profile(name);
+            }
+            None => {
+                eprintln!("Warning: default profile '{name}' is not defined in the user config")
+            }
+        }
+    }
+    state
+}
+
+/// Profiles in the library directory `dir`, by name (the file stem), sorted.
+pub fn library_profiles(dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut profiles = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && crate::state::StateFormat::from_extension(&path).is_some() {
+            if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                profiles.push((name.to_string(), path.clone()));
+            }
+        }
+    }
+    profiles.sort();
+    Ok(profiles)
+}
@@ -0,0 +1,166 @@
+use crate::git::GitHunk;
+
+/// One row of a side-by-side diff: a line of the old version, of the new one,
+/// or of both. Each side is its line number and text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SideBySideRow {
+    pub old: Option<(u32, String)>,
+    pub new: Option<(u32, String)>,
+}
+
+impl SideBySideRow {
+    /// Whether the row pairs a removed line with the added line replacing it.
+    pub fn is_modified(&self) -> bool {
+        matches!((&self.old, &self.new), (Some((_, old)), Some((_, new))) if old != new)
+    }
+}
+
+/// A run of text from `word_diff`, and whether it changed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub text: String,
+    pub changed: bool,
+}
+
+/// Lay out a hunk in two columns. Context lines sit on both sides; a run of
+/// removed lines is paired with the run of added lines after it, and whichever
+/// is longer leaves gaps on the other side.
+pub fn side_by_side(hunk: &GitHunk) -> Vec<SideBySideRow> {
+    let mut rows = Vec::new();
+    let mut removed: Vec<(u32, String)> = Vec::new();
+    let mut added: Vec<(u32, String)> = Vec::new();
+
+    let flush = |rows: &mut Vec<SideBySideRow>,
+                 removed: &mut Vec<(u32, String)>,
+                 added: &mut Vec<(u32, String)>| {
+        let count = removed.len().max(added.len());
+        let mut removed = removed.drain(..);
+        let mut added = added.drain(..);
+        for _ in 0..count {
+            rows.push(SideBySideRow {
+                old: removed.next(),
+                new: added.next(),
+            });
+        }
+    };
+
+    for line in &hunk.lines {
+        let text = line.content.trim_end_matches(['\n', '\r']).to_string();
+        match (line.origin, line.old_lineno, line.new_lineno) {
+            ('-', Some(old), _) => {
+                // A removal after additions starts a new change
+                if !added.is_empty() {
+                    flush(&mut rows, &mut removed, &mut added);
+                }
+                removed.push((old, text));
+            }
+            ('+', _, Some(new)) => added.push((new, text)),
+            (' ', Some(old), Some(new)) => {
+                flush(&mut rows, &mut removed, &mut added);
+                rows.push(SideBySideRow {
+                    old: Some((old, text.clone())),
+                    new: Some((new, text)),
+                });
+            }
+            // "No newline at end of file" markers
+            _ => {}
+        }
+    }
+    flush(&mut rows, &mut removed, &mut added);
+    rows
+}
+
+/// Split a line into words, runs of whitespace, and single other characters.
+fn tokens(line: &str) -> Vec<&str> {
+    let kind = |c: char| {
+        if c.is_alphanumeric() || c == '_' {
+            0
+        } else if c.is_whitespace() {
+            1
+        } else {
+            2
+        }
+    };
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut previous: Option<i32> = None;
+    for (index, c) in line.char_indices() {
+        let current = kind(c);
+        if index > start && (previous != Some(current) || current == 2) {
+            tokens.push(&line[start..index]);
+            start = index;
+        }
+        previous = Some(current);
+    }
+    if start < line.len() {
+        tokens.push(&line[start..]);
+    }
+    tokens
+}
+
+// Past this many token pairs, a line pair is marked changed as a whole
+const WORD_DIFF_LIMIT: usize = 40_000;
+
+/// Compare two versions of a line word by word. Returns the segments of each,
+/// with the words not shared by both marked as changed.
+pub fn word_diff(old: &str, new: &str) -> (Vec<Segment>, Vec<Segment>) {
+    let (old_tokens, new_tokens) = (tokens(old), tokens(new));
+    let (n, m) = (old_tokens.len(), new_tokens.len());
+    if n * m > WORD_DIFF_LIMIT {
+        let whole = |text: &str| {
+            vec![Segment {
+                text: text.to_string(),
+                changed: true,
+            }]
+        };
+        return (whole(old), whole(new));
+    }
+
+    // Longest common subsequence of tokens, filled from the end
+    let mut lengths = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i][j] = if old_tokens[i] == new_tokens[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut old_kept = vec![false; n];
+    let mut new_kept = vec![false; m];
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if old_tokens[i] == new_tokens[j] {
+            old_kept[i] = true;
+            new_kept[j] = true;
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    (
+        segments(&old_tokens, &old_kept),
+        segments(&new_tokens, &new_kept),
+    )
+}
+
+/// Join tokens into segments, merging neighbours that are both changed or both not.
+fn segments(tokens: &[&str], kept: &[bool]) -> Vec<Segment> {
+    let mut segments: Vec<Segment> = Vec::new();
+    for (token, kept) in tokens.iter().zip(kept) {
+        match segments.last_mut() {
+            Some(last) if last.changed != *kept => last.text.push_str(token),
+            _ => segments.push(Segment {
+                text: token.to_string(),
+                changed: !kept,
+            }),
+        }
+    }
+    segments
+}
@@ -1,12 +1,22 @@
 use anyhow::{Context, Result};
-use git2::{DiffOptions, Repository, Status, StatusOptions};
+use git2::build::CheckoutBuilder;
+use git2::{
+    ApplyLocation, BranchType, ConfigLevel, Delta, Diff, DiffFindOptions, DiffOptions, Oid, Patch,
+    Repository, RepositoryState, RevparseMode, Signature, Status, StatusOptions,
+};
+use std::fs;
 use std::path::{Path, PathBuf};
+use std::process::Command;
 
+/// One side of a path's changes: what is staged (`staged`) or what is still
+/// only in the working tree. A partially staged file has one of each.
 #[derive(Debug, Clone)]
 pub struct GitFile {
     pub path: PathBuf,
     pub status: GitFileStatus,
     pub staged: bool,
+    /// The path a renamed file had before
+    pub renamed_from: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -41,6 +51,266 @@ impl GitFileStatus {
     }
 }
 
+/// A path's change on each side of the index, like the two columns of
+/// `git status --short`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GitPathStatus {
+    pub path: PathBuf,
+    pub index: Option<GitFileStatus>,
+    pub worktree: Option<GitFileStatus>,
+    /// Where a rename staged in the index came from
+    pub index_renamed_from: Option<PathBuf>,
+    /// Where a rename only in the working tree came from
+    pub worktree_renamed_from: Option<PathBuf>,
+}
+
+impl GitPathStatus {
+    /// The short status code, such as `MM`, `A ` or `??`.
+    pub fn code(&self) -> String {
+        if self.worktree == Some(GitFileStatus::Untracked) {
+            return "??".to_string();
+        }
+        let column = |status: Option<GitFileStatus>| status.map_or(" ", GitFileStatus::to_str);
+        format!("{}{}", column(self.index), column(self.worktree))
+    }
+}
+
+/// A file's diff: its hunks, and what else changed about the file.
+#[derive(Debug, Clone, Default)]
+pub struct FileDiff {
+    pub hunks: Vec<GitHunk>,
+    /// For a rename, the old path and how similar the contents are, in percent
+    pub renamed_from: Option<(PathBuf, Option<u32>)>,
+    /// The old and new modes, such as 100644 and 100755, when they differ
+    pub mode_change: Option<(u32, u32)>,
+    /// Set, with no hunks, when the file is binary
+    pub binary: Option<BinaryChange>,
+}
+
+/// Each side of a binary change as its size in bytes and blob id, or `None`
+/// where the file doesn't exist.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BinaryChange {
+    pub old: Option<(u64, Oid)>,
+    pub new: Option<(u64, Oid)>,
+}
+
+impl FileDiff {
+    /// One line for each thing the hunks don't show, for the top of the diff.
+    pub fn notes(&self) -> Vec<String> {
+        let mut notes = Vec::new();
+        if let Some((old_path, similarity)) = &self.renamed_from {
+            notes.push(match similarity {
+                Some(similarity) => {
+                    format!(
+                        "Renamed from {} ({similarity}% similar)",
+                        old_path.display()
+                    )
+                }
+                None => format!("Renamed from {}", old_path.display()),
+            });
+        }
+        if let Some((old_mode, new_mode)) = self.mode_change {
+            notes.push(format!("Mode changed {old_mode:o} → {new_mode:o}"));
+        }
+        if let Some(binary) = &self.binary {
+            let side = |side: Option<(u64, Oid)>| match side {
+                Some((size, id)) => format!("{size} bytes ({})", &id.to_string()[..7]),
+                None => "nothing".to_string(),
+            };
+            notes.push(format!(
+                "Binary file: {} → {}",
+                side(binary.old),
+                side(binary.new)
+            ));
+        }
+        notes
+    }
+}
+
+/// Which two versions of a file a diff compares.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffTarget {
+    /// HEAD to the index: what is staged
+    Staged,
+    /// The index to the working tree: what is not staged yet
+    Unstaged,
+    /// HEAD to the working tree: both together
+    All,
+}
+
+impl DiffTarget {
+    pub fn label(self) -> &'static str {
+        match self {
+            DiffTarget::Staged => "HEAD → index",
+            DiffTarget::Unstaged => "index → working tree",
+            DiffTarget::All => "HEAD → working tree",
+        }
+    }
+
+    /// The diff for one section of the file list.
+    pub fn side(staged: bool) -> Self {
+        if staged {
+            DiffTarget::Staged
+        } else {
+            DiffTarget::Unstaged
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            DiffTarget::Staged => DiffTarget::Unstaged,
+            DiffTarget::Unstaged => DiffTarget::All,
+            DiffTarget::All => DiffTarget::Staged,
+        }
+    }
+}
+
+/// Which version `GitManager::discard_file` puts back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiscardSource {
+    /// The staged version, dropping only unstaged changes
+    Index,
+    /// The committed version, dropping staged changes too
+    Head,
+}
+
+// Where `GitManager::save_checkpoint` keeps session checkpoints, one per
+// running icaros process so concurrent sessions don't replace each other's
+fn checkpoint_ref() -> String {
+    format!("refs/icaros/checkpoints/{}", std::process::id())
+}
+
+/// Whether process `pid` is still running. Where that can't be told, it is
+/// assumed to be, so its checkpoint is kept.
+fn process_running(pid: u32) -> bool {
+    if pid == std::process::id() {
+        return true;
+    }
+    let proc_dir = Path::new("/proc");
+    if proc_dir.is_dir() {
+        return proc_dir.join(pid.to_string()).exists();
+    }
+    if cfg!(unix) {
+        return Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .stderr(std::process::Stdio::null())
+            .status()
+            .map_or(true, |status| status.success());
+    }
+    true
+}
+
+/// What Review mode diffs the working tree against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReviewBase {
+    /// The snapshot taken when icaros started
+    Checkpoint,
+    /// Where HEAD branched off a branch, as in `git diff main...`
+    MergeBase(String),
+    /// A branch, tag or commit
+    Rev(String),
+}
+
+impl ReviewBase {
+    /// Read a base as typed: `checkpoint`, `main...` for the merge base with
+    /// `main`, or any revision.
+    pub fn parse(text: &str) -> Self {
+        let text = text.trim();
+        match text.strip_suffix("...") {
+            _ if text.is_empty() || text == "checkpoint" => ReviewBase::Checkpoint,
+            Some(branch) => ReviewBase::MergeBase(branch.to_string()),
+            None => ReviewBase::Rev(text.to_string()),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            ReviewBase::Checkpoint => "session checkpoint".to_string(),
+            ReviewBase::MergeBase(branch) => format!("merge base with {branch}"),
+            ReviewBase::Rev(spec) => spec.clone(),
+        }
+    }
+}
+
+/// A file changed since a review base, with its line counts.
+#[derive(Debug, Clone)]
+pub struct ReviewFile {
+    pub file: GitFile,
+    pub additions: usize,
+    pub deletions: usize,
+    pub binary: bool,
+}
+
+/// The file a diff delta is about, under its new path unless deleted.
+fn delta_file(delta: &git2::DiffDelta) -> Option<GitFile> {
+    let status = match delta.status() {
+        Delta::Added | Delta::Copied | Delta::Untracked => GitFileStatus::Added,
+        Delta::Deleted => GitFileStatus::Deleted,
+        Delta::Renamed => GitFileStatus::Renamed,
+        Delta::Modified | Delta::Typechange => GitFileStatus::Modified,
+        _ => return None,
+    };
+    let file = if status == GitFileStatus::Deleted {
+        delta.old_file()
+    } else {
+        delta.new_file()
+    };
+    Some(GitFile {
+        path: file.path()?.to_path_buf(),
+        status,
+        staged: false,
+        renamed_from: (status == GitFileStatus::Renamed)
+            .then(|| delta.old_file().path().map(Path::to_path_buf))
+            .flatten(),
+    })
+}
+
+/// What to commit and how, for `GitManager::commit`.
+#[derive(Debug, Clone, Default)]
+pub struct CommitOptions {
+    pub message: String,
+    /// Replace the last commit instead of adding one on top of it
+    pub amend: bool,
+    /// End the message with a `Signed-off-by` trailer for the committer
+    pub sign_off: bool,
+    /// `Name <email>` to record as the author instead of the configured user
+    pub author: Option<String>,
+}
+
+/// Parse `Name <email>` into a signature dated now.
+pub fn parse_author(author: &str) -> Result<Signature<'static>> {
+    let parsed = author.trim().strip_suffix('>').and_then(|rest| {
+        let (name, email) = rest.rsplit_once('<')?;
+        Some((name.trim(), email.trim()))
+    });
+    match parsed {
+        Some((name, email)) if !name.is_empty() && !email.is_empty() => {
+            Ok(Signature::now(name, email)?)
+        }
+        _ => anyhow::bail!("Author must look like 'Name <email>', not '{author}'"),
+    }
+}
+
+/// `message` with a `Signed-off-by` trailer for `signature`, unless it has one.
+pub fn sign_off(message: &str, signature: &Signature) -> String {
+    let trailer = format!(
+        "Signed-off-by: {} <{}>",
+        signature.name().unwrap_or(""),
+        signature.email().unwrap_or("")
+    );
+    let message = message.trim_end();
+    if message.lines().any(|line| line == trailer) {
+        return format!("{message}\n");
+    }
+    // Trailers go together in the last paragraph
+    let separator = match message.lines().last() {
+        Some(line) if line.starts_with("Signed-off-by:") => "\n",
+        _ => "\n\n",
+    };
+    format!("{message}{separator}{trailer}\n")
+}
+
 #[derive(Debug, Clone)]
 pub struct GitHunk {
     pub old_start: u32,
@@ -52,6 +322,86 @@ pub struct GitHunk {
     pub staged: bool,
 }
 
+impl GitHunk {
+    /// A unified diff of this hunk alone, for `git apply`. `reverse` swaps the
+    /// sides, turning an applied change into its undo.
+    pub fn to_patch(&self, path: &Path, reverse: bool) -> String {
+        let all: Vec<usize> = (0..self.lines.len()).collect();
+        self.patch_for_lines(path, reverse, &all)
+    }
+
+    /// Like `to_patch`, with only the changed lines at `selected` (indexes into
+    /// `lines`). As when editing a hunk in `git add -p`, unselected removals are
+    /// kept as context and unselected additions are left out.
+    pub fn patch_for_lines(&self, path: &Path, reverse: bool, selected: &[usize]) -> String {
+        let mut body = String::new();
+        let (mut old_lines, mut new_lines) = (0, 0);
+        let mut kept_previous = false;
+        for (index, line) in self.lines.iter().enumerate() {
+            let origin = match (line.origin, reverse) {
+                ('+', true) => '-',
+                ('-', true) => '+',
+                (origin, _) => origin,
+            };
+            let origin = match origin {
+                // The "no newline at end of file" markers belong to the line before
+                '=' | '>' | '<' => {
+                    if kept_previous {
+                        body.push_str("\\ No newline at end of file\n");
+                    }
+                    continue;
+                }
+                '-' if !selected.contains(&index) => ' ',
+                '+' if !selected.contains(&index) => {
+                    kept_previous = false;
+                    continue;
+                }
+                origin => origin,
+            };
+            match origin {
+                '-' => old_lines += 1,
+                '+' => new_lines += 1,
+                _ => {
+                    old_lines += 1;
+                    new_lines += 1;
+                }
+            }
+            body.push(origin);
+            body.push_str(&line.content);
+            if !line.content.ends_with('\n') {
+                body.push('\n');
+            }
+            kept_previous = true;
+        }
+
+        let old_start = if reverse {
+            self.new_start
+        } else {
+            self.old_start
+        };
+        // An empty side is numbered from the line before it
+        let new_start = match (old_lines, new_lines) {
+            (0, _) => old_start + 1,
+            (_, 0) => old_start.saturating_sub(1),
+            _ => old_start,
+        };
+        let path = path.to_string_lossy();
+        format!(
+            "diff --git a/{path} b/{path}\n--- a/{path}\n+++ b/{path}\n@@ -{old_start},{old_lines} +{new_start},{new_lines} @@\n{body}"
+        )
+    }
+
+    /// Indexes of the lines that add or remove something.
+    pub fn changed_lines(&self) -> Vec<usize> {
+        self.lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| matches!(line.origin, '+' | '-'))
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct DiffLine {
     pub origin: char,
@@ -70,154 +420,1049 @@ impl GitManager {
         Ok(GitManager { repo })
     }
 
-    pub fn get_status_files(&self) -> Result<Vec<GitFile>> {
-        let mut files = Vec::new();
+    pub fn workdir(&self) -> Option<&Path> {
+        self.repo.workdir()
+    }
+
+    /// The `.git` directory, which holds `HEAD`.
+    pub fn git_dir(&self) -> &Path {
+        self.repo.path()
+    }
+
+    /// Name of the checked-out branch, or `None` when HEAD is detached.
+    pub fn current_branch(&self) -> Option<String> {
+        match self.repo.head() {
+            Ok(head) if head.is_branch() => head.shorthand().map(str::to_string),
+            Ok(_) => None,
+            // A branch without commits yet still has a symbolic HEAD
+            Err(_) => self
+                .repo
+                .find_reference("HEAD")
+                .ok()?
+                .symbolic_target()?
+                .strip_prefix("refs/heads/")
+                .map(str::to_string),
+        }
+    }
+
+    /// Each changed path with its index and working tree status kept apart.
+    pub fn get_path_statuses(&self) -> Result<Vec<GitPathStatus>> {
+        let mut paths = Vec::new();
         let mut status_opts = StatusOptions::new();
-        status_opts.include_untracked(true).include_ignored(false);
+        status_opts
+            .include_untracked(true)
+            .include_ignored(false)
+            .renames_head_to_index(true)
+            .renames_index_to_workdir(true);
 
         let statuses = self.repo.statuses(Some(&mut status_opts))?;
 
         for entry in statuses.iter() {
             let status = entry.status();
-            let path = entry.path().unwrap_or("");
+            // For a rename, `entry.path()` is where the file came from
+            let renamed = |delta: Option<git2::DiffDelta>| {
+                let delta = delta.filter(|delta| delta.status() == Delta::Renamed)?;
+                Some((
+                    delta.old_file().path()?.to_path_buf(),
+                    delta.new_file().path()?.to_path_buf(),
+                ))
+            };
+            let index_rename = renamed(entry.head_to_index());
+            let worktree_rename = renamed(entry.index_to_workdir());
+            let path = match (&worktree_rename, &index_rename) {
+                (Some((_, to)), _) | (None, Some((_, to))) => to.clone(),
+                (None, None) => PathBuf::from(entry.path().unwrap_or("")),
+            };
+
+            let index = if status.contains(Status::INDEX_NEW) {
+                Some(GitFileStatus::Added)
+            } else if status.contains(Status::INDEX_DELETED) {
+                Some(GitFileStatus::Deleted)
+            } else if status.contains(Status::INDEX_RENAMED) {
+                Some(GitFileStatus::Renamed)
+            } else if status.intersects(Status::INDEX_MODIFIED | Status::INDEX_TYPECHANGE) {
+                Some(GitFileStatus::Modified)
+            } else {
+                None
+            };
+            let worktree = if status.contains(Status::WT_NEW) {
+                Some(GitFileStatus::Untracked)
+            } else if status.contains(Status::WT_DELETED) {
+                Some(GitFileStatus::Deleted)
+            } else if status.contains(Status::WT_RENAMED) {
+                Some(GitFileStatus::Renamed)
+            } else if status.intersects(Status::WT_MODIFIED | Status::WT_TYPECHANGE) {
+                Some(GitFileStatus::Modified)
+            } else {
+                None
+            };
 
-            // Skip if no status bits are set
-            if status.is_empty() {
+            // Skip ignored and conflicted entries, which have neither
+            if index.is_none() && worktree.is_none() {
                 continue;
             }
+            paths.push(GitPathStatus {
+                path,
+                index,
+                worktree,
+                index_renamed_from: index_rename.map(|(from, _)| from),
+                worktree_renamed_from: worktree_rename.map(|(from, _)| from),
+            });
+        }
 
-            let file_status = if status.contains(Status::WT_NEW) {
-                GitFileStatus::Untracked
-            } else if status.contains(Status::WT_DELETED) || status.contains(Status::INDEX_DELETED)
-            {
-                GitFileStatus::Deleted
-            } else if status.contains(Status::WT_RENAMED) || status.contains(Status::INDEX_RENAMED)
-            {
-                GitFileStatus::Renamed
-            } else if status.contains(Status::INDEX_NEW) {
-                GitFileStatus::Added
-            } else if status.contains(Status::WT_MODIFIED)
-                || status.contains(Status::INDEX_MODIFIED)
-            {
-                GitFileStatus::Modified
-            } else {
-                continue;
-            };
+        Ok(paths)
+    }
+
+    /// The staged changes, then the unstaged ones. A file changed on both sides
+    /// of the index is listed in each.
+    pub fn get_status_files(&self) -> Result<Vec<GitFile>> {
+        let paths = self.get_path_statuses()?;
+        let side = |staged: bool| {
+            paths.iter().filter_map(move |path| {
+                let (status, renamed_from) = if staged {
+                    (path.index, &path.index_renamed_from)
+                } else {
+                    (path.worktree, &path.worktree_renamed_from)
+                };
+                status.map(|status| GitFile {
+                    path: path.path.clone(),
+                    status,
+                    staged,
+                    renamed_from: renamed_from.clone(),
+                })
+            })
+        };
+        Ok(side(true).chain(side(false)).collect())
+    }
+
+    /// Files changed between the ends of a revision range such as `main..HEAD`.
+    /// `a...b` compares `b` with the merge base, and a single revision is compared
+    /// with HEAD.
+    pub fn get_range_files(&self, range: &str) -> Result<Vec<GitFile>> {
+        let spec = self
+            .repo
+            .revparse(range)
+            .with_context(|| format!("Invalid revision range '{range}'"))?;
+        let head =
+            || -> Result<git2::Object> { Ok(self.repo.head()?.peel(git2::ObjectType::Commit)?) };
+        let to = match spec.to() {
+            Some(to) if !spec.mode().contains(RevparseMode::SINGLE) => to.clone(),
+            _ => head()?,
+        };
+        let mut from = match spec.from() {
+            Some(from) => from.clone(),
+            None => head()?,
+        };
+        if spec.mode().contains(RevparseMode::MERGE_BASE) {
+            let base = self.repo.merge_base(from.id(), to.id())?;
+            from = self.repo.find_object(base, None)?;
+        }
+
+        let mut diff = self.repo.diff_tree_to_tree(
+            Some(&from.peel_to_tree()?),
+            Some(&to.peel_to_tree()?),
+            None,
+        )?;
+        diff.find_similar(None)?;
+
+        Ok(diff
+            .deltas()
+            .filter_map(|delta| delta_file(&delta))
+            .collect())
+    }
+
+    /// Commit a snapshot of the working tree, untracked files included, as
+    /// this session's checkpoint Review mode can diff against. The index and
+    /// working tree are left as they are. Every changed and untracked file is
+    /// written to the object database, so this can take a while in a large
+    /// repository.
+    pub fn save_checkpoint(&self) -> Result<Oid> {
+        // A second handle, so the snapshot index is never seen or written
+        let repo = Repository::open(self.repo.path())?;
+        let mut index = repo.index()?;
+        index.add_all(["*"], git2::IndexAddOption::DEFAULT, None)?;
+        index.update_all(["*"], None)?;
+        let tree = self.repo.find_tree(index.write_tree()?)?;
+
+        let signature = self
+            .signature()
+            .or_else(|_| Signature::now("icaros", "icaros@localhost"))?;
+        let parent = self
+            .repo
+            .head()
+            .ok()
+            .and_then(|head| head.peel_to_commit().ok());
+        let commit = self.repo.commit(
+            None,
+            &signature,
+            &signature,
+            "icaros session checkpoint",
+            &tree,
+            &parent.iter().collect::<Vec<_>>(),
+        )?;
+        self.repo
+            .reference(&checkpoint_ref(), commit, true, "icaros session checkpoint")?;
+        Ok(commit)
+    }
+
+    /// This session's checkpoint, if one was taken.
+    pub fn checkpoint(&self) -> Option<Oid> {
+        self.repo.refname_to_id(&checkpoint_ref()).ok()
+    }
+
+    /// Remove the checkpoints of icaros sessions that are no longer running,
+    /// such as ones that crashed. Returns how many were removed.
+    pub fn prune_stale_checkpoints(&self) -> Result<usize> {
+        let mut pruned = 0;
+        for reference in self.repo.references_glob("refs/icaros/checkpoints/*")? {
+            let mut reference = reference?;
+            let pid = reference
+                .name()
+                .and_then(|name| name.rsplit('/').next())
+                .and_then(|pid| pid.parse::<u32>().ok());
+            if pid.is_some_and(|pid| !process_running(pid)) {
+                reference.delete()?;
+                pruned += 1;
+            }
+        }
+        Ok(pruned)
+    }
 
-            let staged = status.contains(Status::INDEX_NEW)
-                || status.contains(Status::INDEX_MODIFIED)
-                || status.contains(Status::INDEX_DELETED)
-                || status.contains(Status::INDEX_RENAMED);
+    /// Remove this session's checkpoint, leaving its objects for `git gc`.
+    pub fn drop_checkpoint(&self) -> Result<()> {
+        match self.repo.find_reference(&checkpoint_ref()) {
+            Ok(mut reference) => Ok(reference.delete()?),
+            Err(e) if e.code() == git2::ErrorCode::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
 
-            files.push(GitFile {
-                path: PathBuf::from(path),
-                status: file_status,
-                staged,
+    /// The branch the repository's work is merged into: what `origin/HEAD`
+    /// points at, else `init.defaultBranch`, `main` or `master`, whichever
+    /// exists first.
+    pub fn default_branch(&self) -> Option<String> {
+        if let Some(remote_head) = self
+            .repo
+            .find_reference("refs/remotes/origin/HEAD")
+            .ok()
+            .and_then(|head| head.symbolic_target().map(str::to_string))
+        {
+            let name = remote_head.strip_prefix("refs/remotes/")?;
+            let local = name.strip_prefix("origin/")?;
+            return Some(if self.has_branch(local) {
+                local.to_string()
+            } else {
+                name.to_string()
             });
         }
+        let configured = self
+            .repo
+            .config()
+            .ok()
+            .and_then(|config| config.get_string("init.defaultBranch").ok());
+        configured
+            .into_iter()
+            .chain(["main".to_string(), "master".to_string()])
+            .find(|branch| self.has_branch(branch))
+    }
+
+    fn has_branch(&self, name: &str) -> bool {
+        self.repo.find_branch(name, BranchType::Local).is_ok()
+    }
+
+    /// The commit a review base stands for.
+    pub fn resolve_review_base(&self, base: &ReviewBase) -> Result<Oid> {
+        let commit = |spec: &str| -> Result<Oid> {
+            Ok(self
+                .repo
+                .revparse_single(spec)
+                .with_context(|| format!("Unknown revision '{spec}'"))?
+                .peel_to_commit()?
+                .id())
+        };
+        match base {
+            ReviewBase::Checkpoint => self
+                .checkpoint()
+                .context("No session checkpoint has been taken"),
+            ReviewBase::MergeBase(branch) => {
+                let head = self.repo.head()?.peel_to_commit()?.id();
+                self.repo
+                    .merge_base(head, commit(branch)?)
+                    .with_context(|| format!("HEAD has no common ancestor with '{branch}'"))
+            }
+            ReviewBase::Rev(spec) => commit(spec),
+        }
+    }
+
+    /// Every file that differs between the commit `base` and the working
+    /// tree, with how many lines each gained and lost.
+    pub fn review_files(&self, base: Oid) -> Result<Vec<ReviewFile>> {
+        let mut diff = self.base_diff(base, &mut Self::diff_options(None, None, false))?;
+        diff.find_similar(Some(
+            DiffFindOptions::new().renames(true).for_untracked(true),
+        ))?;
 
+        let mut files = Vec::new();
+        for (index, delta) in diff.deltas().enumerate() {
+            let Some(file) = delta_file(&delta) else {
+                continue;
+            };
+            let binary = delta.flags().is_binary();
+            let (_, additions, deletions) = match Patch::from_diff(&diff, index)? {
+                Some(patch) if !binary => patch.line_stats()?,
+                _ => (0, 0, 0),
+            };
+            files.push(ReviewFile {
+                file,
+                additions,
+                deletions,
+                binary,
+            });
+        }
+        files.sort_by(|a, b| a.file.path.cmp(&b.file.path));
         Ok(files)
     }
 
+    /// The diff of one file from `review_files`, from `base` to the working tree.
+    pub fn review_diff(
+        &self,
+        file: &GitFile,
+        base: Oid,
+        ignore_whitespace: bool,
+    ) -> Result<FileDiff> {
+        let mut diff_opts = Self::diff_options(
+            Some(&file.path),
+            file.renamed_from.as_deref(),
+            ignore_whitespace,
+        );
+        let mut diff = self.base_diff(base, &mut diff_opts)?;
+        self.collect_file_diff(&mut diff, &file.path, false)
+    }
+
+    /// The full text of a file at `base` and in the working tree.
+    pub fn review_versions(&self, file: &GitFile, base: Oid) -> (Option<String>, Option<String>) {
+        let old_path = file.renamed_from.as_deref().unwrap_or(&file.path);
+        (
+            self.commit_text(base, old_path),
+            self.workdir_text(&file.path),
+        )
+    }
+
+    fn base_diff(&self, base: Oid, diff_opts: &mut DiffOptions) -> Result<Diff<'_>> {
+        let tree = self.repo.find_commit(base)?.tree()?;
+        // Against the files on disk, whatever is staged, so a file committed
+        // in the base and now untracked still counts as there
+        Ok(self
+            .repo
+            .diff_tree_to_workdir(Some(&tree), Some(diff_opts))?)
+    }
+
     pub fn get_file_diff(&self, file_path: &Path, staged: bool) -> Result<Vec<GitHunk>> {
-        let mut diff_opts = DiffOptions::new();
-        diff_opts.pathspec(file_path);
-
-        let diff = if staged {
-            // Show staged changes (index vs HEAD)
-            let head = self.repo.head()?.peel_to_tree()?;
-            let mut index = self.repo.index()?;
-            let oid = index.write_tree()?;
-            let index_tree = self.repo.find_tree(oid)?;
-            self.repo
-                .diff_tree_to_tree(Some(&head), Some(&index_tree), Some(&mut diff_opts))?
-        } else {
-            // Show unstaged changes (workdir vs index)
-            self.repo
-                .diff_index_to_workdir(None, Some(&mut diff_opts))?
+        self.get_diff(file_path, DiffTarget::side(staged))
+    }
+
+    pub fn get_diff(&self, file_path: &Path, target: DiffTarget) -> Result<Vec<GitHunk>> {
+        Ok(self.file_diff(file_path, None, target, false)?.hunks)
+    }
+
+    /// The diff of a file from the status list, following it across a rename.
+    /// With `ignore_whitespace`, lines that differ only in whitespace match.
+    pub fn diff_file(
+        &self,
+        file: &GitFile,
+        target: DiffTarget,
+        ignore_whitespace: bool,
+    ) -> Result<FileDiff> {
+        self.file_diff(
+            &file.path,
+            file.renamed_from.as_deref(),
+            target,
+            ignore_whitespace,
+        )
+    }
+
+    fn file_diff(
+        &self,
+        file_path: &Path,
+        renamed_from: Option<&Path>,
+        target: DiffTarget,
+        ignore_whitespace: bool,
+    ) -> Result<FileDiff> {
+        let mut diff_opts = Self::diff_options(Some(file_path), renamed_from, ignore_whitespace);
+
+        // Before the first commit there is no HEAD tree, and everything is new
+        let head = match self.repo.head() {
+            Ok(head) => Some(head.peel_to_tree()?),
+            Err(_) => None,
         };
+        let mut diff = match target {
+            DiffTarget::Staged => {
+                self.repo
+                    .diff_tree_to_index(head.as_ref(), None, Some(&mut diff_opts))?
+            }
+            DiffTarget::Unstaged => self
+                .repo
+                .diff_index_to_workdir(None, Some(&mut diff_opts))?,
+            DiffTarget::All => self
+                .repo
+                .diff_tree_to_workdir_with_index(head.as_ref(), Some(&mut diff_opts))?,
+        };
+        self.collect_file_diff(&mut diff, file_path, target == DiffTarget::Staged)
+    }
 
-        let mut hunks = Vec::new();
+    /// Diff options for one file, and its old path if renamed, or for every
+    /// file without a path. Untracked files show in full.
+    fn diff_options(
+        file_path: Option<&Path>,
+        renamed_from: Option<&Path>,
+        ignore_whitespace: bool,
+    ) -> DiffOptions {
+        let mut diff_opts = DiffOptions::new();
+        diff_opts
+            .include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .show_untracked_content(true)
+            .ignore_whitespace(ignore_whitespace)
+            .ignore_whitespace_change(ignore_whitespace)
+            .ignore_whitespace_eol(ignore_whitespace);
+        for path in file_path.into_iter().chain(renamed_from) {
+            diff_opts.pathspec(path);
+        }
+        diff_opts
+    }
 
-        // Use RefCell to share mutable state between closures
-        use std::cell::RefCell;
-        let current_hunk = RefCell::new(None::<GitHunk>);
+    /// The hunks and notes of a diff limited to one file.
+    fn collect_file_diff(
+        &self,
+        diff: &mut Diff,
+        file_path: &Path,
+        staged: bool,
+    ) -> Result<FileDiff> {
+        diff.find_similar(Some(
+            DiffFindOptions::new().renames(true).for_untracked(true),
+        ))?;
 
-        diff.foreach(
-            &mut |_delta, _| true,
-            None,
-            Some(&mut |_delta, hunk| {
-                let hunk_header = std::str::from_utf8(hunk.header()).unwrap_or("");
+        let mut result = FileDiff::default();
+        for index in 0..diff.deltas().len() {
+            let mut patch = Patch::from_diff(diff, index)?;
+            let Some(delta) = diff.get_delta(index) else {
+                continue;
+            };
 
-                // Start a new hunk
-                if let Some(hunk) = current_hunk.borrow_mut().take() {
-                    hunks.push(hunk);
+            if delta.status() == Delta::Renamed {
+                // git2 doesn't expose the similarity, but the patch header has it
+                let similarity = patch
+                    .as_mut()
+                    .and_then(|patch| patch.to_buf().ok())
+                    .and_then(|buf| {
+                        String::from_utf8_lossy(&buf).lines().find_map(|line| {
+                            line.strip_prefix("similarity index ")?
+                                .strip_suffix('%')?
+                                .parse()
+                                .ok()
+                        })
+                    });
+                if let Some(old_path) = delta.old_file().path() {
+                    result.renamed_from = Some((old_path.to_path_buf(), similarity));
                 }
-                *current_hunk.borrow_mut() = Some(GitHunk {
+            }
+
+            let (old_mode, new_mode) = (
+                u32::from(delta.old_file().mode()),
+                u32::from(delta.new_file().mode()),
+            );
+            if old_mode != 0 && new_mode != 0 && old_mode != new_mode {
+                result.mode_change = Some((old_mode, new_mode));
+            }
+
+            if delta.flags().is_binary() {
+                result.binary = Some(BinaryChange {
+                    old: match delta.status() {
+                        Delta::Added | Delta::Untracked => None,
+                        _ => self.blob_summary(&delta.old_file()),
+                    },
+                    new: match delta.status() {
+                        Delta::Deleted => None,
+                        _ => self.blob_summary(&delta.new_file()),
+                    },
+                });
+                continue;
+            }
+
+            let Some(patch) = patch else {
+                continue;
+            };
+            // Files inside an untracked directory are told apart by path
+            let prefix = match delta.new_file().path() {
+                Some(path) if path != file_path => format!("{} ", path.display()),
+                _ => String::new(),
+            };
+            for hunk_index in 0..patch.num_hunks() {
+                let (hunk, line_count) = patch.hunk(hunk_index)?;
+                let mut lines = Vec::with_capacity(line_count);
+                for line_index in 0..line_count {
+                    let line = patch.line_in_hunk(hunk_index, line_index)?;
+                    lines.push(DiffLine {
+                        origin: line.origin(),
+                        content: String::from_utf8_lossy(line.content()).into_owned(),
+                        old_lineno: line.old_lineno(),
+                        new_lineno: line.new_lineno(),
+                    });
+                }
+                result.hunks.push(GitHunk {
                     old_start: hunk.old_start(),
                     old_lines: hunk.old_lines(),
                     new_start: hunk.new_start(),
                     new_lines: hunk.new_lines(),
-                    header: hunk_header.to_string(),
-                    lines: Vec::new(),
+                    header: format!("{prefix}{}", String::from_utf8_lossy(hunk.header())),
+                    lines,
                     staged,
                 });
-                true
-            }),
-            Some(&mut |_delta, _hunk, line| {
-                let content = std::str::from_utf8(line.content())
-                    .unwrap_or("")
-                    .to_string();
-                let diff_line = DiffLine {
-                    origin: line.origin(),
-                    content,
-                    old_lineno: line.old_lineno(),
-                    new_lineno: line.new_lineno(),
-                };
-
-                if let Some(ref mut hunk) = current_hunk.borrow_mut().as_mut() {
-                    hunk.lines.push(diff_line);
-                }
+            }
+        }
 
-                true
-            }),
-        )?;
+        Ok(result)
+    }
 
-        // Don't forget the last hunk
-        if let Some(hunk) = current_hunk.into_inner() {
-            hunks.push(hunk);
+    /// The full text of the two versions a diff compares, for highlighting
+    /// the file as a whole. A side that is missing or not UTF-8 is `None`.
+    pub fn file_versions(
+        &self,
+        file: &GitFile,
+        target: DiffTarget,
+    ) -> (Option<String>, Option<String>) {
+        let old_path = file.renamed_from.as_deref().unwrap_or(&file.path);
+        match target {
+            DiffTarget::Staged => (self.head_text(old_path), self.index_text(&file.path)),
+            DiffTarget::Unstaged => (self.index_text(old_path), self.workdir_text(&file.path)),
+            DiffTarget::All => (self.head_text(old_path), self.workdir_text(&file.path)),
         }
+    }
+
+    fn head_text(&self, file_path: &Path) -> Option<String> {
+        self.commit_text(
+            self.repo.head().ok()?.peel_to_commit().ok()?.id(),
+            file_path,
+        )
+    }
+
+    fn commit_text(&self, commit: Oid, file_path: &Path) -> Option<String> {
+        let tree = self.repo.find_commit(commit).ok()?.tree().ok()?;
+        let entry = tree.get_path(file_path).ok()?;
+        let blob = self.repo.find_blob(entry.id()).ok()?;
+        String::from_utf8(blob.content().to_vec()).ok()
+    }
 
-        Ok(hunks)
+    fn index_text(&self, file_path: &Path) -> Option<String> {
+        let entry = self.repo.index().ok()?.get_path(file_path, 0)?;
+        let blob = self.repo.find_blob(entry.id).ok()?;
+        String::from_utf8(blob.content().to_vec()).ok()
+    }
+
+    fn workdir_text(&self, file_path: &Path) -> Option<String> {
+        fs::read_to_string(self.workdir()?.join(file_path)).ok()
+    }
+
+    /// The size and blob id of one side of a binary change, reading the
+    /// working tree for a side that isn't stored in the repository.
+    fn blob_summary(&self, file: &git2::DiffFile) -> Option<(u64, Oid)> {
+        if let Ok(blob) = self.repo.find_blob(file.id()) {
+            return Some((blob.size() as u64, blob.id()));
+        }
+        let path = self.workdir()?.join(file.path()?);
+        let size = fs::metadata(&path).ok()?.len();
+        let id = Oid::hash_file(git2::ObjectType::Blob, &path).ok()?;
+        Some((size, id))
     }
 
     pub fn stage_file(&self, file_path: &Path) -> Result<()> {
         let mut index = self.repo.index()?;
-        index.add_path(file_path)?;
+        let path = self.workdir().context("No working tree")?.join(file_path);
+        if path.is_dir() {
+            // An untracked directory, listed as `dir/`
+            index.add_all([file_path], git2::IndexAddOption::DEFAULT, None)?;
+        } else if path.exists() {
+            index.add_path(file_path)?;
+        } else {
+            index.remove_path(file_path)?;
+        }
         index.write()?;
         Ok(())
     }
 
+    /// Stage a file from the status list, with where it came from if renamed.
+    pub fn stage(&self, file: &GitFile) -> Result<()> {
+        if let Some(old_path) = &file.renamed_from {
+            self.stage_file(old_path)?;
+        }
+        self.stage_file(&file.path)
+    }
+
+    /// Unstage a file from the status list, with where it came from if renamed.
+    pub fn unstage(&self, file: &GitFile) -> Result<()> {
+        if let Some(old_path) = &file.renamed_from {
+            self.unstage_file(old_path)?;
+        }
+        self.unstage_file(&file.path)
+    }
+
     pub fn unstage_file(&self, file_path: &Path) -> Result<()> {
-        let head = self.repo.head()?.peel_to_tree()?;
-        let _index = self.repo.index()?;
+        // Reset the file in the index to match HEAD, or drop it before the first commit
+        let head = match self.repo.head() {
+            Ok(head) => Some(head.peel_to_commit()?.into_object()),
+            Err(_) => None,
+        };
+        self.repo.reset_default(head.as_ref(), [file_path])?;
+
+        Ok(())
+    }
+
+    /// Register `icaros merge-driver` in `.git/config` and route `pattern` to it via `.gitattributes`.
+    pub fn install_merge_driver(&self, pattern: &str) -> Result<()> {
+        let mut config = self
+            .repo
+            .config()?
+            .open_level(ConfigLevel::Local)
+            .context("Failed to open repository config")?;
+        config.set_str("merge.icaros.name", "icaros state merge driver")?;
+        config.set_str("merge.icaros.driver", "icaros merge-driver %O %A %B")?;
+
+        let workdir = self
+            .repo
+            .workdir()
+            .context("Cannot install merge driver in a bare repository")?;
+        let attributes_path = workdir.join(".gitattributes");
+        let attribute = format!("{pattern} merge=icaros");
+
+        let mut content = fs::read_to_string(&attributes_path).unwrap_or_default();
+        if !content.lines().any(|line| line.trim() == attribute) {
+            if !content.is_empty() && !content.ends_with('\n') {
+                content.push('\n');
+            }
+            content.push_str(&attribute);
+            content.push('\n');
+            fs::write(&attributes_path, content)?;
+        }
+
+        Ok(())
+    }
+
+    /// Run `command` from the post-checkout hook on branch checkouts, creating the
+    /// hook or appending to an existing one. Returns the hook's path.
+    pub fn install_post_checkout_hook(&self, command: &str) -> Result<PathBuf> {
+        // $3 is 1 for branch checkouts and 0 for file checkouts
+        self.add_to_hook(
+            "post-checkout",
+            command,
+            &format!(
+                "# icaros: follow the branch-to-profile rules in .icaros\n\
+                 if [ \"$3\" = \"1\" ]; then\n    {command}\nfi\n"
+            ),
+        )
+    }
+
+    /// Run `check_command` (an `icaros check` without paths) on the staged
+    /// paths from the pre-commit hook, so a commit changing a locked path fails.
+    /// `relative_to` limits it to a directory below the top of the working tree,
+    /// with paths relative to it. Returns the hook's path.
+    pub fn install_pre_commit_hook(
+        &self,
+        check_command: &str,
+        relative_to: Option<&str>,
+    ) -> Result<PathBuf> {
+        let diff = match relative_to {
+            Some(dir) => format!("git diff --cached --name-only --relative={dir}"),
+            None => "git diff --cached --name-only".to_string(),
+        };
+        let command = format!("{diff} -z | xargs -0 {check_command} || exit 1");
+        self.add_to_hook(
+            "pre-commit",
+            &command,
+            &format!(
+                "# icaros: refuse commits that change locked paths\n\
+                 if [ -n \"$({diff})\" ]; then\n    {command}\nfi\n"
+            ),
+        )
+    }
+
+    /// Append `snippet` to the hook `name` unless it already runs `command`,
+    /// creating the hook if needed, and make it executable.
+    fn add_to_hook(&self, name: &str, command: &str, snippet: &str) -> Result<PathBuf> {
+        let hooks_dir = self.hooks_dir()?;
+        fs::create_dir_all(&hooks_dir)?;
+        let hook_path = hooks_dir.join(name);
+
+        let mut content = fs::read_to_string(&hook_path).unwrap_or_default();
+        if !content.lines().any(|line| line.trim() == command) {
+            if content.is_empty() {
+                content.push_str("#!/bin/sh\n");
+            } else if !content.ends_with('\n') {
+                content.push('\n');
+            }
+            content.push('\n');
+            content.push_str(snippet);
+            fs::write(&hook_path, content)?;
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mut permissions = fs::metadata(&hook_path)?.permissions();
+            permissions.set_mode(permissions.mode() | 0o755);
+            fs::set_permissions(&hook_path, permissions)?;
+        }
+
+        Ok(hook_path)
+    }
+
+    /// Where git looks for hooks: `core.hooksPath`, or `.git/hooks`.
+    fn hooks_dir(&self) -> Result<PathBuf> {
+        Ok(match self.repo.config()?.get_path("core.hooksPath") {
+            Ok(path) if path.is_absolute() => path,
+            Ok(path) => self
+                .repo
+                .workdir()
+                .unwrap_or_else(|| self.repo.path())
+                .join(path),
+            Err(_) => self.repo.path().join("hooks"),
+        })
+    }
 
-        // Reset the file in the index to match HEAD
+    /// Run a hook the way git does: only if it exists and is executable, from
+    /// the top of the working tree. A hook that exits non-zero is an error.
+    fn run_hook(&self, name: &str, args: &[&str]) -> Result<()> {
+        let hook = self.hooks_dir()?.join(name);
+        let Ok(metadata) = fs::metadata(&hook) else {
+            return Ok(());
+        };
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if metadata.permissions().mode() & 0o111 == 0 {
+                return Ok(());
+            }
+        }
+        if !metadata.is_file() {
+            return Ok(());
+        }
+
+        let output = Command::new(&hook)
+            .args(args)
+            .current_dir(self.repo.workdir().unwrap_or_else(|| self.repo.path()))
+            .output()
+            .with_context(|| format!("Failed to run the {name} hook"))?;
+        if !output.status.success() {
+            let mut details = String::from_utf8_lossy(&output.stderr).into_owned();
+            details.push_str(&String::from_utf8_lossy(&output.stdout));
+            anyhow::bail!("The {name} hook failed\n{}", details.trim_end());
+        }
+        Ok(())
+    }
+
+    /// The configured `user.name` and `user.email`, dated now.
+    pub fn signature(&self) -> Result<Signature<'static>> {
         self.repo
-            .reset_default(Some(&head.into_object()), [file_path])?;
+            .signature()
+            .context("Set user.name and user.email in your git config to commit")
+    }
+
+    pub fn last_commit_message(&self) -> Option<String> {
+        let commit = self.repo.head().ok()?.peel_to_commit().ok()?;
+        commit.message().map(str::to_string)
+    }
+
+    /// Whether the index differs from HEAD.
+    pub fn has_staged_changes(&self) -> Result<bool> {
+        let head = match self.repo.head() {
+            Ok(head) => Some(head.peel_to_tree()?),
+            Err(_) => None,
+        };
+        let diff = self.repo.diff_tree_to_index(head.as_ref(), None, None)?;
+        Ok(diff.deltas().len() > 0)
+    }
+
+    /// Commit the index like `git commit`, running the pre-commit,
+    /// prepare-commit-msg, commit-msg and post-commit hooks.
+    pub fn commit(&self, options: &CommitOptions) -> Result<Oid> {
+        let head = self
+            .repo
+            .head()
+            .ok()
+            .and_then(|head| head.peel_to_commit().ok());
+        if options.amend && head.is_none() {
+            anyhow::bail!("There is no commit to amend yet");
+        }
+        // A commit here would drop the extra parents and sequencer state git keeps
+        let operation = match self.repo.state() {
+            RepositoryState::Clean | RepositoryState::Bisect => None,
+            RepositoryState::Merge => Some("merge"),
+            RepositoryState::Revert | RepositoryState::RevertSequence => Some("revert"),
+            RepositoryState::CherryPick | RepositoryState::CherryPickSequence => {
+                Some("cherry-pick")
+            }
+            RepositoryState::ApplyMailbox | RepositoryState::ApplyMailboxOrRebase => Some("am"),
+            RepositoryState::Rebase
+            | RepositoryState::RebaseInteractive
+            | RepositoryState::RebaseMerge => Some("rebase"),
+        };
+        if let Some(operation) = operation {
+            anyhow::bail!("A {operation} is in progress; finish it with git first");
+        }
+        if !options.amend && !self.has_staged_changes()? {
+            anyhow::bail!("Nothing staged to commit");
+        }
+        let committer = self.signature()?;
+        let author = match (&options.author, &head) {
+            (Some(author), _) => parse_author(author)?,
+            // Amending keeps the original author, as git does
+            (None, Some(head)) if options.amend => head.author().to_owned(),
+            (None, _) => committer.clone(),
+        };
+
+        self.run_hook("pre-commit", &[])?;
+
+        // The message hooks edit COMMIT_EDITMSG in place
+        let mut message = options.message.clone();
+        if options.sign_off {
+            message = sign_off(&message, &committer);
+        }
+        if !message.ends_with('\n') {
+            message.push('\n');
+        }
+        let message_file = self.repo.path().join("COMMIT_EDITMSG");
+        fs::write(&message_file, &message)?;
+        let message_arg = message_file.to_string_lossy();
+        if options.amend {
+            self.run_hook("prepare-commit-msg", &[&message_arg, "commit", "HEAD"])?;
+        } else {
+            self.run_hook("prepare-commit-msg", &[&message_arg, "message"])?;
+        }
+        self.run_hook("commit-msg", &[&message_arg])?;
+        let message = git2::message_prettify(fs::read_to_string(&message_file)?, Some(b'#'))?;
+        if message.trim().is_empty() {
+            anyhow::bail!("Aborting commit due to empty commit message");
+        }
+
+        // pre-commit may have staged more, so read the index again
+        let mut index = self.repo.index()?;
+        index.read(true)?;
+        let tree = self.repo.find_tree(index.write_tree()?)?;
+        let oid = match &head {
+            Some(head) if options.amend => head.amend(
+                Some("HEAD"),
+                Some(&author),
+                Some(&committer),
+                None,
+                Some(&message),
+                Some(&tree),
+            )?,
+            _ => {
+                let parents: Vec<&git2::Commit> = head.iter().collect();
+                self.repo
+                    .commit(Some("HEAD"), &author, &committer, &message, &tree, &parents)?
+            }
+        };
+
+        // Like git, a failing post-commit hook doesn't undo the commit
+        let _ = self.run_hook("post-commit", &[]);
+        Ok(oid)
+    }
+
+    /// Put `file_path` back to its staged or committed version, saving what is
+    /// thrown away to the trash first. Returns the trash entry.
+    pub fn discard_file(&self, file_path: &Path, source: DiscardSource) -> Result<PathBuf> {
+        if source == DiscardSource::Index && self.repo.index()?.get_path(file_path, 0).is_none() {
+            anyhow::bail!("{} is not in the index", file_path.display());
+        }
+        let trash = self.save_to_trash(file_path, source == DiscardSource::Head)?;
+        match source {
+            DiscardSource::Index => {
+                self.repo
+                    .checkout_index(None, Some(CheckoutBuilder::new().force().path(file_path)))
+                    .with_context(|| format!("Failed to discard {}", file_path.display()))?;
+            }
+            DiscardSource::Head => {
+                let head = match self.repo.head() {
+                    Ok(head) => Some(head.peel_to_tree()?),
+                    Err(_) => None,
+                };
+                match head {
+                    Some(tree) if tree.get_path(file_path).is_ok() => {
+                        self.repo
+                            .checkout_tree(
+                                tree.as_object(),
+                                Some(CheckoutBuilder::new().force().path(file_path)),
+                            )
+                            .with_context(|| {
+                                format!("Failed to discard {}", file_path.display())
+                            })?;
+                    }
+                    // Added since HEAD, so there's nothing to go back to
+                    _ => {
+                        let mut index = self.repo.index()?;
+                        index.remove_path(file_path)?;
+                        index.write()?;
+                        let workdir = self.workdir().context("No working tree")?;
+                        if workdir.join(file_path).exists() {
+                            fs::remove_file(workdir.join(file_path))?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(trash)
+    }
+
+    /// Undo a rename of `old_path` to `new_path`, putting the old path back and
+    /// dropping the new one. A staged rename goes back to HEAD. An unstaged one
+    /// only exists in the working tree, where the new path is untracked, so the
+    /// old path comes back from the index and the new one is deleted. Returns
+    /// the trash entry of the new path.
+    pub fn discard_rename(
+        &self,
+        old_path: &Path,
+        new_path: &Path,
+        staged: bool,
+    ) -> Result<PathBuf> {
+        if staged {
+            self.discard_file(old_path, DiscardSource::Head)?;
+            self.discard_file(new_path, DiscardSource::Head)
+        } else {
+            self.discard_file(old_path, DiscardSource::Index)?;
+            self.delete_untracked(new_path)
+        }
+    }
+
+    /// Undo one hunk of the file's unstaged changes in the working tree.
+    pub fn discard_hunk(&self, file_path: &Path, hunk: &GitHunk) -> Result<PathBuf> {
+        let trash = self.save_to_trash(file_path, false)?;
+        self.apply_to_workdir(&hunk.to_patch(file_path, true))
+            .context("Failed to discard hunk")?;
+        Ok(trash)
+    }
+
+    /// Undo only the `lines` of an unstaged hunk in the working tree.
+    pub fn discard_lines(
+        &self,
+        file_path: &Path,
+        hunk: &GitHunk,
+        lines: &[usize],
+    ) -> Result<PathBuf> {
+        let trash = self.save_to_trash(file_path, false)?;
+        self.apply_to_workdir(&hunk.patch_for_lines(file_path, true, lines))
+            .context("Failed to discard lines")?;
+        Ok(trash)
+    }
+
+    /// Delete an untracked file, or a directory git lists as `dir/`.
+    pub fn delete_untracked(&self, file_path: &Path) -> Result<PathBuf> {
+        let trash = self.save_to_trash(file_path, false)?;
+        let path = self.workdir().context("No working tree")?.join(file_path);
+        if path.is_dir() {
+            fs::remove_dir_all(&path)?;
+        } else {
+            fs::remove_file(&path)?;
+        }
+        Ok(trash)
+    }
+
+    /// Copy the working tree version of `file_path` (and with `staged`, its
+    /// version in the index) to a new entry under `.git/icaros/trash/`, as
+    /// `worktree/<path>` and `index/<path>`. Returns the entry.
+    fn save_to_trash(&self, file_path: &Path, staged: bool) -> Result<PathBuf> {
+        let trash = self.repo.path().join("icaros").join("trash");
+        let stamp = chrono::Utc::now().format("%Y%m%d-%H%M%S%.3f").to_string();
+        let mut entry = trash.join(&stamp);
+        let mut attempt = 1;
+        while entry.exists() {
+            entry = trash.join(format!("{stamp}-{attempt}"));
+            attempt += 1;
+        }
+        fs::create_dir_all(&entry).context("Failed to create the trash entry")?;
+
+        let workdir = self.workdir().context("No working tree")?;
+        let source = workdir.join(file_path);
+        if source.is_dir() {
+            for item in walkdir::WalkDir::new(&source) {
+                let item = item?;
+                if item.file_type().is_file() {
+                    let relative = item.path().strip_prefix(workdir)?;
+                    copy_file(item.path(), &entry.join("worktree").join(relative))?;
+                }
+            }
+        } else if source.exists() {
+            copy_file(&source, &entry.join("worktree").join(file_path))?;
+        }
+
+        if staged {
+            if let Some(staged_entry) = self.repo.index()?.get_path(file_path, 0) {
+                let blob = self.repo.find_blob(staged_entry.id)?;
+                let target = entry.join("index").join(file_path);
+                fs::create_dir_all(target.parent().unwrap_or(&entry))?;
+                fs::write(target, blob.content())?;
+            }
+        }
+        Ok(entry)
+    }
 
+    fn apply_to_workdir(&self, patch: &str) -> Result<()> {
+        let diff = Diff::from_buffer(patch.as_bytes())?;
+        self.repo.apply(&diff, ApplyLocation::WorkDir, None)?;
         Ok(())
     }
 
-    pub fn stage_hunk(&self, _file_path: &Path, _hunk: &GitHunk) -> Result<()> {
-        // This is more complex and would require patching
-        // For now, return an error indicating it's not implemented
-        anyhow::bail!("Staging individual hunks is not yet implemented")
+    /// Stage one hunk of the file's unstaged changes.
+    pub fn stage_hunk(&self, file_path: &Path, hunk: &GitHunk) -> Result<()> {
+        // The only hunk of a deleted file stages the deletion, not an empty file
+        let workdir = self.workdir().context("No working tree")?;
+        if hunk.new_start == 0 && hunk.new_lines == 0 && !workdir.join(file_path).exists() {
+            return self.stage_file(file_path);
+        }
+        self.apply_to_index(&hunk.to_patch(file_path, false))
+            .context("Failed to stage hunk")
+    }
+
+    /// Take one hunk of the file's staged changes back out of the index.
+    pub fn unstage_hunk(&self, file_path: &Path, hunk: &GitHunk) -> Result<()> {
+        // The only hunk of a new file takes it out of the index, not just its lines
+        if hunk.old_start == 0 && hunk.old_lines == 0 && !self.in_head(file_path)? {
+            return self.unstage_file(file_path);
+        }
+        self.apply_to_index(&hunk.to_patch(file_path, true))
+            .context("Failed to unstage hunk")
+    }
+
+    fn in_head(&self, file_path: &Path) -> Result<bool> {
+        let Ok(head) = self.repo.head() else {
+            return Ok(false);
+        };
+        Ok(head.peel_to_tree()?.get_path(file_path).is_ok())
+    }
+
+    /// Stage only the `lines` (indexes into `hunk.lines`) of an unstaged hunk.
+    pub fn stage_lines(&self, file_path: &Path, hunk: &GitHunk, lines: &[usize]) -> Result<()> {
+        self.apply_to_index(&hunk.patch_for_lines(file_path, false, lines))
+            .context("Failed to stage lines")
+    }
+
+    /// Take only the `lines` of a staged hunk back out of the index.
+    pub fn unstage_lines(&self, file_path: &Path, hunk: &GitHunk, lines: &[usize]) -> Result<()> {
+        self.apply_to_index(&hunk.patch_for_lines(file_path, true, lines))
+            .context("Failed to unstage lines")
     }
 
-    pub fn unstage_hunk(&self, _file_path: &Path, _hunk: &GitHunk) -> Result<()> {
-        // This is more complex and would require patching
-        // For now, return an error indicating it's not implemented
-        anyhow::bail!("Unstaging individual hunks is not yet implemented")
+    fn apply_to_index(&self, patch: &str) -> Result<()> {
+        let diff = Diff::from_buffer(patch.as_bytes())?;
+        self.repo.apply(&diff, ApplyLocation::Index, None)?;
+        Ok(())
+    }
+}
+
+fn copy_file(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
     }
+    fs::copy(from, to)
+        .with_context(|| format!("Failed to save {} to the trash", from.display()))?;
+    Ok(())
 }
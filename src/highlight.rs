@@ -0,0 +1,175 @@
+use crate::config::ThemeName;
+use crate::diff_layout::Segment;
+use crate::git::DiffLine;
+use ratatui::style::Color;
+use std::path::Path;
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
+
+// Files larger than this are shown without syntax colors
+const MAX_HIGHLIGHT_BYTES: usize = 1 << 20;
+
+/// A run of text in one syntax color.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub text: String,
+    pub color: Color,
+}
+
+fn syntaxes() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn themes() -> &'static ThemeSet {
+    static THEMES: OnceLock<ThemeSet> = OnceLock::new();
+    THEMES.get_or_init(ThemeSet::load_defaults)
+}
+
+/// The syntax theme that goes with a UI theme. `mono` keeps diffs uncolored.
+fn syntax_theme(theme: ThemeName) -> Option<&'static Theme> {
+    let name = match theme {
+        ThemeName::Desert => "base16-eighties.dark",
+        ThemeName::Ocean => "base16-ocean.dark",
+        ThemeName::Mono => return None,
+    };
+    themes().themes.get(name)
+}
+
+fn syntax_for(path: &Path) -> Option<&'static SyntaxReference> {
+    let extension = path.extension()?.to_str()?;
+    // The bundled syntaxes have no TypeScript; JavaScript covers most of it
+    let extension = match extension {
+        "ts" | "tsx" | "mts" | "cts" => "js",
+        other => other,
+    };
+    syntaxes().find_syntax_by_extension(extension)
+}
+
+/// Highlight a whole file, one token list per line. Parsing it from the top
+/// keeps block comments, strings and other constructs spanning lines right.
+/// `None` when the theme has no colors, the file type is unknown or the file
+/// is too large.
+pub fn highlight_file(path: &Path, content: &str, theme: ThemeName) -> Option<Vec<Vec<Token>>> {
+    if content.len() > MAX_HIGHLIGHT_BYTES {
+        return None;
+    }
+    let mut highlighter = HighlightLines::new(syntax_for(path)?, syntax_theme(theme)?);
+    let mut lines = Vec::new();
+    for line in LinesWithEndings::from(content) {
+        let ranges = highlighter.highlight_line(line, syntaxes()).ok()?;
+        let tokens = ranges
+            .into_iter()
+            .filter_map(|(style, text)| {
+                let text = text.trim_end_matches(['\n', '\r']);
+                let color = style.foreground;
+                (!text.is_empty()).then(|| Token {
+                    text: text.to_string(),
+                    color: Color::Rgb(color.r, color.g, color.b),
+                })
+            })
+            .collect();
+        lines.push(tokens);
+    }
+    Some(lines)
+}
+
+/// Both versions of a diffed file, highlighted whole, for looking up the
+/// colors of single diff lines.
+#[derive(Debug, Clone, Default)]
+pub struct DiffHighlights {
+    old: Option<Vec<Vec<Token>>>,
+    new: Option<Vec<Vec<Token>>>,
+}
+
+impl DiffHighlights {
+    pub fn new(old: Option<(&Path, &str)>, new: Option<(&Path, &str)>, theme: ThemeName) -> Self {
+        let highlight = |side: Option<(&Path, &str)>| {
+            let (path, content) = side?;
+            highlight_file(path, content, theme)
+        };
+        Self {
+            old: highlight(old),
+            new: highlight(new),
+        }
+    }
+
+    /// The tokens of line `number` of the old version, if it reads `text`.
+    pub fn old_line(&self, number: u32, text: &str) -> Option<&[Token]> {
+        find_line(self.old.as_ref()?, number, text)
+    }
+
+    /// The tokens of line `number` of the new version, if it reads `text`.
+    pub fn new_line(&self, number: u32, text: &str) -> Option<&[Token]> {
+        find_line(self.new.as_ref()?, number, text)
+    }
+
+    /// The tokens of a unified diff line: removed lines from the old version,
+    /// added and context lines from the new one.
+    pub fn line(&self, line: &DiffLine) -> Option<&[Token]> {
+        let text = line.content.trim_end_matches(['\n', '\r']);
+        match (line.origin, line.old_lineno, line.new_lineno) {
+            ('-', Some(old), _) => self.old_line(old, text),
+            ('+' | ' ', _, Some(new)) => self.new_line(new, text),
+            _ => None,
+        }
+    }
+}
+
+// A diff taken with whitespace ignored, or of a file changed since, can
+// disagree with the text read for highlighting; those lines stay plain
+fn find_line<'a>(lines: &'a [Vec<Token>], number: u32, text: &str) -> Option<&'a [Token]> {
+    let tokens = lines.get(usize::try_from(number).ok()?.checked_sub(1)?)?;
+    let mut rest = text;
+    for token in tokens {
+        rest = rest.strip_prefix(token.text.as_str())?;
+    }
+    rest.is_empty().then_some(tokens.as_slice())
+}
+
+/// Split syntax tokens where `word_diff` segments of the same text start and
+/// end, pairing each piece with whether its words changed.
+pub fn mark_changes(tokens: &[Token], segments: &[Segment]) -> Vec<(Token, bool)> {
+    let mut marked = Vec::new();
+    let mut segments = segments
+        .iter()
+        .map(|segment| (segment.text.chars().count(), segment.changed));
+    let mut current = segments.next();
+    for token in tokens {
+        let mut rest = token.text.as_str();
+        while !rest.is_empty() {
+            let Some((left, changed)) = current.as_mut() else {
+                marked.push((
+                    Token {
+                        text: rest.to_string(),
+                        color: token.color,
+                    },
+                    false,
+                ));
+                break;
+            };
+            if *left == 0 {
+                current = segments.next();
+                continue;
+            }
+            let take = rest.chars().count().min(*left);
+            let split = rest
+                .char_indices()
+                .nth(take)
+                .map_or(rest.len(), |(index, _)| index);
+            marked.push((
+                Token {
+                    text: rest[..split].to_string(),
+                    color: token.color,
+                },
+                *changed,
+            ));
+            *left -= take;
+            rest = &rest[split..];
+        }
+    }
+    marked
+}
@@ -1,6 +1,13 @@
 pub mod animations;
+pub mod config;
+pub mod diff_layout;
 pub mod file_tree;
 pub mod git;
+pub mod highlight;
 pub mod logger;
+pub mod merge;
+pub mod policy_diff;
+pub mod scope;
 pub mod state;
+pub mod text_input;
 pub mod ui;
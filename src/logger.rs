@@ -1,16 +1,26 @@
 use chrono::Utc;
 use std::fs::OpenOptions;
 use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+static LOG_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Redirect logging away from the default `logs/unified.log`; only the first call takes effect.
+pub fn set_log_file(path: PathBuf) {
+    let _ = LOG_FILE.set(path);
+}
 
 pub fn log_to_file(message: &str) {
     let timestamp = Utc::now().format("%Y-%m-%d %H:%M:%S%.3f");
     let log_message = format!("[{timestamp}] {message}\n");
 
-    if let Ok(mut file) = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open("logs/unified.log")
-    {
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(
+        LOG_FILE
+            .get()
+            .map(PathBuf::as_path)
+            .unwrap_or(Path::new("logs/unified.log")),
+    ) {
         let _ = file.write_all(log_message.as_bytes());
         let _ = file.flush();
     }
@@ -1,10 +1,17 @@
 #![allow(dead_code)]
 
 mod animations;
+mod config;
+mod diff_layout;
 mod file_tree;
 mod git;
+mod highlight;
 mod logger;
+mod merge;
+mod policy_diff;
+mod scope;
 mod state;
+mod text_input;
 mod ui;
 
 use anyhow::Result;
@@ -26,6 +33,12 @@ struct Args {
 
     #[arg(short, long, help = "Additional ignore patterns")]
     ignore: Vec<String>,
+
+    #[arg(
+        long,
+        help = "State file format (yaml, json or toml); an existing state file is converted"
+    )]
+    format: Option<state::StateFormat>,
 }
 
 #[derive(Subcommand, Debug)]
@@ -34,6 +47,256 @@ enum Commands {
     Init,
     #[command(about = "Show current ignore patterns")]
     ShowIgnore,
+    #[command(about = "Print the JSON Schema of the state file")]
+    Schema,
+    #[command(about = "Three-way merge of .icaros files (used as a git merge driver)")]
+    MergeDriver {
+        #[arg(help = "Common ancestor version (%O)")]
+        base: PathBuf,
+        #[arg(help = "Current branch version, overwritten with the result (%A)")]
+        ours: PathBuf,
+        #[arg(help = "Other branch version (%B)")]
+        theirs: PathBuf,
+    },
+    #[command(about = "Manage git integration hooks")]
+    Hook {
+        #[command(subcommand)]
+        action: HookCommands,
+    },
+    #[command(about = "Lock files or directories (paths or globs)")]
+    Lock {
+        #[arg(
+            required = true,
+            help = "Paths or glob patterns, relative to the current directory"
+        )]
+        targets: Vec<String>,
+        #[arg(long, help = "Print the result as JSON")]
+        json: bool,
+    },
+    #[command(about = "Unlock files or directories (paths or globs)")]
+    Unlock {
+        #[arg(
+            required = true,
+            help = "Paths or glob patterns, relative to the current directory"
+        )]
+        targets: Vec<String>,
+        #[arg(long, help = "Print the result as JSON")]
+        json: bool,
+    },
+    #[command(about = "Allow creating new files inside locked directories")]
+    AllowCreate {
+        #[arg(required = true, help = "Locked directories")]
+        dirs: Vec<String>,
+        #[arg(long, help = "Stop allowing new files instead")]
+        remove: bool,
+        #[arg(long, help = "Print the result as JSON")]
+        json: bool,
+    },
+    #[command(about = "Show the current lock patterns")]
+    Status {
+        #[arg(long, help = "Print the status as JSON")]
+        json: bool,
+    },
+    #[command(about = "List files and their lock state")]
+    Ls {
+        #[arg(long, help = "Only list locked files")]
+        locked: bool,
+        #[arg(long, help = "Print the list as JSON")]
+        json: bool,
+    },
+    #[command(
+        about = "Check whether paths may be changed; exits with 1 if any is locked, unless enforcement is warn"
+    )]
+    Check {
+        #[arg(required = true, help = "Paths relative to the current directory")]
+        paths: Vec<PathBuf>,
+        #[arg(
+            long,
+            help = "Agent whose profile applies instead of the active one (default: $ICAROS_AGENT)"
+        )]
+        agent: Option<String>,
+        #[arg(long, help = "Print the result as JSON")]
+        json: bool,
+    },
+    #[command(about = "Manage lock profiles")]
+    Profile {
+        #[command(subcommand)]
+        action: ProfileCommands,
+        #[arg(long, global = true, help = "Print the result as JSON")]
+        json: bool,
+    },
+    #[command(about = "Bind agents to the profiles that govern them")]
+    Agent {
+        #[command(subcommand)]
+        action: AgentCommands,
+        #[arg(long, global = true, help = "Print the result as JSON")]
+        json: bool,
+    },
+    #[command(about = "Lock everything except the files a task needs, until the quest ends")]
+    Quest {
+        #[command(subcommand)]
+        action: QuestCommands,
+        #[arg(long, global = true, help = "Print the result as JSON")]
+        json: bool,
+    },
+    #[command(about = "Unlock only the files changed in a revision range or the working tree")]
+    Scope {
+        #[arg(help = "Revision range such as main..HEAD (default: uncommitted changes)")]
+        range: Option<String>,
+        #[arg(long, help = "Unlock the directories holding changed files")]
+        widen: bool,
+        #[arg(
+            long,
+            help = "Allow new files in the directories where files were added"
+        )]
+        allow_create: bool,
+        #[arg(
+            long,
+            value_name = "NAME",
+            conflicts_with = "quest",
+            help = "Save the scope as a profile"
+        )]
+        profile: Option<String>,
+        #[arg(long, value_name = "NAME", help = "Start a quest with the scope")]
+        quest: Option<String>,
+        #[arg(long, help = "Replace an existing profile with the same name")]
+        force: bool,
+        #[arg(long, help = "Print the result as JSON")]
+        json: bool,
+    },
+    #[command(about = "Inspect the user configuration")]
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommands,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ProfileCommands {
+    #[command(about = "List saved profiles")]
+    List,
+    #[command(about = "Show the rules of a profile, including inherited ones")]
+    Show { name: String },
+    #[command(about = "Save the current locks as a profile and make it active")]
+    Save {
+        name: String,
+        #[arg(short, long, help = "Profile description")]
+        description: Option<String>,
+        #[arg(
+            short,
+            long,
+            help = "Base profile to inherit from; only rules it doesn't provide are saved"
+        )]
+        extends: Vec<String>,
+    },
+    #[command(about = "Apply a profile's locks, or layer several profiles in order")]
+    Switch {
+        #[arg(required = true)]
+        names: Vec<String>,
+    },
+    #[command(about = "Delete a profile")]
+    Delete { name: String },
+    #[command(about = "Rename a profile")]
+    Rename { from: String, to: String },
+    #[command(about = "Copy a profile under a new name")]
+    Copy { from: String, to: String },
+    #[command(about = "Switch to the profile the branch rules select for the current branch")]
+    Sync,
+    #[command(about = "Show which paths change when switching from one profile to another")]
+    Diff { from: String, to: String },
+    #[command(about = "Write a profile, inherited rules included, to a standalone file")]
+    Export {
+        name: String,
+        #[arg(help = "Output file (.yaml, .json or .toml); prints YAML if omitted")]
+        file: Option<PathBuf>,
+        #[arg(
+            long,
+            conflicts_with = "file",
+            help = "Save into the user profile library"
+        )]
+        library: bool,
+        #[arg(long, help = "Overwrite an existing file")]
+        force: bool,
+    },
+    #[command(about = "Import a profile from a file or the user profile library")]
+    Import {
+        #[arg(help = "Profile file, or the name of a profile in the library")]
+        source: String,
+        #[arg(
+            long = "as",
+            value_name = "NAME",
+            help = "Name for the imported profile"
+        )]
+        name: Option<String>,
+        #[arg(long, help = "Keep patterns that match nothing in this repository")]
+        keep_unresolved: bool,
+        #[arg(long, help = "Replace an existing profile with the same name")]
+        force: bool,
+    },
+    #[command(about = "List the profiles in the user profile library")]
+    Library,
+}
+
+#[derive(Subcommand, Debug)]
+enum AgentCommands {
+    #[command(about = "List agents and their profiles")]
+    List,
+    #[command(about = "Bind an agent to a profile")]
+    Bind { agent: String, profile: String },
+    #[command(about = "Remove an agent's binding")]
+    Unbind { agent: String },
+}
+
+#[derive(Subcommand, Debug)]
+enum QuestCommands {
+    #[command(about = "Lock the whole root except these paths, remembering the current locks")]
+    Start {
+        #[arg(
+            required = true,
+            help = "Paths or glob patterns the quest may change, relative to the current directory"
+        )]
+        targets: Vec<String>,
+        #[arg(short, long, help = "Quest name (default: the first path's name)")]
+        name: Option<String>,
+    },
+    #[command(about = "End the quest and restore the locks and profiles from before it")]
+    End,
+    #[command(about = "Show the quest in progress")]
+    Status,
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommands {
+    #[command(about = "Print the effective settings (user config merged with .icaros)")]
+    Show,
+}
+
+#[derive(Subcommand, Debug)]
+enum HookCommands {
+    #[command(about = "Install git integration into the current repository")]
+    Install {
+        #[arg(
+            long,
+            help = "Register the .icaros merge driver in .git/config and .gitattributes"
+        )]
+        merge_driver: bool,
+        #[arg(
+            long,
+            help = "Add a post-checkout hook that applies the branch-to-profile rules"
+        )]
+        post_checkout: bool,
+        #[arg(
+            long,
+            help = "Add a pre-commit hook that refuses commits changing locked paths"
+        )]
+        pre_commit: bool,
+        #[arg(
+            long,
+            requires = "pre_commit",
+            help = "Agent whose profile the pre-commit hook checks against (default: $ICAROS_AGENT when it runs)"
+        )]
+        agent: Option<String>,
+    },
 }
 
 fn main() -> Result<()> {
@@ -41,43 +304,146 @@ fn main() -> Result<()> {
 
     let root_path = args.path.canonicalize()?;
 
+    // A broken user config only stops `config show`, which is there to inspect it
+    let user_config = match config::UserConfig::load() {
+        Ok(user_config) => user_config,
+        Err(e) if matches!(args.command, Some(Commands::Config { .. })) => return Err(e),
+        Err(e) => {
+            eprintln!("Warning: {e:#}; using the default user settings");
+            config::UserConfig::default()
+        }
+    };
+    if let Some(log_file) = &user_config.log_file {
+        logger::set_log_file(log_file.clone());
+    }
+
     match args.command {
         Some(Commands::Init) => {
-            init_command(&root_path)?;
+            init_command(&root_path, &user_config)?;
             Ok(())
         }
         Some(Commands::ShowIgnore) => {
-            show_ignore_command(&root_path, &args)?;
+            show_ignore_command(&root_path, &args, &user_config)?;
             Ok(())
         }
+        Some(Commands::Schema) => {
+            let schema = state::state_json_schema();
+            println!("{}", serde_json::to_string_pretty(&schema)?);
+            Ok(())
+        }
+        Some(Commands::MergeDriver {
+            ref base,
+            ref ours,
+            ref theirs,
+        }) => merge_driver_command(base, ours, theirs),
+        Some(Commands::Hook {
+            action:
+                HookCommands::Install {
+                    merge_driver,
+                    post_checkout,
+                    pre_commit,
+                    ref agent,
+                },
+        }) => hook_install_command(
+            &root_path,
+            &args,
+            merge_driver,
+            post_checkout,
+            pre_commit.then_some(agent.as_deref()),
+        ),
+        Some(Commands::Check {
+            ref paths,
+            ref agent,
+            json,
+        }) => check_command(&root_path, &args, &user_config, paths, agent, json),
+        Some(Commands::Agent { ref action, json }) => {
+            agent_command(&root_path, &args, action, json)
+        }
+        Some(Commands::Scope {
+            ref range,
+            widen,
+            allow_create,
+            ref profile,
+            ref quest,
+            force,
+            json,
+        }) => {
+            let output = ScopeOutput {
+                profile: profile.as_deref(),
+                quest: quest.as_deref(),
+                force,
+            };
+            let options = scope::ScopeOptions {
+                widen,
+                allow_create,
+            };
+            scope_command(
+                &root_path,
+                &args,
+                &user_config,
+                range.as_deref(),
+                options,
+                output,
+                json,
+            )
+        }
+        Some(Commands::Quest { ref action, json }) => {
+            quest_command(&root_path, &args, &user_config, action, json)
+        }
+        Some(Commands::Profile { ref action, json }) => {
+            profile_command(&root_path, &args, &user_config, action, json)
+        }
+        Some(Commands::Config {
+            action: ConfigCommands::Show,
+        }) => config_show_command(&root_path, &args, &user_config),
+        Some(Commands::Lock { ref targets, json }) => {
+            lock_command(&root_path, &args, &user_config, targets, true, json)
+        }
+        Some(Commands::Unlock { ref targets, json }) => {
+            lock_command(&root_path, &args, &user_config, targets, false, json)
+        }
+        Some(Commands::AllowCreate {
+            ref dirs,
+            remove,
+            json,
+        }) => allow_create_command(&root_path, &args, &user_config, dirs, !remove, json),
+        Some(Commands::Status { json }) => status_command(&root_path, &args, &user_config, json),
+        Some(Commands::Ls { locked, json }) => {
+            ls_command(&root_path, &args, &user_config, locked, json)
+        }
         None => {
             let state_file = args.state_file.unwrap_or_else(|| root_path.join(".icaros"));
+            if let Some(format) = args.format {
+                convert_state_file(&state_file, &root_path, format)?;
+            }
 
-            // Load ignore patterns from state file, or use defaults plus CLI args
-            let mut ignore_patterns = if state_file.exists() {
-                if let Ok(state) = state::AppState::load_from_file(&state_file) {
-                    state.ignore_patterns
-                } else {
-                    state::default_ignore_patterns()
-                }
-            } else {
-                state::default_ignore_patterns()
-            };
+            // New repositories start from the user's default profile
+            if !state_file.exists() && user_config.default_profile.is_some() {
+                config::new_repo_state(root_path.clone(), &user_config)
+                    .save_to_file(&state_file)?;
+            }
 
-            // Add CLI ignore patterns
+            // Ignore patterns from the state file (or defaults) and user config, plus CLI args
+            let repo_state = state::AppState::load_from_file(&state_file).ok();
+            let mut ignore_patterns =
+                config::Settings::resolve(&user_config, repo_state.as_ref()).ignore_patterns;
             ignore_patterns.extend(args.ignore);
 
             let tree = file_tree::build_tree(&root_path, &ignore_patterns, false)?;
 
             let mut app = ui::App::new(tree, state_file.clone(), root_path.clone());
+            for warning in duplicate_key_warnings(&app.settings) {
+                eprintln!("Warning: {warning}");
+            }
 
-            if state_file.exists() {
-                if let Ok(state) = state::AppState::load_from_file(&state_file) {
-                    restore_state(&mut app, &state);
-                }
+            if let Some(state) = &repo_state {
+                app.restore_state(state);
             }
+            app.follow_branch();
+            app.take_session_checkpoint();
 
-            let final_app = ui::run_ui(app)?;
+            let mut final_app = ui::run_ui(app)?;
+            final_app.drop_session_checkpoint();
 
             println!("\nState file: {}", state_file.display());
             println!("Locked files: {}", final_app.get_locked_files().len());
@@ -87,7 +453,11 @@ fn main() -> Result<()> {
     }
 }
 
-fn show_ignore_command(root_path: &Path, args: &Args) -> Result<()> {
+fn show_ignore_command(
+    root_path: &Path,
+    args: &Args,
+    user_config: &config::UserConfig,
+) -> Result<()> {
     let default_state_file = root_path.join(".icaros");
     let state_file = args.state_file.as_ref().unwrap_or(&default_state_file);
 
@@ -95,19 +465,29 @@ fn show_ignore_command(root_path: &Path, args: &Args) -> Result<()> {
     println!();
 
     // Load patterns from state file or use defaults
-    let ignore_patterns = if state_file.exists() {
+    let repo_state = if state_file.exists() {
         if let Ok(state) = state::AppState::load_from_file(state_file) {
             println!("Source: .icaros file");
-            state.ignore_patterns
+            Some(state)
         } else {
             println!("Source: defaults (failed to read .icaros file)");
-            state::default_ignore_patterns()
+            None
         }
     } else {
         println!("Source: defaults (no .icaros file found)");
-        state::default_ignore_patterns()
+        None
     };
 
+    // Add global patterns from the user config
+    if !user_config.ignore_patterns.is_empty() {
+        println!(
+            "User config patterns: {} additional patterns",
+            user_config.ignore_patterns.len()
+        );
+    }
+    let ignore_patterns =
+        config::Settings::resolve(user_config, repo_state.as_ref()).ignore_patterns;
+
     // Add CLI patterns if any
     let mut all_patterns = ignore_patterns;
     if !args.ignore.is_empty() {
@@ -152,160 +532,1265 @@ fn show_ignore_command(root_path: &Path, args: &Args) -> Result<()> {
     Ok(())
 }
 
-fn restore_state(app: &mut ui::App, state: &state::AppState) {
-    eprintln!("Restoring state...");
-    eprintln!("  locked_patterns: {}", state.locked_patterns.join(", "));
-    eprintln!(
-        "  unlocked_patterns: {}",
-        state.unlocked_patterns.join(", ")
-    );
+/// Rewrite (or create) the state file in the requested format; later saves keep it.
+fn convert_state_file(
+    state_file: &Path,
+    root_path: &Path,
+    format: state::StateFormat,
+) -> Result<()> {
+    let state = if state_file.exists() {
+        state::AppState::load_from_file(state_file)?
+    } else {
+        state::AppState::new(root_path.to_path_buf())
+    };
+    state.save_to_file_as(state_file, format)
+}
+
+fn merge_driver_command(base: &Path, ours: &Path, theirs: &Path) -> Result<()> {
+    let ours_state = state::AppState::load_from_file(ours)?;
+    let theirs_state = state::AppState::load_from_file(theirs)?;
+
+    // Git passes an empty ancestor when both branches added the file
+    let base_state = if fs::metadata(base)?.len() == 0 {
+        let mut empty = state::AppState::new(ours_state.root_path.clone());
+        empty.unlocked_patterns.clear();
+        empty
+    } else {
+        state::AppState::load_from_file(base)?
+    };
+
+    let outcome = merge::merge_states(&base_state, &ours_state, &theirs_state);
+    outcome.state.save_to_file(ours)?;
+
+    if !outcome.conflicts.is_empty() {
+        eprintln!("icaros: {} conflict(s):", outcome.conflicts.len());
+        for conflict in &outcome.conflicts {
+            eprintln!("  {conflict}");
+        }
+        // Non-zero exit tells git the merge needs manual resolution
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn hook_install_command(
+    root_path: &Path,
+    args: &Args,
+    merge_driver: bool,
+    post_checkout: bool,
+    // `Some` for the pre-commit hook, with the agent it checks for
+    pre_commit: Option<Option<&str>>,
+) -> Result<()> {
+    if !merge_driver && !post_checkout && pre_commit.is_none() {
+        anyhow::bail!(
+            "Nothing to install: pass --merge-driver, --post-checkout and/or --pre-commit"
+        );
+    }
+
+    let git = git::GitManager::new(root_path)?;
+    let default_state_file = root_path.join(".icaros");
+    let state_file = args
+        .state_file
+        .clone()
+        .unwrap_or_else(|| default_state_file.clone());
+    let state_file = std::path::absolute(state_file)?;
+
+    let workdir = git
+        .workdir()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| root_path.to_path_buf())
+        .canonicalize()?;
+    let relative_to_workdir = |path: &Path| {
+        path.strip_prefix(&workdir)
+            .map(|relative| relative.to_string_lossy().replace('\\', "/"))
+            .ok()
+    };
+
+    if merge_driver {
+        let pattern = relative_to_workdir(&state_file).unwrap_or_else(|| ".icaros".to_string());
+        git.install_merge_driver(&pattern)?;
+        println!("Registered icaros merge driver for '{pattern}'");
+        println!("  .git/config:    merge.icaros.driver = icaros merge-driver %O %A %B");
+        println!("  .gitattributes: {pattern} merge=icaros");
+    }
+
+    // Hooks run from the top of the working tree
+    let mut icaros = "icaros".to_string();
+    let root = (root_path != workdir).then(|| {
+        relative_to_workdir(root_path).unwrap_or_else(|| root_path.to_string_lossy().to_string())
+    });
+    if let Some(root) = &root {
+        icaros.push_str(&format!(" {}", shell_quote(root)));
+    }
+    if state_file != default_state_file {
+        let state_file = relative_to_workdir(&state_file)
+            .unwrap_or_else(|| state_file.to_string_lossy().to_string());
+        icaros.push_str(&format!(" --state-file {}", shell_quote(&state_file)));
+    }
+
+    if post_checkout {
+        let command = format!("{icaros} profile sync");
+        let hook = git.install_post_checkout_hook(&command)?;
+        println!("Installed post-checkout hook: {}", hook.display());
+        println!("  runs: {command}");
+    }
+
+    if let Some(agent) = pre_commit {
+        let mut command = format!("{icaros} check");
+        if let Some(agent) = agent {
+            command.push_str(&format!(" --agent {}", shell_quote(agent)));
+        }
+        let relative_to = root.as_deref().map(shell_quote);
+        let hook = git.install_pre_commit_hook(&command, relative_to.as_deref())?;
+        println!("Installed pre-commit hook: {}", hook.display());
+        println!("  runs: {command} on the staged paths");
+    }
+
+    Ok(())
+}
+
+fn shell_quote(value: &str) -> String {
+    if value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "/._-".contains(c))
+    {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+/// The agent selected with `--agent` or `ICAROS_AGENT`, if any. The variable
+/// may be set for every repository an agent visits, so in one without agents
+/// it is ignored with a warning rather than failing every check.
+fn selected_agent(agent: &Option<String>, state: &state::AppState) -> Option<String> {
+    if agent.is_some() {
+        return agent.clone();
+    }
+    let agent = std::env::var("ICAROS_AGENT")
+        .ok()
+        .filter(|agent| !agent.is_empty())?;
+    if state.agents.is_empty() {
+        eprintln!(
+            "Warning: ICAROS_AGENT is '{agent}', but no agents are configured; using the active profile"
+        );
+        return None;
+    }
+    Some(agent)
+}
+
+fn check_command(
+    root_path: &Path,
+    args: &Args,
+    user_config: &config::UserConfig,
+    paths: &[PathBuf],
+    agent: &Option<String>,
+    json: bool,
+) -> Result<()> {
+    let state_file = args
+        .state_file
+        .clone()
+        .unwrap_or_else(|| root_path.join(".icaros"));
+    let mut state = if state_file.exists() {
+        state::AppState::load_from_file(&state_file)?
+    } else {
+        state::AppState::new(root_path.to_path_buf())
+    };
+
+    // An agent has its own profile; otherwise evaluate against the profile of
+    // the branch actually checked out. Only `profile sync` saves that switch.
+    let agent = selected_agent(agent, &state);
+    let (policy, profile) = match &agent {
+        Some(agent) => (state.agent_policy(agent)?, state.agents.get(agent).cloned()),
+        None => {
+            if let Some(branch) = git::GitManager::new(root_path)
+                .ok()
+                .and_then(|git| git.current_branch())
+            {
+                state.follow_branch(&branch);
+            }
+            (state.current_policy(), state.active_profile.clone())
+        }
+    };
+
+    let base = target_base(root_path)?;
+    let mut results = Vec::new();
+    for path in paths {
+        let absolute = base.join(path);
+        let absolute = absolute
+            .canonicalize()
+            .unwrap_or_else(|_| state::normalize_path(&absolute));
+        let Ok(relative) = absolute.strip_prefix(root_path) else {
+            anyhow::bail!("'{}' is outside {}", path.display(), root_path.display());
+        };
 
-    // First restore expanded dirs
-    for expanded_dir in &state.expanded_dirs {
-        restore_expanded(&mut app.tree, expanded_dir);
+        let status = if !policy.is_locked(relative) {
+            "unlocked"
+        } else if !absolute.exists() && policy.can_create(relative) {
+            "create-allowed"
+        } else {
+            "locked"
+        };
+        results.push((display_relative(root_path, &absolute), status));
     }
+    let enforcement = config::Settings::resolve(user_config, Some(&state)).enforcement;
 
-    // Apply locked patterns and track explicitly locked paths
-    app.explicitly_locked_paths.clear();
-    for pattern in &state.locked_patterns {
-        if pattern == "**" {
-            eprintln!("  Locking entire tree with pattern: {pattern}");
-            app.explicitly_locked_paths.push(state.root_path.clone());
-        } else if let Some(path) = pattern_to_path(&state.root_path, pattern) {
-            eprintln!("  Locking path: {path:?} from pattern: {pattern}");
-            app.explicitly_locked_paths.push(path);
+    if json {
+        let entries: Vec<serde_json::Value> = results
+            .iter()
+            .map(|(path, status)| serde_json::json!({ "path": path, "status": status }))
+            .collect();
+        let mut result = serde_json::json!({
+            "active_profile": profile,
+            "enforcement": enforcement,
+            "paths": entries,
+        });
+        if let Some(agent) = agent {
+            result["agent"] = agent.into();
+        }
+        println!("{}", serde_json::to_string_pretty(&result)?);
+    } else {
+        for (path, status) in &results {
+            println!("{status:<15}{path}");
         }
     }
 
-    // Then apply unlocked patterns (exceptions to locked patterns)
-    app.explicitly_unlocked_paths.clear();
-    for pattern in &state.unlocked_patterns {
-        if let Some(path) = pattern_to_path(&state.root_path, pattern) {
-            eprintln!("  Explicit unlock path: {path:?} from pattern: {pattern}");
-            app.explicitly_unlocked_paths.push(path);
+    let locked = results
+        .iter()
+        .filter(|(_, status)| *status == "locked")
+        .count();
+    match enforcement {
+        _ if locked == 0 => {}
+        // Report, but let the caller go ahead
+        config::EnforcementMode::Warn => {
+            eprintln!("Warning: {locked} path(s) are locked; enforcement is warn, so not failing");
         }
+        config::EnforcementMode::Block => std::process::exit(1),
+    }
+    Ok(())
+}
+
+fn config_show_command(
+    root_path: &Path,
+    args: &Args,
+    user_config: &config::UserConfig,
+) -> Result<()> {
+    let state_file = args
+        .state_file
+        .clone()
+        .unwrap_or_else(|| root_path.join(".icaros"));
+    let repo_state = if state_file.exists() {
+        Some(state::AppState::load_from_file(&state_file)?)
+    } else {
+        None
+    };
+
+    match config::UserConfig::path() {
+        Some(path) if path.exists() => println!("# User config: {}", path.display()),
+        Some(path) => println!("# User config: {} (not found)", path.display()),
+        None => println!("# User config: none"),
+    }
+    if repo_state.is_some() {
+        println!("# Repository overrides: {}", state_file.display());
+    }
+    for action in config::Settings::unknown_keybindings(user_config, repo_state.as_ref()) {
+        println!("# Warning: unknown keybinding action '{action}' is ignored");
+    }
+
+    let settings = config::Settings::resolve(user_config, repo_state.as_ref());
+    for warning in duplicate_key_warnings(&settings) {
+        println!("# Warning: {warning}");
+    }
+    print!("{}", serde_yaml::to_string(&settings)?);
+
+    Ok(())
+}
+
+fn duplicate_key_warnings(settings: &config::Settings) -> Vec<String> {
+    settings
+        .duplicate_keybindings
+        .iter()
+        .map(|(key, first, second)| {
+            format!("'{key}' is bound to both {first} and {second}; only one of them will work")
+        })
+        .collect()
+}
+
+/// The File Guardian for `root_path` without a terminal, so the scriptable commands
+/// share the TUI's lock rules and write the same canonical state file.
+fn load_guardian(
+    root_path: &Path,
+    args: &Args,
+    user_config: &config::UserConfig,
+) -> Result<ui::App> {
+    let state_file = args
+        .state_file
+        .clone()
+        .unwrap_or_else(|| root_path.join(".icaros"));
+    let repo_state = if state_file.exists() {
+        Some(state::AppState::load_from_file(&state_file)?)
+    } else {
+        None
+    };
+
+    let mut ignore_patterns =
+        config::Settings::resolve(user_config, repo_state.as_ref()).ignore_patterns;
+    ignore_patterns.extend(args.ignore.iter().cloned());
+    let tree = file_tree::build_tree(root_path, &ignore_patterns, true)?;
+
+    let mut app = ui::App::new(tree, state_file, root_path.to_path_buf());
+    if let Some(state) = &repo_state {
+        app.restore_state(state);
     }
+    Ok(app)
+}
+
+/// Resolve command line targets (paths or globs) to paths in the tree.
+fn resolve_targets(app: &ui::App, targets: &[String]) -> Result<Vec<PathBuf>> {
+    let root = &app.root_path;
+    let base = target_base(root)?;
+
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for target in targets {
+        // `dir/**` means the directory itself, as in the state file
+        let literal = target.strip_suffix("/**").unwrap_or(target);
+        let candidate = base.join(literal);
+        if !glob_chars(literal) && candidate.exists() {
+            let path = candidate.canonicalize()?;
+            if !path.starts_with(root) {
+                anyhow::bail!("'{target}' is outside {}", root.display());
+            }
+            paths.push(path);
+            continue;
+        }
+
+        let relative_base = base.strip_prefix(root).unwrap_or(Path::new(""));
+        let pattern = state::normalize_path(&relative_base.join(target));
+        let pattern = glob::Pattern::new(&pattern.to_string_lossy())?;
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
 
-    // Finally restore allow_create patterns
-    for pattern in &state.allow_create_patterns {
-        if let Some(path) = pattern_to_path(&state.root_path, pattern) {
-            restore_allow_create(&mut app.tree, &path);
+        let before = paths.len();
+        collect_glob_matches(&app.tree, root, &pattern, options, &mut paths);
+        if paths.len() == before {
+            anyhow::bail!("No files match '{target}'");
         }
     }
 
-    // Clean up any conflicts or duplicates
-    app.cleanup_lock_lists();
+    // Targets can name the same path more than once, and not always next to each other
+    let mut seen = std::collections::HashSet::new();
+    paths.retain(|path| seen.insert(path.clone()));
+    Ok(paths)
+}
+
+/// Directory command line paths are relative to: the current directory when it
+/// is inside the root, otherwise the root itself.
+fn target_base(root: &Path) -> Result<PathBuf> {
+    let cwd = std::env::current_dir()?.canonicalize()?;
+    Ok(if cwd.starts_with(root) {
+        cwd
+    } else {
+        root.to_path_buf()
+    })
+}
+
+fn glob_chars(target: &str) -> bool {
+    target.contains(['*', '?', '['])
+}
 
-    // Apply the explicit locks to the tree
-    app.reapply_explicit_locks();
+/// Collect the outermost nodes matching `pattern`; the contents of a matched
+/// directory are covered by the directory itself.
+fn collect_glob_matches(
+    node: &file_tree::TreeNode,
+    root: &Path,
+    pattern: &glob::Pattern,
+    options: glob::MatchOptions,
+    matches: &mut Vec<PathBuf>,
+) {
+    for child in &node.children {
+        let relative = child.path.strip_prefix(root).unwrap_or(&child.path);
+        if pattern.matches_path_with(relative, options) {
+            matches.push(child.path.clone());
+        } else {
+            collect_glob_matches(child, root, pattern, options, matches);
+        }
+    }
+}
 
-    app.update_items();
-    eprintln!("State restoration complete.");
+fn display_relative(root: &Path, path: &Path) -> String {
+    match path.strip_prefix(root) {
+        Ok(relative) if relative.as_os_str().is_empty() => ".".to_string(),
+        Ok(relative) => relative.display().to_string(),
+        Err(_) => path.display().to_string(),
+    }
 }
 
-fn pattern_to_path(root: &std::path::Path, pattern: &str) -> Option<std::path::PathBuf> {
-    if pattern == "**" {
-        // Special case: root directory is locked
-        Some(root.to_path_buf())
-    } else if pattern.ends_with("/**") {
-        // Directory pattern
-        let clean_pattern = pattern.trim_end_matches("/**");
-        if clean_pattern.is_empty() {
-            Some(root.to_path_buf())
+fn lock_command(
+    root_path: &Path,
+    args: &Args,
+    user_config: &config::UserConfig,
+    targets: &[String],
+    lock: bool,
+    json: bool,
+) -> Result<()> {
+    let mut app = load_guardian(root_path, args, user_config)?;
+    let paths = resolve_targets(&app, targets)?;
+
+    let mut changed = Vec::new();
+    let mut unchanged = Vec::new();
+    for path in &paths {
+        let name = display_relative(root_path, path);
+        if app.is_path_effectively_locked(path) == lock {
+            unchanged.push(name);
         } else {
-            Some(root.join(clean_pattern))
+            // Unlocking an explicit lock inside a locked parent takes a second
+            // toggle, just like pressing Space twice in the File Guardian tab
+            for _ in 0..2 {
+                if app.is_path_effectively_locked(path) != lock {
+                    app.toggle_path(path, path.is_dir());
+                }
+            }
+            changed.push(name);
+        }
+    }
+    app.write_state()?;
+
+    let (verb, already) = if lock {
+        ("Locked", "already locked")
+    } else {
+        ("Unlocked", "already unlocked")
+    };
+    if json {
+        let result = serde_json::json!({
+            "changed": changed,
+            "unchanged": unchanged,
+            "state": state_summary(&app)?,
+        });
+        println!("{}", serde_json::to_string_pretty(&result)?);
+    } else {
+        for name in &changed {
+            println!("{verb} {name}");
+        }
+        for name in &unchanged {
+            println!("{name} is {already}");
+        }
+    }
+
+    // Never report success for a path that a more specific lock still covers
+    if !lock {
+        for path in &paths {
+            if app.is_path_effectively_locked(path) {
+                eprintln!(
+                    "Warning: {} is still locked by a more specific pattern",
+                    display_relative(root_path, path)
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn allow_create_command(
+    root_path: &Path,
+    args: &Args,
+    user_config: &config::UserConfig,
+    dirs: &[String],
+    allow: bool,
+    json: bool,
+) -> Result<()> {
+    let mut app = load_guardian(root_path, args, user_config)?;
+    let paths = resolve_targets(&app, dirs)?;
+
+    for path in &paths {
+        if !app.set_allow_create(path, allow) {
+            anyhow::bail!(
+                "{} is not a locked directory",
+                display_relative(root_path, path)
+            );
         }
+    }
+    app.write_state()?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&state_summary(&app)?)?);
     } else {
-        // File or specific path pattern
-        Some(root.join(pattern))
+        for path in &paths {
+            let name = display_relative(root_path, path);
+            if allow {
+                println!("New files allowed in {name}");
+            } else {
+                println!("New files no longer allowed in {name}");
+            }
+        }
     }
+
+    Ok(())
+}
+
+fn state_summary(app: &ui::App) -> Result<serde_json::Value> {
+    let state = if app.state_file.exists() {
+        state::AppState::load_from_file(&app.state_file)?
+    } else {
+        state::AppState::new(app.root_path.clone())
+    };
+    Ok(serde_json::json!({
+        "state_file": app.state_file,
+        "active_profile": state.active_profile,
+        "quest": state.quest,
+        "locked_patterns": state.locked_patterns,
+        "unlocked_patterns": state.unlocked_patterns,
+        "allow_create_patterns": state.allow_create_patterns,
+    }))
 }
 
-fn restore_expanded(node: &mut file_tree::TreeNode, path: &Path) -> bool {
-    if node.path == path && node.is_dir {
-        node.is_expanded = true;
-        return true;
+fn status_command(
+    root_path: &Path,
+    args: &Args,
+    user_config: &config::UserConfig,
+    json: bool,
+) -> Result<()> {
+    let app = load_guardian(root_path, args, user_config)?;
+    let mut files = Vec::new();
+    collect_files(&app.tree, &mut files);
+    let locked_files = files.iter().filter(|node| node.is_locked).count();
+
+    let mut summary = state_summary(&app)?;
+    if json {
+        summary["locked_files"] = locked_files.into();
+        summary["total_files"] = files.len().into();
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+        return Ok(());
     }
 
-    for child in &mut node.children {
-        if restore_expanded(child, path) {
-            return true;
+    let list = |value: &serde_json::Value| {
+        let patterns: Vec<&str> = value
+            .as_array()
+            .map(|items| items.iter().filter_map(|item| item.as_str()).collect())
+            .unwrap_or_default();
+        if patterns.is_empty() {
+            "-".to_string()
+        } else {
+            patterns.join(", ")
         }
+    };
+    println!("State file:     {}", app.state_file.display());
+    println!(
+        "Active profile: {}",
+        summary["active_profile"].as_str().unwrap_or("-")
+    );
+    if let Some(quest) = summary["quest"]["name"].as_str() {
+        println!(
+            "Quest:          {quest} (only {})",
+            list(&summary["quest"]["scope"])
+        );
     }
-    false
+    println!("Locked:         {}", list(&summary["locked_patterns"]));
+    println!("Unlocked:       {}", list(&summary["unlocked_patterns"]));
+    println!(
+        "Allow create:   {}",
+        list(&summary["allow_create_patterns"])
+    );
+    println!("Locked files:   {locked_files} of {}", files.len());
+
+    Ok(())
 }
 
-#[allow(dead_code)]
-fn lock_all_recursive(node: &mut file_tree::TreeNode) {
-    node.is_locked = true;
-    node.allow_create_in_locked = false;
-    for child in &mut node.children {
-        lock_all_recursive(child);
+fn collect_files<'a>(node: &'a file_tree::TreeNode, files: &mut Vec<&'a file_tree::TreeNode>) {
+    for child in &node.children {
+        if child.is_dir {
+            collect_files(child, files);
+        } else {
+            files.push(child);
+        }
     }
 }
 
-#[allow(dead_code)]
-fn restore_locked(node: &mut file_tree::TreeNode, path: &Path) -> bool {
-    if node.path == path || node.path.starts_with(path) {
-        node.is_locked = true;
-        // Don't reset allow_create_in_locked here - it will be set by restore_allow_create
-        // Also don't call lock_all_children as it resets allow_create_in_locked flags
-        return true;
+fn ls_command(
+    root_path: &Path,
+    args: &Args,
+    user_config: &config::UserConfig,
+    locked_only: bool,
+    json: bool,
+) -> Result<()> {
+    let app = load_guardian(root_path, args, user_config)?;
+    let mut files = Vec::new();
+    collect_files(&app.tree, &mut files);
+    files.retain(|node| node.is_locked || !locked_only);
+
+    if json {
+        let entries: Vec<serde_json::Value> = files
+            .iter()
+            .map(|node| {
+                serde_json::json!({
+                    "path": display_relative(root_path, &node.path),
+                    "locked": node.is_locked,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+    } else {
+        for node in files {
+            let marker = if node.is_locked { "🔒 " } else { "   " };
+            if locked_only {
+                println!("{}", display_relative(root_path, &node.path));
+            } else {
+                println!("{marker}{}", display_relative(root_path, &node.path));
+            }
+        }
     }
 
-    for child in &mut node.children {
-        restore_locked(child, path);
+    Ok(())
+}
+
+fn profile_command(
+    root_path: &Path,
+    args: &Args,
+    user_config: &config::UserConfig,
+    action: &ProfileCommands,
+    json: bool,
+) -> Result<()> {
+    let state_file = args
+        .state_file
+        .clone()
+        .unwrap_or_else(|| root_path.join(".icaros"));
+    let mut state = if state_file.exists() {
+        state::AppState::load_from_file(&state_file)?
+    } else if matches!(
+        action,
+        ProfileCommands::List
+            | ProfileCommands::Save { .. }
+            | ProfileCommands::Import { .. }
+            | ProfileCommands::Library
+    ) {
+        state::AppState::new(root_path.to_path_buf())
+    } else {
+        anyhow::bail!("No state file at {}", state_file.display());
+    };
+
+    let missing = |state: &state::AppState, name: &str| {
+        let names = state.get_profile_names();
+        if names.is_empty() {
+            anyhow::anyhow!("Profile '{name}' not found (no profiles saved)")
+        } else {
+            anyhow::anyhow!(
+                "Profile '{name}' not found (available: {})",
+                names.join(", ")
+            )
+        }
+    };
+
+    match action {
+        ProfileCommands::List => {
+            if json {
+                let profiles: Vec<serde_json::Value> = state
+                    .profiles
+                    .iter()
+                    .map(|(name, profile)| {
+                        serde_json::json!({
+                            "name": name,
+                            "description": profile.description,
+                            "extends": profile.extends,
+                            "active": state.active_stack().contains(name),
+                        })
+                    })
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&profiles)?);
+            } else if state.profiles.is_empty() {
+                println!("No profiles saved");
+            } else {
+                let stack = state.active_stack();
+                for (name, profile) in &state.profiles {
+                    let marker = if state.active_profile.as_ref() == Some(name) {
+                        "●"
+                    } else if stack.contains(name) {
+                        "◐"
+                    } else {
+                        " "
+                    };
+                    let mut line = format!("{marker} {name}");
+                    if !profile.extends.is_empty() {
+                        line.push_str(&format!(" (extends {})", profile.extends.join(", ")));
+                    }
+                    if !profile.description.is_empty() {
+                        line.push_str(&format!("  {}", profile.description));
+                    }
+                    println!("{line}");
+                }
+            }
+            return Ok(());
+        }
+        ProfileCommands::Show { name } => {
+            let profile = state
+                .profiles
+                .get(name)
+                .ok_or_else(|| missing(&state, name))?;
+            let resolved = state.resolve_profile(name)?;
+            if json {
+                let mut value = serde_json::to_value(profile)?;
+                value["name"] = name.clone().into();
+                value["active"] = state.active_stack().contains(name).into();
+                value["resolved"] = serde_json::to_value(&resolved.rules)?;
+                println!("{}", serde_json::to_string_pretty(&value)?);
+            } else {
+                println!("Profile: {name}");
+                if !profile.description.is_empty() {
+                    println!("Description: {}", profile.description);
+                }
+                if !profile.extends.is_empty() {
+                    println!("Extends: {}", profile.extends.join(", "));
+                }
+                if !profile.removed_patterns.is_empty() {
+                    println!("Drops: {}", profile.removed_patterns.join(", "));
+                }
+                let resolved = resolved.policy();
+                print_patterns("Locked", &resolved.locked_patterns);
+                print_patterns("Unlocked", &resolved.unlocked_patterns);
+                print_patterns("Allow create", &resolved.allow_create_patterns);
+            }
+            return Ok(());
+        }
+        ProfileCommands::Diff { from, to } => {
+            for name in [from, to] {
+                if !state.profiles.contains_key(name) {
+                    return Err(missing(&state, name));
+                }
+            }
+            let guardian = load_guardian(root_path, args, user_config)?;
+            let diff = policy_diff::diff_policies(
+                &guardian.tree,
+                root_path,
+                &state.resolve_profile(from)?.policy(),
+                &state.resolve_profile(to)?.policy(),
+            );
+            if json {
+                let result = serde_json::json!({
+                    "from": from,
+                    "to": to,
+                    "counts": {
+                        "locked": diff.count(policy_diff::PathChange::Locked),
+                        "unlocked": diff.count(policy_diff::PathChange::Unlocked),
+                        "create_allowed": diff.count(policy_diff::PathChange::CreateAllowed),
+                        "create_revoked": diff.count(policy_diff::PathChange::CreateRevoked),
+                    },
+                    "changes": diff.changes,
+                });
+                println!("{}", serde_json::to_string_pretty(&result)?);
+            } else if diff.is_empty() {
+                println!("Switching from '{from}' to '{to}' changes nothing");
+            } else {
+                println!("Switching from '{from}' to '{to}': {}", diff.summary());
+                for line in diff.tree_lines() {
+                    let indent = "  ".repeat(line.depth);
+                    match line.diff {
+                        Some(change) if change.is_dir && change.files > 0 => println!(
+                            "{indent}{}  {} ({} files)",
+                            line.name,
+                            change.change.label(),
+                            change.files
+                        ),
+                        Some(change) => {
+                            println!("{indent}{}  {}", line.name, change.change.label())
+                        }
+                        None => println!("{indent}{}", line.name),
+                    }
+                }
+            }
+            return Ok(());
+        }
+        ProfileCommands::Export {
+            name,
+            file,
+            library,
+            force,
+        } => {
+            if !state.profiles.contains_key(name) {
+                return Err(missing(&state, name));
+            }
+            let profile = state.standalone_profile(name)?;
+            let target = if *library {
+                let dir = profile_library(user_config)?;
+                fs::create_dir_all(&dir)?;
+                Some(dir.join(format!("{name}.yaml")))
+            } else {
+                file.clone()
+            };
+            let Some(target) = target else {
+                print!("{}", serde_yaml::to_string(&profile)?);
+                return Ok(());
+            };
+            if target.exists() && !force {
+                anyhow::bail!(
+                    "{} already exists (use --force to overwrite it)",
+                    target.display()
+                );
+            }
+            profile.save_to_file(&target)?;
+            if json {
+                let result = serde_json::json!({"name": name, "file": target});
+                println!("{}", serde_json::to_string_pretty(&result)?);
+            } else {
+                println!("Exported profile '{name}' to {}", target.display());
+            }
+            return Ok(());
+        }
+        ProfileCommands::Import {
+            source,
+            name,
+            keep_unresolved,
+            force,
+        } => {
+            let path = if Path::new(source).is_file() {
+                PathBuf::from(source)
+            } else {
+                let dir = profile_library(user_config)?;
+                config::library_profiles(&dir)?
+                    .into_iter()
+                    .find(|(library_name, _)| library_name == source)
+                    .map(|(_, path)| path)
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "No profile file or library profile named '{source}' (library: {})",
+                            dir.display()
+                        )
+                    })?
+            };
+            let name = match name {
+                Some(name) => name.clone(),
+                None => path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .map(str::to_string)
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("Cannot name a profile after {}", path.display())
+                    })?,
+            };
+            if state.profiles.contains_key(&name) && !force {
+                anyhow::bail!("Profile '{name}' already exists (use --force to replace it)");
+            }
+
+            let mut profile = state::LockProfile::load_from_file(&path)?;
+            let unresolved = profile.unresolved_patterns(&state.root_path);
+            for pattern in &unresolved {
+                eprintln!("Warning: pattern '{pattern}' matches nothing in this repository");
+            }
+            if !keep_unresolved {
+                profile.remove_patterns(&unresolved);
+            }
+            state.profiles.insert(name.clone(), profile);
+            // Its bases must exist here too
+            state.resolve_profile(&name)?;
+            state.save_to_file(&state_file)?;
+
+            if json {
+                let result = serde_json::json!({
+                    "name": name,
+                    "file": path,
+                    "unresolved_patterns": unresolved,
+                    "kept_unresolved": keep_unresolved,
+                });
+                println!("{}", serde_json::to_string_pretty(&result)?);
+            } else {
+                println!("Imported profile '{name}' from {}", path.display());
+                if !unresolved.is_empty() && !keep_unresolved {
+                    println!(
+                        "Dropped {} unresolved pattern(s); use --keep-unresolved to keep them",
+                        unresolved.len()
+                    );
+                }
+            }
+            return Ok(());
+        }
+        ProfileCommands::Library => {
+            let dir = profile_library(user_config)?;
+            let mut entries = Vec::new();
+            for (name, path) in config::library_profiles(&dir)? {
+                let description = state::LockProfile::load_from_file(&path)
+                    .map(|profile| profile.description)
+                    .unwrap_or_else(|e| format!("unreadable: {e}"));
+                entries.push((name, path, description));
+            }
+            if json {
+                let entries: Vec<serde_json::Value> = entries
+                    .iter()
+                    .map(|(name, path, description)| {
+                        serde_json::json!({"name": name, "file": path, "description": description})
+                    })
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&entries)?);
+            } else if entries.is_empty() {
+                println!("No profiles in {}", dir.display());
+            } else {
+                for (name, _, description) in &entries {
+                    if description.is_empty() {
+                        println!("{name}");
+                    } else {
+                        println!("{name}  {description}");
+                    }
+                }
+            }
+            return Ok(());
+        }
+        ProfileCommands::Save {
+            name,
+            description,
+            extends,
+        } => {
+            let description = description.clone().unwrap_or_else(|| {
+                format!(
+                    "Saved on {}",
+                    chrono::Utc::now().format("%Y-%m-%d %H:%M:%S")
+                )
+            });
+            if extends.is_empty() {
+                if let Some(warning) = state.save_current_as_profile(name.clone(), description) {
+                    eprintln!("Warning: {warning}");
+                }
+            } else {
+                if let Some(base) = extends.iter().find(|b| !state.profiles.contains_key(*b)) {
+                    return Err(missing(&state, base));
+                }
+                state.save_current_extending(name.clone(), description, extends.clone())?;
+            }
+            state.save_to_file(&state_file)?;
+            if !json {
+                println!("Saved current locks as profile '{name}'");
+            }
+        }
+        ProfileCommands::Switch { names } => {
+            if let Some(name) = names.iter().find(|n| !state.profiles.contains_key(*n)) {
+                return Err(missing(&state, name));
+            }
+            state.activate(names)?;
+            state.save_to_file(&state_file)?;
+            if !json {
+                let quoted: Vec<String> = names.iter().map(|n| format!("'{n}'")).collect();
+                match quoted.as_slice() {
+                    [single] => println!("Switched to profile {single}"),
+                    layers => println!("Switched to profiles {}", layers.join(" + ")),
+                }
+            }
+        }
+        ProfileCommands::Delete { name } => {
+            let dependents = state.profiles_extending(name);
+            if !dependents.is_empty() {
+                anyhow::bail!("Profile '{name}' is extended by {}", dependents.join(", "));
+            }
+            let agents = state.agents_using(name);
+            if !agents.is_empty() {
+                anyhow::bail!("Profile '{name}' is used by agent {}", agents.join(", "));
+            }
+            if !state.delete_profile(name) {
+                return Err(missing(&state, name));
+            }
+            state.save_to_file(&state_file)?;
+            if !json {
+                println!("Deleted profile '{name}'");
+            }
+        }
+        ProfileCommands::Sync => {
+            if let Some(quest) = &state.quest {
+                anyhow::bail!("Quest '{}' is in progress; end it first", quest.name);
+            }
+            let git = git::GitManager::new(root_path)?;
+            // Checking out a commit keeps whatever profile was active
+            let Some(branch) = git.current_branch() else {
+                if !json {
+                    println!("HEAD is detached; keeping the current profile");
+                }
+                return print_profile_result(&state, json);
+            };
+            match state.profile_for_branch(&branch) {
+                None => {
+                    if !json {
+                        println!("No branch rule matches '{branch}'");
+                    }
+                }
+                Some(profile) if !state.profiles.contains_key(profile) => {
+                    anyhow::bail!(
+                        "Branch '{branch}' maps to profile '{profile}', which does not exist"
+                    );
+                }
+                Some(profile) => {
+                    let profile = profile.to_string();
+                    state.resolve_profile(&profile)?;
+                    if state.follow_branch(&branch).is_some() {
+                        state.save_to_file(&state_file)?;
+                        if !json {
+                            println!("Switched to profile '{profile}' for branch '{branch}'");
+                        }
+                    } else if !json {
+                        println!("Profile '{profile}' is already active");
+                    }
+                }
+            }
+        }
+        ProfileCommands::Rename { from, to } | ProfileCommands::Copy { from, to } => {
+            if !state.profiles.contains_key(from) {
+                return Err(missing(&state, from));
+            }
+            if state.profiles.contains_key(to) {
+                anyhow::bail!("Profile '{to}' already exists");
+            }
+            let renaming = matches!(action, ProfileCommands::Rename { .. });
+            if renaming {
+                state.rename_profile(from, to);
+            } else {
+                state.copy_profile(from, to);
+            }
+            state.save_to_file(&state_file)?;
+            if !json {
+                let verb = if renaming { "Renamed" } else { "Copied" };
+                println!("{verb} profile '{from}' to '{to}'");
+            }
+        }
     }
-    false
+
+    print_profile_result(&state, json)
 }
 
-#[allow(dead_code)]
-fn restore_unlocked(node: &mut file_tree::TreeNode, path: &Path) -> bool {
-    if node.path == path || node.path.starts_with(path) {
-        node.is_locked = false;
-        node.allow_create_in_locked = false;
-        // Also unlock all children recursively
-        for child in &mut node.children {
-            unlock_all_recursive(child);
+/// Where `icaros scope` puts the scope it builds; printed only when neither is set.
+struct ScopeOutput<'a> {
+    profile: Option<&'a str>,
+    quest: Option<&'a str>,
+    force: bool,
+}
+
+fn scope_command(
+    root_path: &Path,
+    args: &Args,
+    user_config: &config::UserConfig,
+    range: Option<&str>,
+    options: scope::ScopeOptions,
+    output: ScopeOutput,
+    json: bool,
+) -> Result<()> {
+    let git = git::GitManager::new(root_path)?;
+    let workdir = git
+        .workdir()
+        .ok_or_else(|| anyhow::anyhow!("The repository has no working tree"))?
+        .canonicalize()?;
+    let (mut files, source) = match range {
+        Some(range) => (git.get_range_files(range)?, range.to_string()),
+        None => (git.get_status_files()?, "the working tree".to_string()),
+    };
+    // Never hand out the locks themselves
+    let state_file = args
+        .state_file
+        .clone()
+        .unwrap_or_else(|| root_path.join(".icaros"));
+    files.retain(|file| workdir.join(&file.path) != state_file);
+    let scope = scope::scope_from_changes(&files, &workdir, root_path, options);
+    if scope.is_empty() {
+        anyhow::bail!("No changed files under {} in {source}", root_path.display());
+    }
+
+    if let Some(name) = output.profile {
+        let mut state = if state_file.exists() {
+            state::AppState::load_from_file(&state_file)?
+        } else {
+            state::AppState::new(root_path.to_path_buf())
+        };
+        if state.profiles.contains_key(name) && !output.force {
+            anyhow::bail!("Profile '{name}' already exists (use --force to replace it)");
+        }
+        state.profiles.insert(
+            name.to_string(),
+            scope.profile(format!("Files changed in {source}")),
+        );
+        state.save_to_file(&state_file)?;
+        if !json {
+            println!("Saved profile '{name}' from {source}");
+        }
+    } else if let Some(name) = output.quest {
+        let mut app = load_guardian(root_path, args, user_config)?;
+        app.begin_quest(name, scope.unlocked.clone(), scope.allow_create.clone())?;
+        if !json {
+            println!("Quest '{name}' started from {source}");
+        }
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&scope)?);
+    } else if output.profile.is_none() && output.quest.is_none() {
+        for pattern in &scope.unlocked {
+            println!("unlock        {pattern}");
+        }
+        for dir in &scope.allow_create {
+            println!("allow-create  {dir}");
         }
-        return true;
     }
+    Ok(())
+}
+
+fn quest_command(
+    root_path: &Path,
+    args: &Args,
+    user_config: &config::UserConfig,
+    action: &QuestCommands,
+    json: bool,
+) -> Result<()> {
+    let mut app = load_guardian(root_path, args, user_config)?;
+
+    let mut ended = None;
+    let quest = match action {
+        QuestCommands::Start { targets, name } => {
+            app.quest_marks = resolve_targets(&app, targets)?;
+            app.start_quest(name.as_deref())?;
+            let quest = app.active_quest.clone();
+            if let (false, Some(quest)) = (json, &quest) {
+                println!("Quest '{}' started; only these may change:", quest.name);
+                for pattern in &quest.scope {
+                    println!("  {pattern}");
+                }
+            }
+            quest
+        }
+        QuestCommands::End => {
+            let quest = app.end_quest()?;
+            if !json {
+                match &quest.previous_profile {
+                    Some(profile) => println!(
+                        "Quest '{}' ended; profile '{profile}' is active again",
+                        quest.name
+                    ),
+                    None => println!("Quest '{}' ended; previous locks restored", quest.name),
+                }
+            }
+            ended = Some(quest);
+            None
+        }
+        QuestCommands::Status => {
+            let quest = app.active_quest.clone();
+            if !json {
+                match &quest {
+                    Some(quest) => {
+                        println!("Quest '{}'; only these may change:", quest.name);
+                        for pattern in &quest.scope {
+                            println!("  {pattern}");
+                        }
+                    }
+                    None => println!("No quest in progress"),
+                }
+            }
+            quest
+        }
+    };
 
-    for child in &mut node.children {
-        restore_unlocked(child, path);
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&serde_json::json!({ "quest": quest, "ended": ended }))?
+        );
     }
-    false
+    Ok(())
 }
 
-#[allow(dead_code)]
-fn unlock_all_recursive(node: &mut file_tree::TreeNode) {
-    node.is_locked = false;
-    node.allow_create_in_locked = false;
-    for child in &mut node.children {
-        unlock_all_recursive(child);
+fn agent_command(root_path: &Path, args: &Args, action: &AgentCommands, json: bool) -> Result<()> {
+    let state_file = args
+        .state_file
+        .clone()
+        .unwrap_or_else(|| root_path.join(".icaros"));
+    let mut state = if state_file.exists() {
+        state::AppState::load_from_file(&state_file)?
+    } else if matches!(action, AgentCommands::List) {
+        state::AppState::new(root_path.to_path_buf())
+    } else {
+        anyhow::bail!("No state file at {}", state_file.display());
+    };
+
+    match action {
+        AgentCommands::List => {}
+        AgentCommands::Bind { agent, profile } => {
+            if !state.bind_agent(agent, profile) {
+                anyhow::bail!(
+                    "Profile '{profile}' not found (available: {})",
+                    state.get_profile_names().join(", ")
+                );
+            }
+            state.save_to_file(&state_file)?;
+            if !json {
+                println!("Agent '{agent}' now uses profile '{profile}'");
+            }
+        }
+        AgentCommands::Unbind { agent } => {
+            if !state.unbind_agent(agent) {
+                anyhow::bail!("Agent '{agent}' has no profile");
+            }
+            state.save_to_file(&state_file)?;
+            if !json {
+                println!("Agent '{agent}' no longer has a profile");
+            }
+        }
+    }
+
+    if json {
+        let agents: Vec<serde_json::Value> = state
+            .agents
+            .iter()
+            .map(|(agent, profile)| serde_json::json!({"agent": agent, "profile": profile}))
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&agents)?);
+    } else if matches!(action, AgentCommands::List) {
+        if state.agents.is_empty() {
+            println!("No agents configured");
+        }
+        let width = state
+            .agents
+            .keys()
+            .map(|agent| agent.len())
+            .max()
+            .unwrap_or(0);
+        for (agent, profile) in &state.agents {
+            println!("{agent:<width$}  {profile}");
+        }
     }
+    Ok(())
 }
 
-fn restore_allow_create(node: &mut file_tree::TreeNode, path: &Path) -> bool {
-    if node.path == path && node.is_dir && node.is_locked {
-        node.allow_create_in_locked = true;
-        return true;
+fn profile_library(user_config: &config::UserConfig) -> Result<PathBuf> {
+    config::Settings::resolve(user_config, None)
+        .profile_library
+        .ok_or_else(|| {
+            anyhow::anyhow!("No profile library directory; set profile_library in the user config")
+        })
+}
+
+fn print_profile_result(state: &state::AppState, json: bool) -> Result<()> {
+    if json {
+        let result = serde_json::json!({
+            "active_profile": state.active_profile,
+            "profile_stack": state.active_stack(),
+            "profiles": state.get_profile_names(),
+            "locked_patterns": state.locked_patterns,
+            "unlocked_patterns": state.unlocked_patterns,
+            "allow_create_patterns": state.allow_create_patterns,
+        });
+        println!("{}", serde_json::to_string_pretty(&result)?);
     }
+    Ok(())
+}
 
-    for child in &mut node.children {
-        if restore_allow_create(child, path) {
-            return true;
+fn print_patterns(label: &str, patterns: &[String]) {
+    if patterns.is_empty() {
+        println!("{label}: -");
+    } else {
+        println!("{label}:");
+        for pattern in patterns {
+            println!("  {pattern}");
         }
     }
-    false
 }
 
-fn init_command(root_path: &Path) -> Result<()> {
+fn init_command(root_path: &Path, user_config: &config::UserConfig) -> Result<()> {
     let claude_md_path = root_path.join("CLAUDE.md");
     let icaros_md_path = root_path.join("ICAROS.md");
 
     // Load templates from embedded files or from prompts directory
-    let icaros_content = load_template("ICAROS.md")?;
+    let icaros_content = load_template("ICAROS.md", user_config)?;
 
     // Write ICAROS.md
     fs::write(&icaros_md_path, icaros_content)?;
@@ -319,7 +1804,7 @@ fn init_command(root_path: &Path) -> Result<()> {
         // Check if it already references ICAROS.md
         if !claude_content.contains("ICAROS.md") {
             // Load update template
-            let update_template = load_template("CLAUDE_UPDATE.md")?;
+            let update_template = load_template("CLAUDE_UPDATE.md", user_config)?;
 
             // Remove any existing CLAUDE.md header variations
             let existing_content = claude_content
@@ -336,7 +1821,7 @@ fn init_command(root_path: &Path) -> Result<()> {
         }
     } else {
         // Create new CLAUDE.md
-        let claude_content = load_template("CLAUDE.md")?;
+        let claude_content = load_template("CLAUDE.md", user_config)?;
         fs::write(&claude_md_path, claude_content)?;
         println!("Created CLAUDE.md with reference to ICAROS.md");
     }
@@ -344,10 +1829,11 @@ fn init_command(root_path: &Path) -> Result<()> {
     Ok(())
 }
 
-fn load_template(filename: &str) -> Result<String> {
-    // First try to load from user's config directory
-    if let Some(config_dir) = dirs::config_dir() {
-        let user_template = config_dir.join("icaros").join("prompts").join(filename);
+fn load_template(filename: &str, user_config: &config::UserConfig) -> Result<String> {
+    // First try the configured templates dir (default: the user's config directory)
+    let templates_dir = config::Settings::resolve(user_config, None).templates_dir;
+    if let Some(templates_dir) = templates_dir {
+        let user_template = templates_dir.join(filename);
         if user_template.exists() {
             return fs::read_to_string(user_template)
                 .map_err(|e| anyhow::anyhow!("Failed to read user template: {}", e));
@@ -0,0 +1,372 @@
+use crate::state::{AppState, LockProfile};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MergeSide {
+    Ours,
+    Theirs,
+}
+
+impl MergeSide {
+    fn label(self) -> &'static str {
+        match self {
+            MergeSide::Ours => "ours",
+            MergeSide::Theirs => "theirs",
+        }
+    }
+
+    fn other(self) -> Self {
+        match self {
+            MergeSide::Ours => MergeSide::Theirs,
+            MergeSide::Theirs => MergeSide::Ours,
+        }
+    }
+}
+
+/// A change the merge could not reconcile on its own.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeConflict {
+    /// Profile the conflict was found in, or `None` for the top-level state.
+    pub profile: Option<String>,
+    pub kind: ConflictKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConflictKind {
+    /// A pattern that one side locked while the other side explicitly unlocked it.
+    Lock {
+        pattern: String,
+        locked_by: MergeSide,
+    },
+    /// A field both sides changed to different values; ours is kept.
+    Field {
+        field: &'static str,
+        ours: String,
+        theirs: String,
+    },
+}
+
+impl fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.profile, &self.kind) {
+            (Some(name), _) => write!(f, "profile '{name}': ")?,
+            (None, ConflictKind::Lock { .. }) => write!(f, "active patterns: ")?,
+            (None, ConflictKind::Field { .. }) => {}
+        }
+        match &self.kind {
+            ConflictKind::Lock { pattern, locked_by } => write!(
+                f,
+                "'{pattern}' locked by {} but unlocked by {} (kept locked)",
+                locked_by.label(),
+                locked_by.other().label()
+            ),
+            ConflictKind::Field {
+                field,
+                ours,
+                theirs,
+            } => write!(
+                f,
+                "{field} changed to {ours} by ours and {theirs} by theirs (kept ours)"
+            ),
+        }
+    }
+}
+
+pub struct MergeOutcome {
+    pub state: AppState,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PatternState {
+    Locked,
+    Unlocked,
+    Absent,
+}
+
+/// Three-way merge of `.icaros` states, as used by `icaros merge-driver`.
+///
+/// Pattern lists are merged as sets: additions from either side are kept and
+/// a removal wins only when the other side left the pattern untouched. When
+/// one side locks a pattern the other side unlocked, the lock is kept and the
+/// pattern is reported as a conflict. Other fields take the side that changed
+/// them; when both sides changed the quest or a profile's `extends`, ours is
+/// kept and the field is reported as a conflict.
+pub fn merge_states(base: &AppState, ours: &AppState, theirs: &AppState) -> MergeOutcome {
+    let mut conflicts = Vec::new();
+
+    let active = merge_policy(
+        &base.current_policy(),
+        &ours.current_policy(),
+        &theirs.current_policy(),
+        None,
+        &mut conflicts,
+    );
+
+    let mut state = ours.clone();
+    state.locked_patterns = active.locked_patterns;
+    state.unlocked_patterns = active.unlocked_patterns;
+    state.allow_create_patterns = active.allow_create_patterns;
+    state.profiles = merge_profiles(base, ours, theirs, &mut conflicts);
+    state.active_profile = merge_scalar(
+        &base.active_profile,
+        &ours.active_profile,
+        &theirs.active_profile,
+    );
+    state.profile_stack = merge_scalar(
+        &base.profile_stack,
+        &ours.profile_stack,
+        &theirs.profile_stack,
+    );
+    // The stack's top is the active profile; drop both if the merge broke that
+    if state
+        .profile_stack
+        .last()
+        .is_some_and(|top| state.active_profile.as_ref() != Some(top))
+        || state
+            .active_stack()
+            .iter()
+            .any(|name| !state.profiles.contains_key(name))
+    {
+        state.active_profile = None;
+        state.profile_stack.clear();
+    }
+    let agents: BTreeSet<&String> = base
+        .agents
+        .keys()
+        .chain(ours.agents.keys())
+        .chain(theirs.agents.keys())
+        .collect();
+    state.agents = agents
+        .into_iter()
+        .filter_map(|agent| {
+            let profile = merge_scalar(
+                &base.agents.get(agent),
+                &ours.agents.get(agent),
+                &theirs.agents.get(agent),
+            )?;
+            state
+                .profiles
+                .contains_key(profile)
+                .then(|| (agent.clone(), profile.clone()))
+        })
+        .collect();
+    state.quest = merge_reported(
+        &base.quest,
+        &ours.quest,
+        &theirs.quest,
+        "quest",
+        |quest| match quest {
+            Some(quest) => format!("'{}'", quest.name),
+            None => "none".to_string(),
+        },
+        None,
+        &mut conflicts,
+    );
+    state.ignore_patterns = merge_lists(
+        &base.ignore_patterns,
+        &ours.ignore_patterns,
+        &theirs.ignore_patterns,
+    );
+    for dir in &theirs.expanded_dirs {
+        if !state.expanded_dirs.contains(dir) {
+            state.expanded_dirs.push(dir.clone());
+        }
+    }
+
+    MergeOutcome { state, conflicts }
+}
+
+fn merge_profiles(
+    base: &AppState,
+    ours: &AppState,
+    theirs: &AppState,
+    conflicts: &mut Vec<MergeConflict>,
+) -> BTreeMap<String, LockProfile> {
+    let names: BTreeSet<&String> = base
+        .profiles
+        .keys()
+        .chain(ours.profiles.keys())
+        .chain(theirs.profiles.keys())
+        .collect();
+
+    let mut merged = BTreeMap::new();
+    for name in names {
+        let b = base.profiles.get(name);
+        let o = ours.profiles.get(name);
+        let t = theirs.profiles.get(name);
+
+        let profile = match (o, t) {
+            (Some(o), Some(t)) if o == t => Some(o.clone()),
+            (Some(o), Some(t)) => {
+                if b == Some(o) {
+                    Some(t.clone())
+                } else if b == Some(t) {
+                    Some(o.clone())
+                } else {
+                    let empty = LockProfile::default();
+                    let b = b.unwrap_or(&empty);
+                    let mut profile = merge_policy(b, o, t, Some(name), conflicts);
+                    profile.description =
+                        merge_scalar(&b.description, &o.description, &t.description);
+                    Some(profile)
+                }
+            }
+            // Deleted on one side: honour it only if the other side left the profile alone
+            (Some(kept), None) | (None, Some(kept)) => {
+                if b == Some(kept) {
+                    None
+                } else {
+                    Some(kept.clone())
+                }
+            }
+            (None, None) => None,
+        };
+
+        if let Some(profile) = profile {
+            merged.insert(name.clone(), profile);
+        }
+    }
+    merged
+}
+
+fn merge_policy(
+    base: &LockProfile,
+    ours: &LockProfile,
+    theirs: &LockProfile,
+    profile: Option<&String>,
+    conflicts: &mut Vec<MergeConflict>,
+) -> LockProfile {
+    let mut locked = merge_lists(
+        &base.locked_patterns,
+        &ours.locked_patterns,
+        &theirs.locked_patterns,
+    );
+    let mut unlocked = merge_lists(
+        &base.unlocked_patterns,
+        &ours.unlocked_patterns,
+        &theirs.unlocked_patterns,
+    );
+    let allow_create = merge_lists(
+        &base.allow_create_patterns,
+        &ours.allow_create_patterns,
+        &theirs.allow_create_patterns,
+    );
+
+    let patterns: BTreeSet<&String> = ours
+        .locked_patterns
+        .iter()
+        .chain(ours.unlocked_patterns.iter())
+        .chain(theirs.locked_patterns.iter())
+        .chain(theirs.unlocked_patterns.iter())
+        .collect();
+
+    for pattern in patterns {
+        let b = pattern_state(base, pattern);
+        let o = pattern_state(ours, pattern);
+        let t = pattern_state(theirs, pattern);
+
+        let locked_by = match (o, t) {
+            (PatternState::Locked, PatternState::Unlocked) if o != b && t != b => MergeSide::Ours,
+            (PatternState::Unlocked, PatternState::Locked) if o != b && t != b => MergeSide::Theirs,
+            _ => continue,
+        };
+
+        // Locking is the safe resolution: the conflict is reported either way
+        if !locked.contains(pattern) {
+            locked.push(pattern.clone());
+        }
+        unlocked.retain(|p| p != pattern);
+        conflicts.push(MergeConflict {
+            profile: profile.cloned(),
+            kind: ConflictKind::Lock {
+                pattern: pattern.clone(),
+                locked_by,
+            },
+        });
+    }
+
+    LockProfile {
+        locked_patterns: locked,
+        unlocked_patterns: unlocked,
+        allow_create_patterns: allow_create,
+        removed_patterns: merge_lists(
+            &base.removed_patterns,
+            &ours.removed_patterns,
+            &theirs.removed_patterns,
+        ),
+        description: ours.description.clone(),
+        extends: merge_reported(
+            &base.extends,
+            &ours.extends,
+            &theirs.extends,
+            "extends",
+            |extends| match extends.as_slice() {
+                [] => "nothing".to_string(),
+                bases => format!("'{}'", bases.join(", ")),
+            },
+            profile,
+            conflicts,
+        ),
+    }
+}
+
+fn pattern_state(policy: &LockProfile, pattern: &String) -> PatternState {
+    if policy.locked_patterns.contains(pattern) {
+        PatternState::Locked
+    } else if policy.unlocked_patterns.contains(pattern) {
+        PatternState::Unlocked
+    } else {
+        PatternState::Absent
+    }
+}
+
+fn merge_lists(base: &[String], ours: &[String], theirs: &[String]) -> Vec<String> {
+    let keep = |item: &String| {
+        let in_base = base.contains(item);
+        let in_ours = ours.contains(item);
+        let in_theirs = theirs.contains(item);
+        (in_ours && in_theirs) || (!in_base && (in_ours || in_theirs))
+    };
+
+    let mut merged: Vec<String> = Vec::new();
+    for item in ours.iter().chain(theirs.iter()) {
+        if keep(item) && !merged.contains(item) {
+            merged.push(item.clone());
+        }
+    }
+    merged
+}
+
+fn merge_scalar<T: Clone + PartialEq>(base: &T, ours: &T, theirs: &T) -> T {
+    if ours == base {
+        theirs.clone()
+    } else {
+        ours.clone()
+    }
+}
+
+/// Like `merge_scalar`, but reports a conflict when both sides changed the value
+/// differently.
+fn merge_reported<T: Clone + PartialEq>(
+    base: &T,
+    ours: &T,
+    theirs: &T,
+    field: &'static str,
+    describe: impl Fn(&T) -> String,
+    profile: Option<&String>,
+    conflicts: &mut Vec<MergeConflict>,
+) -> T {
+    if ours != base && theirs != base && ours != theirs {
+        conflicts.push(MergeConflict {
+            profile: profile.cloned(),
+            kind: ConflictKind::Field {
+                field,
+                ours: describe(ours),
+                theirs: describe(theirs),
+            },
+        });
+    }
+    merge_scalar(base, ours, theirs)
+}
@@ -0,0 +1,246 @@
+use crate::file_tree::TreeNode;
+use crate::state::LockProfile;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// How switching policies changes what may be done with a path.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PathChange {
+    Locked,
+    Unlocked,
+    CreateAllowed,
+    CreateRevoked,
+}
+
+impl PathChange {
+    pub fn label(self) -> &'static str {
+        match self {
+            PathChange::Locked => "locked",
+            PathChange::Unlocked => "unlocked",
+            PathChange::CreateAllowed => "create allowed",
+            PathChange::CreateRevoked => "create revoked",
+        }
+    }
+}
+
+/// A changed path, relative to the root. A directory whose files all change the
+/// same way is reported once, with the number of files it holds.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct PathDiff {
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub change: PathChange,
+    pub files: usize,
+}
+
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct PolicyDiff {
+    pub changes: Vec<PathDiff>,
+}
+
+/// A row of the tree-shaped rendering of a diff.
+pub struct DiffLine<'a> {
+    pub depth: usize,
+    pub name: String,
+    /// `None` for directories shown only to place their changed contents.
+    pub diff: Option<&'a PathDiff>,
+}
+
+impl PolicyDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Files that become locked or unlocked, or directories whose create
+    /// permission changes.
+    pub fn count(&self, change: PathChange) -> usize {
+        self.changes
+            .iter()
+            .filter(|diff| diff.change == change)
+            .map(|diff| match change {
+                PathChange::Locked | PathChange::Unlocked => diff.files,
+                PathChange::CreateAllowed | PathChange::CreateRevoked => 1,
+            })
+            .sum()
+    }
+
+    /// One-line summary such as "3 locked, 1 unlocked, 0 create allowed, 0 create revoked".
+    pub fn summary(&self) -> String {
+        [
+            PathChange::Locked,
+            PathChange::Unlocked,
+            PathChange::CreateAllowed,
+            PathChange::CreateRevoked,
+        ]
+        .iter()
+        .map(|&change| format!("{} {}", self.count(change), change.label()))
+        .collect::<Vec<_>>()
+        .join(", ")
+    }
+
+    /// The changes as an indented tree, with the unchanged directories leading to them.
+    pub fn tree_lines(&self) -> Vec<DiffLine<'_>> {
+        let mut lines = Vec::new();
+        let mut shown: Vec<&Path> = Vec::new();
+        for diff in &self.changes {
+            let ancestors: Vec<&Path> = diff
+                .path
+                .ancestors()
+                .skip(1)
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .collect();
+            for dir in ancestors.into_iter().rev() {
+                if !shown.contains(&dir) {
+                    shown.push(dir);
+                    lines.push(DiffLine {
+                        depth: dir.components().count() - 1,
+                        name: format!("{}/", file_name(dir)),
+                        diff: None,
+                    });
+                }
+            }
+            let name = file_name(&diff.path);
+            lines.push(DiffLine {
+                depth: diff.path.components().count().saturating_sub(1),
+                name: if diff.is_dir {
+                    format!("{name}/")
+                } else {
+                    name
+                },
+                diff: Some(diff),
+            });
+            if diff.is_dir {
+                shown.push(&diff.path);
+            }
+        }
+        lines
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| ".".to_string())
+}
+
+/// What changes for the paths in `tree` when `to` replaces `from`.
+pub fn diff_policies(
+    tree: &TreeNode,
+    root: &Path,
+    from: &LockProfile,
+    to: &LockProfile,
+) -> PolicyDiff {
+    let mut diff = PolicyDiff::default();
+    walk(tree, root, from, to, &mut diff.changes);
+    diff
+}
+
+enum Subtree {
+    Unchanged,
+    // Every file changed the same way
+    Uniform(PathChange, usize),
+    Mixed,
+}
+
+fn lock_change(from: &LockProfile, to: &LockProfile, path: &Path) -> Option<PathChange> {
+    match (from.is_locked(path), to.is_locked(path)) {
+        (false, true) => Some(PathChange::Locked),
+        (true, false) => Some(PathChange::Unlocked),
+        _ => None,
+    }
+}
+
+fn walk(
+    node: &TreeNode,
+    root: &Path,
+    from: &LockProfile,
+    to: &LockProfile,
+    changes: &mut Vec<PathDiff>,
+) -> Subtree {
+    let path = node.path.strip_prefix(root).unwrap_or(&node.path);
+    let own = lock_change(from, to, path);
+    let shown = if path.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        path.to_path_buf()
+    };
+
+    if !node.is_dir {
+        return match own {
+            Some(change) => {
+                changes.push(PathDiff {
+                    path: shown,
+                    is_dir: false,
+                    change,
+                    files: 1,
+                });
+                Subtree::Uniform(change, 1)
+            }
+            None => Subtree::Unchanged,
+        };
+    }
+
+    let create = match (from.allows_create_in(path), to.allows_create_in(path)) {
+        (false, true) => Some(PathChange::CreateAllowed),
+        (true, false) => Some(PathChange::CreateRevoked),
+        _ => None,
+    };
+
+    let start = changes.len();
+    let mut uniform = own.map(|change| (change, 0));
+    let mut unchanged = true;
+    for child in &node.children {
+        match walk(child, root, from, to, changes) {
+            Subtree::Unchanged => uniform = None,
+            Subtree::Uniform(change, files) => {
+                unchanged = false;
+                uniform = uniform
+                    .filter(|(own, _)| *own == change)
+                    .map(|(own, count)| (own, count + files));
+            }
+            Subtree::Mixed => {
+                unchanged = false;
+                uniform = None;
+            }
+        }
+    }
+
+    let create_diff = create.map(|change| PathDiff {
+        path: shown.clone(),
+        is_dir: true,
+        change,
+        files: 0,
+    });
+    match uniform {
+        // Collapse the directory's file entries into one
+        Some((change, files)) => {
+            changes.truncate(start);
+            changes.push(PathDiff {
+                path: shown,
+                is_dir: true,
+                change,
+                files,
+            });
+            match create_diff {
+                // Keep the parent from collapsing the create change away
+                Some(diff) => {
+                    changes.push(diff);
+                    Subtree::Mixed
+                }
+                None => Subtree::Uniform(change, files),
+            }
+        }
+        None => {
+            if let Some(diff) = create_diff {
+                changes.insert(start, diff);
+                return Subtree::Mixed;
+            }
+            if unchanged {
+                Subtree::Unchanged
+            } else {
+                Subtree::Mixed
+            }
+        }
+    }
+}
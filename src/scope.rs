@@ -0,0 +1,120 @@
+use crate::git::{GitFile, GitFileStatus};
+use crate::state::LockProfile;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScopeOptions {
+    /// Unlock the directories holding changed files rather than the files alone.
+    pub widen: bool,
+    /// Allow new files in the directories where files were added.
+    pub allow_create: bool,
+}
+
+/// The paths a set of changes touched, as patterns for a profile or quest.
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct Scope {
+    pub unlocked: Vec<String>,
+    pub allow_create: Vec<String>,
+}
+
+impl Scope {
+    pub fn is_empty(&self) -> bool {
+        self.unlocked.is_empty()
+    }
+
+    /// Everything locked except the scope.
+    pub fn profile(&self, description: String) -> LockProfile {
+        LockProfile {
+            locked_patterns: vec!["**".to_string()],
+            unlocked_patterns: self.unlocked.clone(),
+            allow_create_patterns: self.allow_create.clone(),
+            description,
+            ..LockProfile::default()
+        }
+    }
+}
+
+/// Build a scope from changed files, whose paths are relative to `workdir`.
+/// Files outside `root` and deleted files are left out.
+pub fn scope_from_changes(
+    files: &[GitFile],
+    workdir: &Path,
+    root: &Path,
+    options: ScopeOptions,
+) -> Scope {
+    let mut unlocked_dirs: Vec<PathBuf> = Vec::new();
+    let mut unlocked_files: Vec<PathBuf> = Vec::new();
+    let mut create_dirs: Vec<PathBuf> = Vec::new();
+
+    for file in files {
+        if file.status == GitFileStatus::Deleted {
+            continue;
+        }
+        let Ok(path) = workdir
+            .join(&file.path)
+            .strip_prefix(root)
+            .map(Path::to_path_buf)
+        else {
+            continue;
+        };
+        if path.as_os_str().is_empty() {
+            continue;
+        }
+        // Git reports a wholly untracked directory as `dir/`
+        if file.path.to_string_lossy().ends_with('/') {
+            unlocked_dirs.push(path);
+            continue;
+        }
+
+        let parent = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+        if options.allow_create
+            && matches!(
+                file.status,
+                GitFileStatus::Added | GitFileStatus::Untracked | GitFileStatus::Renamed
+            )
+        {
+            // The root itself can't be named as an allow-create directory
+            create_dirs.extend(parent.map(Path::to_path_buf));
+        }
+        match parent {
+            Some(dir) if options.widen => unlocked_dirs.push(dir.to_path_buf()),
+            _ => unlocked_files.push(path),
+        }
+    }
+
+    unlocked_dirs.sort();
+    unlocked_dirs.dedup();
+    // Drop directories inside other unlocked directories, and files they cover
+    let outermost: Vec<PathBuf> = unlocked_dirs
+        .iter()
+        .filter(|dir| {
+            !unlocked_dirs
+                .iter()
+                .any(|other| other != *dir && dir.starts_with(other))
+        })
+        .cloned()
+        .collect();
+    unlocked_files.retain(|file| !outermost.iter().any(|dir| file.starts_with(dir)));
+
+    let mut unlocked: Vec<String> = outermost
+        .iter()
+        .map(|dir| format!("{}/**", dir.display()))
+        .chain(unlocked_files.iter().map(|file| file.display().to_string()))
+        .collect();
+    unlocked.sort();
+    unlocked.dedup();
+
+    create_dirs.retain(|dir| !outermost.iter().any(|unlocked| dir.starts_with(unlocked)));
+    let mut allow_create: Vec<String> = create_dirs
+        .iter()
+        .map(|dir| dir.display().to_string())
+        .collect();
+    allow_create.sort();
+    allow_create.dedup();
+
+    Scope {
+        unlocked,
+        allow_create,
+    }
+}
@@ -1,8 +1,11 @@
-use anyhow::Result;
+use crate::config::Preferences;
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
 
 pub fn default_ignore_patterns() -> Vec<String> {
     vec![
@@ -26,24 +29,165 @@ pub fn default_ignore_patterns() -> Vec<String> {
     ]
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// A named, reusable set of lock rules.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default, PartialEq)]
 pub struct LockProfile {
+    /// Profiles whose rules apply first, in order; this profile's rules are layered on top.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extends: Vec<String>,
+    #[serde(default)]
     pub locked_patterns: Vec<String>,
+    #[serde(default)]
     pub unlocked_patterns: Vec<String>,
+    #[serde(default)]
     pub allow_create_patterns: Vec<String>,
+    /// Patterns whose inherited rules, of any kind, this profile drops before adding its own.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed_patterns: Vec<String>,
     #[serde(default)]
     pub description: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Which list of a policy a rule belongs to.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleKind {
+    Locked,
+    Unlocked,
+    AllowCreate,
+}
+
+/// A rule of a resolved profile stack and the profile that contributed it.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ResolvedRule {
+    pub kind: RuleKind,
+    pub pattern: String,
+    pub origin: String,
+}
+
+/// The rules of a profile stack once inheritance is applied.
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct ResolvedPolicy {
+    /// Every profile that contributed, in the order their rules were applied.
+    pub layers: Vec<String>,
+    pub rules: Vec<ResolvedRule>,
+}
+
+impl ResolvedPolicy {
+    /// Layer `profile`'s rules on top. Locking or unlocking a pattern replaces
+    /// whatever an earlier layer said about the same pattern.
+    fn push_layer(&mut self, name: &str, profile: &LockProfile) {
+        self.rules
+            .retain(|rule| !profile.removed_patterns.contains(&rule.pattern));
+        let rules = [
+            (RuleKind::Locked, &profile.locked_patterns),
+            (RuleKind::Unlocked, &profile.unlocked_patterns),
+            (RuleKind::AllowCreate, &profile.allow_create_patterns),
+        ];
+        for (kind, patterns) in rules {
+            for pattern in patterns {
+                self.rules.retain(|rule| {
+                    rule.pattern != *pattern
+                        || (rule.kind == RuleKind::AllowCreate) != (kind == RuleKind::AllowCreate)
+                });
+                self.rules.push(ResolvedRule {
+                    kind,
+                    pattern: pattern.clone(),
+                    origin: name.to_string(),
+                });
+            }
+        }
+        self.layers.push(name.to_string());
+    }
+
+    fn patterns(&self, kind: RuleKind) -> Vec<String> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.kind == kind)
+            .map(|rule| rule.pattern.clone())
+            .collect()
+    }
+
+    /// The composed rules as a flat, undescribed profile.
+    pub fn policy(&self) -> LockProfile {
+        LockProfile {
+            locked_patterns: self.patterns(RuleKind::Locked),
+            unlocked_patterns: self.patterns(RuleKind::Unlocked),
+            allow_create_patterns: self.patterns(RuleKind::AllowCreate),
+            ..LockProfile::default()
+        }
+    }
+}
+
+/// Whether two policies have the same rules, in any order.
+fn same_rules(a: &LockProfile, b: &LockProfile) -> bool {
+    let sorted = |patterns: &[String]| {
+        let mut patterns = patterns.to_vec();
+        patterns.sort();
+        patterns.dedup();
+        patterns
+    };
+    sorted(&a.locked_patterns) == sorted(&b.locked_patterns)
+        && sorted(&a.unlocked_patterns) == sorted(&b.unlocked_patterns)
+        && sorted(&a.allow_create_patterns) == sorted(&b.allow_create_patterns)
+}
+
+/// Activates `profile` while a branch whose name matches the `branch` glob is checked out.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct BranchRule {
+    pub branch: String,
+    pub profile: String,
+}
+
+impl BranchRule {
+    pub fn matches(&self, branch: &str) -> bool {
+        glob::Pattern::new(&self.branch).is_ok_and(|pattern| pattern.matches(branch))
+    }
+}
+
+/// A task-scoped lockdown: the whole root is locked except `scope`, and the
+/// policy in effect beforehand comes back when the quest ends.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct Quest {
+    pub name: String,
+    /// Unlocked patterns: `dir/**` for directories, plain paths for files.
+    pub scope: Vec<String>,
+    /// Locked directories where the quest may still create files.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow_create: Vec<String>,
+    /// The policy to restore.
+    pub previous: LockProfile,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_profile: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub previous_stack: Vec<String>,
+}
+
+/// Contents of an `.icaros` state file.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct AppState {
+    /// Project root, relative to the directory containing the state file.
     pub root_path: PathBuf,
 
     // Profile system
     #[serde(default)]
     pub active_profile: Option<String>,
     #[serde(default)]
-    pub profiles: HashMap<String, LockProfile>,
+    pub profiles: BTreeMap<String, LockProfile>,
+    /// Profiles layered to form the active policy, bottom first, when more than
+    /// one is active. `active_profile` names the top of the stack.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profile_stack: Vec<String>,
+    /// Agent name to the profile that governs it, for agents selected with
+    /// `ICAROS_AGENT` or `--agent` instead of the active profile.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub agents: BTreeMap<String, String>,
+    /// Branch-to-profile rules; the first match for the checked-out branch wins.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub branch_profiles: Vec<BranchRule>,
+    /// The quest in progress, if any. Branch rules don't switch profiles during one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quest: Option<Quest>,
 
     // Legacy/active state (backward compatibility + current active state)
     #[serde(default)]
@@ -53,12 +197,104 @@ pub struct AppState {
     #[serde(default)]
     pub allow_create_patterns: Vec<String>,
 
+    /// Directories expanded in the tree view, relative to the state file's directory.
     #[serde(default)]
     pub expanded_dirs: Vec<PathBuf>,
 
     // File system ignore patterns
     #[serde(default = "default_ignore_patterns")]
     pub ignore_patterns: Vec<String>,
+
+    /// Per-repository overrides of the user config.
+    #[serde(default, skip_serializing_if = "Preferences::is_empty")]
+    pub settings: Preferences,
+}
+
+/// On-disk encoding of a state file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StateFormat {
+    Yaml,
+    Json,
+    Toml,
+}
+
+impl StateFormat {
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "yaml" | "yml" => Some(StateFormat::Yaml),
+            "json" => Some(StateFormat::Json),
+            "toml" => Some(StateFormat::Toml),
+            _ => None,
+        }
+    }
+
+    /// Guess the format of existing content, for files like `.icaros` without an extension.
+    pub fn sniff(content: &str) -> Self {
+        let first_line = content
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('#'))
+            .unwrap_or("");
+
+        if first_line.starts_with('{') {
+            StateFormat::Json
+        } else if first_line.starts_with('[') || is_toml_assignment(first_line) {
+            StateFormat::Toml
+        } else {
+            StateFormat::Yaml
+        }
+    }
+
+    /// Format to write `path` in: its extension, else the format it already uses, else YAML.
+    pub fn for_path(path: &Path) -> Self {
+        Self::from_extension(path)
+            .or_else(|| fs::read_to_string(path).ok().map(|c| Self::sniff(&c)))
+            .unwrap_or(StateFormat::Yaml)
+    }
+
+    pub fn serialize<T: Serialize>(self, value: &T) -> Result<String> {
+        Ok(match self {
+            StateFormat::Yaml => serde_yaml::to_string(value)?,
+            StateFormat::Json => serde_json::to_string_pretty(value)? + "\n",
+            StateFormat::Toml => toml::to_string_pretty(value)?,
+        })
+    }
+
+    pub fn deserialize<T: serde::de::DeserializeOwned>(self, content: &str) -> Result<T> {
+        Ok(match self {
+            StateFormat::Yaml => serde_yaml::from_str(content)?,
+            StateFormat::Json => serde_json::from_str(content)?,
+            StateFormat::Toml => toml::from_str(content)?,
+        })
+    }
+}
+
+impl FromStr for StateFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "yaml" | "yml" => Ok(StateFormat::Yaml),
+            "json" => Ok(StateFormat::Json),
+            "toml" => Ok(StateFormat::Toml),
+            other => Err(format!(
+                "unknown state format '{other}' (expected yaml, json or toml)"
+            )),
+        }
+    }
+}
+
+fn is_toml_assignment(line: &str) -> bool {
+    match (line.find('='), line.find(':')) {
+        (Some(eq), Some(colon)) => eq < colon,
+        (Some(_), None) => true,
+        _ => false,
+    }
+}
+
+/// JSON Schema describing the state file, for editor validation and external tooling.
+pub fn state_json_schema() -> schemars::schema::RootSchema {
+    schemars::schema_for!(AppState)
 }
 
 impl AppState {
@@ -66,27 +302,88 @@ impl AppState {
         Self {
             root_path,
             active_profile: None,
-            profiles: HashMap::new(),
+            profiles: BTreeMap::new(),
+            profile_stack: Vec::new(),
+            agents: BTreeMap::new(),
+            branch_profiles: Vec::new(),
+            quest: None,
             locked_patterns: Vec::new(),
             unlocked_patterns: vec!["**".to_string()],
             allow_create_patterns: Vec::new(),
             expanded_dirs: Vec::new(),
             ignore_patterns: default_ignore_patterns(),
+            settings: Preferences::default(),
         }
     }
 
+    /// Paths are written relative to the state file's directory so a committed
+    /// `.icaros` keeps working when the repository is cloned or moved.
     pub fn save_to_file(&self, path: &Path) -> Result<()> {
-        let yaml = serde_yaml::to_string(self)?;
-        fs::write(path, yaml)?;
+        self.save_to_file_as(path, StateFormat::for_path(path))
+    }
+
+    pub fn save_to_file_as(&self, path: &Path, format: StateFormat) -> Result<()> {
+        let base = state_file_dir(path)?;
+        let mut portable = self.clone();
+        portable.root_path = relative_path(&self.root_path, &base);
+        portable.expanded_dirs = self
+            .expanded_dirs
+            .iter()
+            .map(|dir| relative_path(dir, &base))
+            .collect();
+
+        fs::write(path, format.serialize(&portable)?)?;
         Ok(())
     }
 
+    /// Loads a state file, resolving its relative paths against the file's directory.
+    /// Legacy files with absolute paths are accepted and rebased when the project has moved.
     pub fn load_from_file(path: &Path) -> Result<Self> {
         let content = fs::read_to_string(path)?;
-        let state = serde_yaml::from_str(&content)?;
+        let format =
+            StateFormat::from_extension(path).unwrap_or_else(|| StateFormat::sniff(&content));
+        let mut state: Self = format
+            .deserialize(&content)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        state.resolve_paths(&state_file_dir(path)?);
         Ok(state)
     }
 
+    fn resolve_paths(&mut self, base: &Path) {
+        let stored_root = normalize_path(&base.join(&self.root_path));
+        // A legacy absolute root that no longer exists means the project moved with its state file
+        let root = if self.root_path.is_absolute() && !stored_root.is_dir() {
+            base.to_path_buf()
+        } else {
+            stored_root.clone()
+        };
+
+        self.expanded_dirs = self
+            .expanded_dirs
+            .iter()
+            .map(|dir| match dir.strip_prefix(&stored_root) {
+                Ok(relative) if dir.is_absolute() => root.join(relative),
+                _ => normalize_path(&base.join(dir)),
+            })
+            .collect();
+
+        let rebase = |patterns: &mut Vec<String>| {
+            for pattern in patterns.iter_mut() {
+                *pattern = rebase_pattern(pattern, &stored_root);
+            }
+        };
+        rebase(&mut self.locked_patterns);
+        rebase(&mut self.unlocked_patterns);
+        rebase(&mut self.allow_create_patterns);
+        for profile in self.profiles.values_mut() {
+            rebase(&mut profile.locked_patterns);
+            rebase(&mut profile.unlocked_patterns);
+            rebase(&mut profile.allow_create_patterns);
+        }
+
+        self.root_path = root;
+    }
+
     pub fn update_expanded_dirs(&mut self, expanded_dirs: Vec<PathBuf>) {
         self.expanded_dirs = expanded_dirs;
     }
@@ -119,48 +416,619 @@ impl AppState {
         }
     }
 
-    // Profile management methods
-    pub fn save_current_as_profile(&mut self, name: String, description: String) {
-        let profile = LockProfile {
+    /// The policy currently in effect, as an undescribed profile. Every kind of
+    /// permission belongs here and in `apply_policy` so profiles capture all of it.
+    pub fn current_policy(&self) -> LockProfile {
+        LockProfile {
             locked_patterns: self.locked_patterns.clone(),
             unlocked_patterns: self.unlocked_patterns.clone(),
             allow_create_patterns: self.allow_create_patterns.clone(),
+            ..LockProfile::default()
+        }
+    }
+
+    pub fn apply_policy(&mut self, profile: &LockProfile) {
+        self.locked_patterns = profile.locked_patterns.clone();
+        self.unlocked_patterns = profile.unlocked_patterns.clone();
+        self.allow_create_patterns = profile.allow_create_patterns.clone();
+    }
+
+    // Profile management methods
+    /// Save the current policy as `name` and make it active. Overwriting a profile
+    /// keeps its `extends`, storing only the rules its base profiles don't provide.
+    /// If it can't keep them, it saves without bases and returns a warning saying so.
+    pub fn save_current_as_profile(&mut self, name: String, description: String) -> Option<String> {
+        let extends = self
+            .profiles
+            .get(&name)
+            .map(|profile| profile.extends.clone())
+            .unwrap_or_default();
+        let error = self
+            .save_current_extending(name.clone(), description.clone(), extends.clone())
+            .err()?;
+        self.save_current_extending(name.clone(), description, Vec::new())
+            .expect("a profile without bases always resolves");
+        Some(format!(
+            "Profile '{name}' no longer extends {}: {error}",
+            extends.join(", ")
+        ))
+    }
+
+    /// Save the current policy as `name` on top of the `extends` profiles and make it active.
+    pub fn save_current_extending(
+        &mut self,
+        name: String,
+        description: String,
+        extends: Vec<String>,
+    ) -> Result<()> {
+        if extends.contains(&name) {
+            anyhow::bail!("Profile '{name}' cannot extend itself");
+        }
+        let resolved = self.resolve_stack(&extends)?;
+        if resolved.layers.contains(&name) {
+            anyhow::bail!(
+                "Profile inheritance cycle: '{name}' would extend itself through its bases"
+            );
+        }
+        let inherited = resolved.policy();
+        let current = self.current_policy();
+        // Inherited rules the current policy no longer has are dropped outright,
+        // and what it still has for those patterns is added back below
+        let mut removed: Vec<String> = Vec::new();
+        for (inherited, current) in [
+            (&inherited.locked_patterns, &current.locked_patterns),
+            (&inherited.unlocked_patterns, &current.unlocked_patterns),
+            (
+                &inherited.allow_create_patterns,
+                &current.allow_create_patterns,
+            ),
+        ] {
+            for pattern in inherited {
+                if !current.contains(pattern) && !removed.contains(pattern) {
+                    removed.push(pattern.clone());
+                }
+            }
+        }
+        let own = |current: &[String], inherited: &[String]| -> Vec<String> {
+            current
+                .iter()
+                .filter(|pattern| removed.contains(pattern) || !inherited.contains(pattern))
+                .cloned()
+                .collect()
+        };
+        let profile = LockProfile {
+            locked_patterns: own(&current.locked_patterns, &inherited.locked_patterns),
+            unlocked_patterns: own(&current.unlocked_patterns, &inherited.unlocked_patterns),
+            allow_create_patterns: own(
+                &current.allow_create_patterns,
+                &inherited.allow_create_patterns,
+            ),
+            removed_patterns: removed,
             description,
+            extends,
         };
-        self.profiles.insert(name.clone(), profile);
+
+        let previous = self.profiles.insert(name.clone(), profile);
+        let saved = self.resolve_profile(&name)?.policy();
+        if !same_rules(&saved, &current) {
+            match previous {
+                Some(previous) => self.profiles.insert(name.clone(), previous),
+                None => self.profiles.remove(&name),
+            };
+            anyhow::bail!(
+                "Profile '{name}' would not reproduce the current locks on top of its bases"
+            );
+        }
         self.active_profile = Some(name);
+        self.profile_stack.clear();
+        Ok(())
     }
 
     pub fn switch_to_profile(&mut self, name: &str) -> bool {
-        if let Some(profile) = self.profiles.get(name) {
-            self.locked_patterns = profile.locked_patterns.clone();
-            self.unlocked_patterns = profile.unlocked_patterns.clone();
-            self.allow_create_patterns = profile.allow_create_patterns.clone();
-            self.active_profile = Some(name.to_string());
-            true
+        self.activate(&[name.to_string()]).is_ok()
+    }
+
+    /// Apply the composed policy of `stack`, bottom first, and make it the active stack.
+    /// Refused during a quest, whose locks stay until it ends.
+    pub fn activate(&mut self, stack: &[String]) -> Result<()> {
+        if let Some(quest) = &self.quest {
+            anyhow::bail!("Quest '{}' is in progress; end it first", quest.name);
+        }
+        let resolved = self.resolve_stack(stack)?;
+        self.apply_policy(&resolved.policy());
+        self.active_profile = stack.last().cloned();
+        self.profile_stack = if stack.len() > 1 {
+            stack.to_vec()
+        } else {
+            Vec::new()
+        };
+        Ok(())
+    }
+
+    /// The active profiles, bottom first.
+    pub fn active_stack(&self) -> Vec<String> {
+        if self.profile_stack.is_empty() {
+            self.active_profile.iter().cloned().collect()
         } else {
-            false
+            self.profile_stack.clone()
+        }
+    }
+
+    pub fn resolve_profile(&self, name: &str) -> Result<ResolvedPolicy> {
+        self.resolve_stack(&[name.to_string()])
+    }
+
+    /// Compose `stack` into the rules it applies. Each profile's bases are applied
+    /// before it, and a profile reached more than once contributes only the first time.
+    pub fn resolve_stack(&self, stack: &[String]) -> Result<ResolvedPolicy> {
+        let mut resolved = ResolvedPolicy::default();
+        let mut chain = Vec::new();
+        for name in stack {
+            self.resolve_into(name, &mut chain, &mut resolved)?;
+        }
+        Ok(resolved)
+    }
+
+    fn resolve_into(
+        &self,
+        name: &str,
+        chain: &mut Vec<String>,
+        resolved: &mut ResolvedPolicy,
+    ) -> Result<()> {
+        if let Some(start) = chain.iter().position(|n| n == name) {
+            let cycle: Vec<&str> = chain[start..]
+                .iter()
+                .map(String::as_str)
+                .chain([name])
+                .collect();
+            anyhow::bail!("Profile inheritance cycle: {}", cycle.join(" -> "));
+        }
+        if resolved.layers.iter().any(|layer| layer == name) {
+            return Ok(());
+        }
+        let Some(profile) = self.profiles.get(name) else {
+            match chain.last() {
+                Some(parent) => {
+                    anyhow::bail!("Profile '{parent}' extends unknown profile '{name}'")
+                }
+                None => anyhow::bail!("Profile '{name}' not found"),
+            }
+        };
+
+        chain.push(name.to_string());
+        for base in &profile.extends {
+            self.resolve_into(base, chain, resolved)?;
         }
+        chain.pop();
+        resolved.push_layer(name, profile);
+        Ok(())
+    }
+
+    /// `name` with its inherited rules folded in, so it stands alone outside this repository.
+    pub fn standalone_profile(&self, name: &str) -> Result<LockProfile> {
+        let description = self
+            .profiles
+            .get(name)
+            .map(|profile| profile.description.clone())
+            .unwrap_or_default();
+        Ok(LockProfile {
+            description,
+            ..self.resolve_profile(name)?.policy()
+        })
+    }
+
+    /// Bind `agent` to `profile`. Fails if the profile doesn't exist.
+    pub fn bind_agent(&mut self, agent: &str, profile: &str) -> bool {
+        if !self.profiles.contains_key(profile) {
+            return false;
+        }
+        self.agents.insert(agent.to_string(), profile.to_string());
+        true
+    }
+
+    pub fn unbind_agent(&mut self, agent: &str) -> bool {
+        self.agents.remove(agent).is_some()
+    }
+
+    /// Agents bound to `profile`.
+    pub fn agents_using(&self, profile: &str) -> Vec<String> {
+        self.agents
+            .iter()
+            .filter(|(_, bound)| *bound == profile)
+            .map(|(agent, _)| agent.clone())
+            .collect()
+    }
+
+    /// The resolved policy that applies to `agent`.
+    pub fn agent_policy(&self, agent: &str) -> Result<LockProfile> {
+        let Some(profile) = self.agents.get(agent) else {
+            if self.agents.is_empty() {
+                anyhow::bail!("Agent '{agent}' has no profile (no agents are configured)");
+            }
+            let known: Vec<&str> = self.agents.keys().map(String::as_str).collect();
+            anyhow::bail!(
+                "Agent '{agent}' has no profile (known agents: {})",
+                known.join(", ")
+            );
+        };
+        self.resolve_profile(profile)
+            .map(|resolved| resolved.policy())
+            .with_context(|| format!("Agent '{agent}' uses profile '{profile}'"))
+    }
+
+    /// Profiles that name `name` in their `extends`.
+    pub fn profiles_extending(&self, name: &str) -> Vec<String> {
+        self.profiles
+            .iter()
+            .filter(|(_, profile)| profile.extends.iter().any(|base| base == name))
+            .map(|(other, _)| other.clone())
+            .collect()
     }
 
     pub fn get_profile_names(&self) -> Vec<String> {
         self.profiles.keys().cloned().collect()
     }
 
+    /// Delete a profile. Fails if it is missing, or another profile or an agent uses it.
     pub fn delete_profile(&mut self, name: &str) -> bool {
-        if self.profiles.remove(name).is_some() {
-            if self.active_profile.as_ref() == Some(&name.to_string()) {
-                self.active_profile = None;
+        if !self.profiles_extending(name).is_empty()
+            || !self.agents_using(name).is_empty()
+            || self.profiles.remove(name).is_none()
+        {
+            return false;
+        }
+        if self.active_stack().iter().any(|layer| layer == name) {
+            self.active_profile = None;
+            self.profile_stack.clear();
+        }
+        true
+    }
+
+    /// Rename a profile, updating everything that refers to it. Fails if `from`
+    /// is missing or `to` exists.
+    pub fn rename_profile(&mut self, from: &str, to: &str) -> bool {
+        if self.profiles.contains_key(to) {
+            return false;
+        }
+        let Some(profile) = self.profiles.remove(from) else {
+            return false;
+        };
+        self.profiles.insert(to.to_string(), profile);
+
+        let rename = |name: &mut String| {
+            if name == from {
+                *name = to.to_string();
             }
-            true
-        } else {
-            false
+        };
+        for profile in self.profiles.values_mut() {
+            profile.extends.iter_mut().for_each(rename);
+        }
+        self.profile_stack.iter_mut().for_each(rename);
+        self.active_profile.iter_mut().for_each(rename);
+        self.agents.values_mut().for_each(rename);
+        for rule in &mut self.branch_profiles {
+            rename(&mut rule.profile);
+        }
+        true
+    }
+
+    /// Copy a profile under a new name. Fails if `from` is missing or `to` exists.
+    pub fn copy_profile(&mut self, from: &str, to: &str) -> bool {
+        if self.profiles.contains_key(to) {
+            return false;
+        }
+        match self.profiles.get(from).cloned() {
+            Some(profile) => {
+                self.profiles.insert(to.to_string(), profile);
+                true
+            }
+            None => false,
         }
     }
 
+    /// Lock everything except `scope` until `end_quest`, remembering the current
+    /// policy and profiles. New files may also be created in `allow_create`.
+    pub fn start_quest(
+        &mut self,
+        name: &str,
+        scope: Vec<String>,
+        allow_create: Vec<String>,
+    ) -> Result<()> {
+        if let Some(quest) = &self.quest {
+            anyhow::bail!(
+                "Quest '{}' is already in progress; end it first",
+                quest.name
+            );
+        }
+        if scope.is_empty() {
+            anyhow::bail!("A quest needs at least one path to allow");
+        }
+        if scope.iter().any(|pattern| pattern == "**") {
+            anyhow::bail!("A quest can't allow the whole root");
+        }
+        self.quest = Some(Quest {
+            name: name.to_string(),
+            scope: scope.clone(),
+            allow_create: allow_create.clone(),
+            previous: self.current_policy(),
+            previous_profile: self.active_profile.take(),
+            previous_stack: std::mem::take(&mut self.profile_stack),
+        });
+        self.apply_policy(&LockProfile {
+            locked_patterns: vec!["**".to_string()],
+            unlocked_patterns: scope,
+            allow_create_patterns: allow_create,
+            ..LockProfile::default()
+        });
+        Ok(())
+    }
+
+    /// Restore the policy and profiles from before the quest, returning it.
+    pub fn end_quest(&mut self) -> Result<Quest> {
+        let quest = self
+            .quest
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("No quest is in progress"))?;
+        self.apply_policy(&quest.previous);
+        self.active_profile = quest.previous_profile.clone();
+        self.profile_stack = quest.previous_stack.clone();
+        // Profiles deleted during the quest can't be active again
+        if self
+            .active_stack()
+            .iter()
+            .any(|name| !self.profiles.contains_key(name))
+        {
+            self.active_profile = None;
+            self.profile_stack.clear();
+        }
+        Ok(quest)
+    }
+
     pub fn get_active_profile_name(&self) -> Option<&String> {
         self.active_profile.as_ref()
     }
+
+    /// Profile that the branch rules map `branch` to.
+    pub fn profile_for_branch(&self, branch: &str) -> Option<&str> {
+        self.branch_profiles
+            .iter()
+            .find(|rule| rule.matches(branch))
+            .map(|rule| rule.profile.as_str())
+    }
+
+    /// Switch to the profile mapped to `branch` unless it is already active.
+    /// Returns the profile that was switched to.
+    pub fn follow_branch(&mut self, branch: &str) -> Option<String> {
+        if self.quest.is_some() {
+            return None;
+        }
+        let profile = self.profile_for_branch(branch)?.to_string();
+        if self.active_profile.as_deref() == Some(profile.as_str()) {
+            return None;
+        }
+        self.switch_to_profile(&profile).then_some(profile)
+    }
+
+    /// Whether `path` (relative to the root) is locked. The most specific matching
+    /// pattern decides, and an unlock beats a lock at the same depth.
+    pub fn is_locked(&self, path: &Path) -> bool {
+        self.rules().lock_depth(path).is_some()
+    }
+
+    /// Whether a new file may be created at `path`: either it isn't locked, or a
+    /// directory between it and the lock allows creating files.
+    pub fn can_create(&self, path: &Path) -> bool {
+        self.rules().can_create(path)
+    }
+
+    fn rules(&self) -> Rules<'_> {
+        Rules {
+            locked: &self.locked_patterns,
+            unlocked: &self.unlocked_patterns,
+            allow_create: &self.allow_create_patterns,
+        }
+    }
+}
+
+impl LockProfile {
+    /// Reads a standalone profile file, as written by `icaros profile export`.
+    pub fn load_from_file(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let format =
+            StateFormat::from_extension(path).unwrap_or_else(|| StateFormat::sniff(&content));
+        format
+            .deserialize(&content)
+            .with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn save_to_file(&self, path: &Path) -> Result<()> {
+        let format = StateFormat::from_extension(path).unwrap_or(StateFormat::Yaml);
+        fs::write(path, format.serialize(self)?)?;
+        Ok(())
+    }
+
+    /// Patterns that name nothing under `root`, or that point outside it.
+    pub fn unresolved_patterns(&self, root: &Path) -> Vec<String> {
+        self.locked_patterns
+            .iter()
+            .chain(&self.unlocked_patterns)
+            .chain(&self.allow_create_patterns)
+            .filter(|pattern| {
+                if pattern.as_str() == "**" {
+                    return false;
+                }
+                let path = Path::new(pattern.strip_suffix("/**").unwrap_or(pattern));
+                let inside = path
+                    .components()
+                    .all(|component| matches!(component, Component::Normal(_)));
+                !inside || !root.join(path).exists()
+            })
+            .cloned()
+            .collect()
+    }
+
+    pub fn remove_patterns(&mut self, patterns: &[String]) {
+        for list in [
+            &mut self.locked_patterns,
+            &mut self.unlocked_patterns,
+            &mut self.allow_create_patterns,
+        ] {
+            list.retain(|pattern| !patterns.contains(pattern));
+        }
+    }
+
+    /// Whether `path` is locked by this profile's own rules, as for `AppState::is_locked`.
+    pub fn is_locked(&self, path: &Path) -> bool {
+        self.rules().lock_depth(path).is_some()
+    }
+
+    /// Whether a new file may be created at `path`, as for `AppState::can_create`.
+    pub fn can_create(&self, path: &Path) -> bool {
+        self.rules().can_create(path)
+    }
+
+    /// Whether `dir` is locked but lets new files be created directly inside it.
+    pub fn allows_create_in(&self, dir: &Path) -> bool {
+        self.rules().allows_create_in(dir)
+    }
+
+    fn rules(&self) -> Rules<'_> {
+        Rules {
+            locked: &self.locked_patterns,
+            unlocked: &self.unlocked_patterns,
+            allow_create: &self.allow_create_patterns,
+        }
+    }
+}
+
+/// The pattern lists that decide what is locked, borrowed from a state or profile.
+struct Rules<'a> {
+    locked: &'a [String],
+    unlocked: &'a [String],
+    allow_create: &'a [String],
+}
+
+impl Rules<'_> {
+    /// Depth of the pattern that locks `path`, if it is locked.
+    fn lock_depth(&self, path: &Path) -> Option<usize> {
+        let deepest = |patterns: &[String]| {
+            patterns
+                .iter()
+                .filter_map(|pattern| pattern_depth(pattern, path))
+                .max()
+        };
+        match (deepest(self.locked), deepest(self.unlocked)) {
+            (Some(locked), Some(unlocked)) if locked > unlocked => Some(locked),
+            (Some(locked), None) => Some(locked),
+            _ => None,
+        }
+    }
+
+    fn can_create(&self, path: &Path) -> bool {
+        match self.lock_depth(path) {
+            None => true,
+            Some(depth) => self.allow_create.iter().any(|pattern| {
+                pattern_depth(pattern, path)
+                    .is_some_and(|d| d >= depth && d < path.components().count())
+            }),
+        }
+    }
+
+    fn allows_create_in(&self, dir: &Path) -> bool {
+        match self.lock_depth(dir) {
+            None => false,
+            Some(depth) => self
+                .allow_create
+                .iter()
+                .any(|pattern| pattern_depth(pattern, dir).is_some_and(|d| d >= depth)),
+        }
+    }
+}
+
+/// Number of components in the directory or file `pattern` names if it covers
+/// `path`, e.g. 1 for `src/**` and `src/main.rs`; `**` covers everything at depth 0.
+fn pattern_depth(pattern: &str, path: &Path) -> Option<usize> {
+    if pattern == "**" {
+        return Some(0);
+    }
+    let base = Path::new(pattern.strip_suffix("/**").unwrap_or(pattern));
+    path.starts_with(base).then(|| base.components().count())
+}
+
+fn state_file_dir(path: &Path) -> Result<PathBuf> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    Ok(normalize_path(&std::path::absolute(dir)?))
+}
+
+/// Lexically resolve `.` and `..` components without touching the filesystem.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push(component);
+                }
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Express `path` relative to `base`, falling back to `path` when they share no root.
+pub fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let path = normalize_path(path);
+    let base = normalize_path(base);
+
+    let path_components: Vec<_> = path.components().collect();
+    let base_components: Vec<_> = base.components().collect();
+    let common = path_components
+        .iter()
+        .zip(&base_components)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    if common == 0 && path.is_absolute() {
+        return path;
+    }
+
+    let mut relative = PathBuf::new();
+    for _ in common..base_components.len() {
+        relative.push("..");
+    }
+    for component in &path_components[common..] {
+        relative.push(component);
+    }
+
+    if relative.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        relative
+    }
+}
+
+/// Turn a legacy absolute pattern under `root` into the root-relative form.
+fn rebase_pattern(pattern: &str, root: &Path) -> String {
+    let (path, suffix) = match pattern.strip_suffix("/**") {
+        Some(dir) => (dir, "/**"),
+        None => (pattern, ""),
+    };
+    let path = Path::new(path);
+    if !path.is_absolute() {
+        return pattern.to_string();
+    }
+
+    match path.strip_prefix(root) {
+        Ok(relative) if relative.as_os_str().is_empty() => "**".to_string(),
+        Ok(relative) => format!("{}{suffix}", relative.display()),
+        Err(_) => pattern.to_string(),
+    }
 }
 
 #[derive(Clone)]
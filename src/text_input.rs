@@ -0,0 +1,222 @@
+use crossterm::event::KeyCode;
+
+/// A single-line text field with a cursor, for the TUI's prompts.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextInput {
+    value: String,
+    // Position in chars, from 0 to the length of `value`
+    cursor: usize,
+}
+
+impl TextInput {
+    /// A field holding `value`, with the cursor at the end.
+    pub fn new(value: &str) -> Self {
+        Self {
+            value: value.to_string(),
+            cursor: value.chars().count(),
+        }
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn insert(&mut self, c: char) {
+        let at = self.byte_index(self.cursor);
+        self.value.insert(at, c);
+        self.cursor += 1;
+    }
+
+    /// Remove the character before the cursor.
+    pub fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            let at = self.byte_index(self.cursor);
+            self.value.remove(at);
+        }
+    }
+
+    /// Remove the character under the cursor.
+    pub fn delete(&mut self) {
+        if self.cursor < self.value.chars().count() {
+            let at = self.byte_index(self.cursor);
+            self.value.remove(at);
+        }
+    }
+
+    pub fn move_left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn move_right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.value.chars().count());
+    }
+
+    /// Apply an editing key. Returns false for keys the field doesn't handle,
+    /// such as Enter and Esc.
+    pub fn handle_key(&mut self, code: KeyCode) -> bool {
+        match code {
+            KeyCode::Char(c) => self.insert(c),
+            KeyCode::Backspace => self.backspace(),
+            KeyCode::Delete => self.delete(),
+            KeyCode::Left => self.move_left(),
+            KeyCode::Right => self.move_right(),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.value.chars().count(),
+            _ => return false,
+        }
+        true
+    }
+
+    /// The text before the cursor, the character under it, and the text after.
+    pub fn split_at_cursor(&self) -> (&str, Option<char>, &str) {
+        let at = self.byte_index(self.cursor);
+        let (before, rest) = self.value.split_at(at);
+        let mut chars = rest.chars();
+        let under = chars.next();
+        (before, under, chars.as_str())
+    }
+
+    fn byte_index(&self, chars: usize) -> usize {
+        self.value
+            .char_indices()
+            .nth(chars)
+            .map_or(self.value.len(), |(index, _)| index)
+    }
+}
+
+/// A multi-line text field with a cursor, where Enter starts a new line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextArea {
+    lines: Vec<TextInput>,
+    row: usize,
+}
+
+impl Default for TextArea {
+    fn default() -> Self {
+        Self {
+            lines: vec![TextInput::default()],
+            row: 0,
+        }
+    }
+}
+
+impl TextArea {
+    /// A field holding `value`, with the cursor at the end.
+    pub fn new(value: &str) -> Self {
+        let lines: Vec<TextInput> = value.split('\n').map(TextInput::new).collect();
+        Self {
+            row: lines.len() - 1,
+            lines,
+        }
+    }
+
+    pub fn value(&self) -> String {
+        self.lines
+            .iter()
+            .map(TextInput::value)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn lines(&self) -> &[TextInput] {
+        &self.lines
+    }
+
+    /// The line the cursor is on.
+    pub fn row(&self) -> usize {
+        self.row
+    }
+
+    pub fn is_blank(&self) -> bool {
+        self.lines.iter().all(|line| line.value().trim().is_empty())
+    }
+
+    /// Split the current line at the cursor.
+    pub fn newline(&mut self) {
+        let line = &mut self.lines[self.row];
+        let (before, under, after) = line.split_at_cursor();
+        let rest: String = under.into_iter().chain(after.chars()).collect();
+        let before = before.to_string();
+        *line = TextInput::new(&before);
+        let mut next = TextInput::new(&rest);
+        next.cursor = 0;
+        self.row += 1;
+        self.lines.insert(self.row, next);
+    }
+
+    /// Remove the character before the cursor, joining lines at the start of one.
+    pub fn backspace(&mut self) {
+        if self.lines[self.row].cursor > 0 {
+            self.lines[self.row].backspace();
+        } else if self.row > 0 {
+            let line = self.lines.remove(self.row);
+            self.row -= 1;
+            let previous = &mut self.lines[self.row];
+            let at = previous.value().chars().count();
+            *previous = TextInput::new(&format!("{}{}", previous.value(), line.value()));
+            previous.cursor = at;
+        }
+    }
+
+    /// Remove the character under the cursor, joining lines at the end of one.
+    pub fn delete(&mut self) {
+        let line = &self.lines[self.row];
+        if line.cursor < line.value().chars().count() {
+            self.lines[self.row].delete();
+        } else if self.row + 1 < self.lines.len() {
+            let next = self.lines.remove(self.row + 1);
+            let line = &mut self.lines[self.row];
+            let at = line.cursor;
+            *line = TextInput::new(&format!("{}{}", line.value(), next.value()));
+            line.cursor = at;
+        }
+    }
+
+    /// Move to the line above or below, keeping the column where it fits.
+    fn move_vertically(&mut self, down: bool) {
+        let target = if down {
+            (self.row + 1).min(self.lines.len() - 1)
+        } else {
+            self.row.saturating_sub(1)
+        };
+        let column = self.lines[self.row].cursor;
+        self.row = target;
+        let line = &mut self.lines[self.row];
+        line.cursor = column.min(line.value().chars().count());
+    }
+
+    /// Apply an editing key. Returns false for keys the field doesn't handle,
+    /// such as Esc and Tab.
+    pub fn handle_key(&mut self, code: KeyCode) -> bool {
+        match code {
+            KeyCode::Enter => self.newline(),
+            KeyCode::Backspace => self.backspace(),
+            KeyCode::Delete => self.delete(),
+            KeyCode::Up => self.move_vertically(false),
+            KeyCode::Down => self.move_vertically(true),
+            KeyCode::Left if self.lines[self.row].cursor == 0 && self.row > 0 => {
+                self.row -= 1;
+                let line = &mut self.lines[self.row];
+                line.cursor = line.value().chars().count();
+            }
+            KeyCode::Right
+                if self.lines[self.row].cursor == self.lines[self.row].value().chars().count()
+                    && self.row + 1 < self.lines.len() =>
+            {
+                self.row += 1;
+                self.lines[self.row].cursor = 0;
+            }
+            code => return self.lines[self.row].handle_key(code),
+        }
+        true
+    }
+}
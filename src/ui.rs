@@ -1,10 +1,19 @@
 use crate::animations::AnimationEngine;
+use crate::config::{EnforcementMode, Settings, ThemeName};
+use crate::diff_layout::{self, Segment, SideBySideRow};
 use crate::file_tree::TreeNode;
-use crate::git::{GitFile, GitHunk, GitManager};
+use crate::git::{
+    CommitOptions, DiffTarget, DiscardSource, GitFile, GitFileStatus, GitHunk, GitManager,
+    ReviewBase, ReviewFile,
+};
+use crate::highlight::{self, DiffHighlights, Token};
 use crate::log_debug;
+use crate::policy_diff::{self, PathChange, PolicyDiff};
+use crate::state::{Quest, ResolvedPolicy, RuleKind};
+use crate::text_input::{TextArea, TextInput};
 use anyhow::Result;
 use crossterm::{
-    event::{self, Event, KeyCode},
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
@@ -17,7 +26,7 @@ use ratatui::{
     widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
     Terminal,
 };
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 use std::io;
 use std::sync::mpsc::{channel, Receiver};
 use std::time::{Duration, Instant};
@@ -39,7 +48,11 @@ pub struct App {
     pub last_refresh: Instant,
     pub explicitly_locked_paths: Vec<std::path::PathBuf>,
     pub explicitly_unlocked_paths: Vec<std::path::PathBuf>,
+    pub explicitly_allow_create_paths: Vec<std::path::PathBuf>,
     pub show_hidden: bool,
+    // Paths marked to be allowed by the next quest
+    pub quest_marks: Vec<std::path::PathBuf>,
+    pub active_quest: Option<Quest>,
     // Tab support
     pub active_tab: TabIndex,
     // Git support
@@ -48,17 +61,60 @@ pub struct App {
     pub git_file_list_state: ListState,
     pub git_selected_file: usize,
     pub git_diff_hunks: Vec<GitHunk>,
+    // Renames, mode changes and binary summaries, shown above the hunks
+    pub git_diff_notes: Vec<String>,
+    // Syntax colors for both versions of the file in the diff
+    pub git_diff_highlights: DiffHighlights,
     pub git_diff_scroll: u16,
     pub git_selected_hunk: usize,
     pub git_pane: GitPane,
+    // Which sides of the index the diff pane compares
+    pub git_diff_target: DiffTarget,
+    // Show the diff in two columns, and leave whitespace-only changes out of it
+    pub git_side_by_side: bool,
+    pub git_ignore_whitespace: bool,
+    // Outcome of the last Git Stage action, shown in the diff title
+    pub git_message: Option<String>,
+    // The commit dialog, while it is open
+    pub commit_dialog: Option<CommitDialog>,
+    // A discard waiting for the user to confirm it
+    pub git_pending_discard: Option<Discard>,
+    // How each entry of `git_files` breaks the lock policy, if it does
+    pub git_violations: Vec<Option<LockViolation>>,
+    // Staging of a locked file waiting for the user to confirm it
+    pub git_pending_locked_stage: Option<LockedStage>,
+    // Review mode, listing everything changed since a base instead of git status
+    pub git_review: Option<Review>,
+    // The prompt for a review base, while it is open
+    pub git_review_input: Option<TextInput>,
+    // The session checkpoint being taken in the background
+    git_checkpoint: Option<std::thread::JoinHandle<Result<git2::Oid>>>,
+    // Whether this App took the session checkpoint, and removes it when dropped
+    owns_checkpoint: bool,
+    // Line mode: a cursor over the selected hunk's lines, and the lines picked
+    pub git_line_mode: bool,
+    pub git_line_cursor: usize,
+    pub git_selected_lines: BTreeSet<usize>,
     pub show_help: bool,
     // Profile system
     pub profile_list_state: ListState,
     pub profile_names: Vec<String>,
     pub active_profile_name: Option<String>,
+    // Active profiles, bottom first, when several are layered
+    pub active_profile_stack: Vec<String>,
+    // Agents bound to each profile
+    pub profile_agents: std::collections::BTreeMap<String, Vec<String>>,
+    // Resolved rules of the selected profile, or why it doesn't resolve
+    pub selected_profile_rules: Option<std::result::Result<ResolvedPolicy, String>>,
+    // What switching to the selected profile would change
+    pub selected_profile_preview: Option<PolicyDiff>,
+    // Whether the locks have changed since the active profiles were loaded
+    pub active_profile_modified: bool,
     pub profile_input_mode: bool,
-    pub profile_input_buffer: String,
+    pub profile_input: TextInput,
     pub profile_action: ProfileAction,
+    // Outcome of the last profile action worth reporting, shown in the Profiles tab
+    pub profile_message: Option<String>,
     // Animation system
     pub profile_switching: bool,
     // Simple animation engine
@@ -69,6 +125,8 @@ pub struct App {
     pub current_image_path: Option<String>,
     // Stateful image protocol for better rendering
     pub image_state: Option<Box<dyn ratatui_image::protocol::Protocol>>,
+    // User config layered with per-repo overrides
+    pub settings: Settings,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -82,6 +140,85 @@ pub enum TabIndex {
 pub enum ProfileAction {
     None,
     Save,
+    Rename,
+    Duplicate,
+    Describe,
+    // Replace the selected profile with the current locks, once confirmed
+    Overwrite,
+}
+
+/// What a discard in the Git Stage tab throws away.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Discard {
+    // The selected file's changes in its section, or the file if untracked
+    File,
+    Hunk,
+    Lines,
+    // Every change to a file that breaks a lock, staged or not
+    Locked,
+}
+
+/// How a changed file breaks the lock policy.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LockViolation {
+    /// A locked file was edited
+    Modified,
+    /// A new file in a locked directory that doesn't allow creating files
+    Created,
+    /// A locked file was deleted, or renamed away
+    Deleted,
+}
+
+impl LockViolation {
+    pub fn label(self) -> &'static str {
+        match self {
+            LockViolation::Modified => "locked file modified",
+            LockViolation::Created => "created in a locked directory",
+            LockViolation::Deleted => "locked file deleted",
+        }
+    }
+}
+
+/// Staging of a file that breaks a lock, waiting for confirmation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LockedStage {
+    File,
+    Hunk,
+}
+
+/// The Git Stage tab's commit dialog.
+#[derive(Debug, Clone, Default)]
+pub struct CommitDialog {
+    pub message: TextArea,
+    pub author: TextInput,
+    pub amend: bool,
+    // Record `author` instead of the configured user
+    pub custom_author: bool,
+    pub sign_off: bool,
+    // Commit even though some staged paths are locked
+    pub override_locks: bool,
+    // Whether keys go to the author field rather than the message
+    pub editing_author: bool,
+    pub staged: Vec<GitFile>,
+    // Staged paths the current policy doesn't let change
+    pub locked: Vec<std::path::PathBuf>,
+    pub error: Option<String>,
+}
+
+/// Review mode's base, and the files changed since it.
+#[derive(Debug, Clone)]
+pub struct Review {
+    pub base: ReviewBase,
+    pub commit: git2::Oid,
+    pub files: Vec<ReviewFile>,
+}
+
+/// What the file watcher saw change.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FsEvent {
+    Tree,
+    // `.git/HEAD` moved, e.g. after `git checkout`
+    Head,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -139,6 +276,8 @@ impl App {
             Vec::new()
         };
 
+        let settings = Settings::load(&state_file);
+
         let mut app = Self {
             tree,
             list_state: ListState::default(),
@@ -148,7 +287,7 @@ impl App {
             root_path,
             frame_count: 0,
             _last_update: Instant::now(),
-            animations_enabled: true,
+            animations_enabled: settings.animations,
             llama_x: 0.0,
             day_night_cycle: 0.0,
             wave_offset: 0.0,
@@ -156,33 +295,61 @@ impl App {
             last_refresh: Instant::now(),
             explicitly_locked_paths: Vec::new(),
             explicitly_unlocked_paths: Vec::new(),
+            explicitly_allow_create_paths: Vec::new(),
             show_hidden: false,
+            quest_marks: Vec::new(),
+            active_quest: None,
             active_tab: TabIndex::FileGuardian,
             git_manager,
             git_files,
             git_file_list_state: ListState::default(),
             git_selected_file: 0,
             git_diff_hunks: Vec::new(),
+            git_diff_notes: Vec::new(),
+            git_diff_highlights: DiffHighlights::default(),
             git_diff_scroll: 0,
             git_selected_hunk: 0,
             git_pane: GitPane::FileList,
+            git_diff_target: DiffTarget::Unstaged,
+            git_side_by_side: false,
+            git_ignore_whitespace: false,
+            git_message: None,
+            commit_dialog: None,
+            git_pending_discard: None,
+            git_violations: Vec::new(),
+            git_pending_locked_stage: None,
+            git_review: None,
+            git_review_input: None,
+            git_checkpoint: None,
+            owns_checkpoint: false,
+            git_line_mode: false,
+            git_line_cursor: 0,
+            git_selected_lines: BTreeSet::new(),
             show_help: false,
             profile_list_state: ListState::default(),
             profile_names: Vec::new(),
             active_profile_name: None,
+            active_profile_stack: Vec::new(),
+            profile_agents: std::collections::BTreeMap::new(),
+            selected_profile_rules: None,
+            selected_profile_preview: None,
+            active_profile_modified: false,
             profile_input_mode: false,
-            profile_input_buffer: String::new(),
+            profile_input: TextInput::default(),
             profile_action: ProfileAction::None,
+            profile_message: None,
             profile_switching: false,
             animation_engine: AnimationEngine::new(),
             pending_profile_switch: None,
             current_image_path: None,
             image_state: None,
+            settings,
         };
         app.update_items();
         app.list_state.select(Some(0));
-        if !app.git_files.is_empty() {
+        if let Some(file) = app.git_files.first() {
             app.git_file_list_state.select(Some(0));
+            app.git_diff_target = DiffTarget::side(file.staged);
         }
 
         // Load animation spells
@@ -197,6 +364,11 @@ impl App {
         app
     }
 
+    /// Whether `code` is the key bound to `action` in the settings.
+    pub fn is_key(&self, action: &str, code: KeyCode) -> bool {
+        key_matches(self.settings.key_for(action), code)
+    }
+
     fn update_animations(&mut self, width: u16) {
         // Update llama position (slow wandering)
         self.llama_x += 0.3;
@@ -240,102 +412,110 @@ impl App {
             let path = self.items[self.selected].0.path.clone();
             let is_dir = self.items[self.selected].0.is_dir;
 
-            // Determine the effective lock state of this path
-            let was_locked = self.is_path_effectively_locked(&path);
+            self.toggle_path(&path, is_dir);
 
-            if std::env::var("ICAROS_DEBUG").is_ok() {
-                eprintln!("Toggle: {path:?}, was_locked: {was_locked}");
-                eprintln!("  Explicitly locked: {:?}", self.explicitly_locked_paths);
-                eprintln!(
-                    "  Explicitly unlocked: {:?}",
-                    self.explicitly_unlocked_paths
-                );
+            // Ensure selection stays on the same path
+            for (i, (item_node, _)) in self.items.iter().enumerate() {
+                if item_node.path == path {
+                    self.selected = i;
+                    self.list_state.select(Some(i));
+                    break;
+                }
             }
 
-            if !was_locked {
-                // LOCKING a node
-                self.explicitly_locked_paths.push(path.clone());
+            self.save_state();
+        }
+    }
+
+    /// Lock or unlock `path`, pruning redundant child entries the same way the
+    /// File Guardian tab does. The caller is responsible for saving.
+    pub fn toggle_path(&mut self, path: &std::path::Path, is_dir: bool) {
+        let path = path.to_path_buf();
+
+        // Determine the effective lock state of this path
+        let was_locked = self.is_path_effectively_locked(&path);
+
+        if std::env::var("ICAROS_DEBUG").is_ok() {
+            eprintln!("Toggle: {path:?}, was_locked: {was_locked}");
+            eprintln!("  Explicitly locked: {:?}", self.explicitly_locked_paths);
+            eprintln!(
+                "  Explicitly unlocked: {:?}",
+                self.explicitly_unlocked_paths
+            );
+        }
+
+        if !was_locked {
+            // LOCKING a node
+            self.explicitly_locked_paths.push(path.clone());
+
+            // Trigger lock animation
+            self.animation_engine.trigger("file_locked");
+
+            // Remove this path from explicitly unlocked if it was there
+            self.explicitly_unlocked_paths.retain(|p| p != &path);
+
+            // If locking a directory, clean up redundant child states
+            if is_dir {
+                // Remove child locks (they're now redundant)
+                self.explicitly_locked_paths
+                    .retain(|p| !p.starts_with(&path) || p == &path);
+                // Remove child unlocks (they're overridden by the lock)
+                self.explicitly_unlocked_paths
+                    .retain(|p| !p.starts_with(&path));
+            }
+        } else {
+            // UNLOCKING a node
+            // First check if this is an explicit lock
+            let is_explicitly_locked = self.explicitly_locked_paths.contains(&path);
 
-                // Trigger lock animation
-                self.animation_engine.trigger("file_locked");
+            if is_explicitly_locked {
+                // Remove the explicit lock
+                self.explicitly_locked_paths.retain(|p| p != &path);
 
-                // Remove this path from explicitly unlocked if it was there
-                self.explicitly_unlocked_paths.retain(|p| p != &path);
+                // Trigger unlock animation
+                self.animation_engine.trigger("file_unlocked");
 
-                // If locking a directory, clean up redundant child states
+                // If unlocking a directory that was explicitly locked,
+                // remove redundant child states
                 if is_dir {
-                    // Remove child locks (they're now redundant)
+                    // Remove child locks (parent is now unlocked)
                     self.explicitly_locked_paths
-                        .retain(|p| !p.starts_with(&path) || p == &path);
-                    // Remove child unlocks (they're overridden by the lock)
+                        .retain(|p| !p.starts_with(&path));
+                    // Remove child unlocks (they're redundant now)
                     self.explicitly_unlocked_paths
                         .retain(|p| !p.starts_with(&path));
                 }
             } else {
-                // UNLOCKING a node
-                // First check if this is an explicit lock
-                let is_explicitly_locked = self.explicitly_locked_paths.contains(&path);
+                // This is an inherited lock, check if we need to explicitly unlock
+                let has_locked_parent = self.has_locked_ancestor(&path);
 
-                if is_explicitly_locked {
-                    // Remove the explicit lock
-                    self.explicitly_locked_paths.retain(|p| p != &path);
+                if has_locked_parent {
+                    // Add explicit unlock
+                    self.explicitly_unlocked_paths.push(path.clone());
 
-                    // Trigger unlock animation
-                    self.animation_engine.trigger("file_unlocked");
-
-                    // If unlocking a directory that was explicitly locked,
-                    // remove redundant child states
+                    // If unlocking a directory, remove child states
                     if is_dir {
-                        // Remove child locks (parent is now unlocked)
+                        // Remove child locks
                         self.explicitly_locked_paths
-                            .retain(|p| !p.starts_with(&path));
-                        // Remove child unlocks (they're redundant now)
+                            .retain(|p| !p.starts_with(&path) || p == &path);
+                        // Remove child unlocks
                         self.explicitly_unlocked_paths
-                            .retain(|p| !p.starts_with(&path));
-                    }
-                } else {
-                    // This is an inherited lock, check if we need to explicitly unlock
-                    let has_locked_parent = self.has_locked_ancestor(&path);
-
-                    if has_locked_parent {
-                        // Add explicit unlock
-                        self.explicitly_unlocked_paths.push(path.clone());
-
-                        // If unlocking a directory, remove child states
-                        if is_dir {
-                            // Remove child locks
-                            self.explicitly_locked_paths
-                                .retain(|p| !p.starts_with(&path) || p == &path);
-                            // Remove child unlocks
-                            self.explicitly_unlocked_paths
-                                .retain(|p| !p.starts_with(&path) || p == &path);
-                        }
+                            .retain(|p| !p.starts_with(&path) || p == &path);
                     }
                 }
             }
+        }
 
-            // Clean up redundant entries
-            self.cleanup_lock_lists();
-
-            // Reapply all locks to ensure correct state
-            self.reapply_explicit_locks();
-
-            self.update_items();
+        // Clean up redundant entries
+        self.cleanup_lock_lists();
 
-            // Ensure selection stays on the same path
-            for (i, (item_node, _)) in self.items.iter().enumerate() {
-                if item_node.path == path {
-                    self.selected = i;
-                    self.list_state.select(Some(i));
-                    break;
-                }
-            }
+        // Reapply all locks to ensure correct state
+        self.reapply_explicit_locks();
 
-            self.save_state();
-        }
+        self.update_items();
     }
 
-    fn is_path_effectively_locked(&self, path: &std::path::Path) -> bool {
+    pub fn is_path_effectively_locked(&self, path: &std::path::Path) -> bool {
         // First check if this exact path is explicitly unlocked
         if self.explicitly_unlocked_paths.contains(&path.to_path_buf()) {
             return false;
@@ -427,6 +607,15 @@ impl App {
             }
             false // Remove this unlock (no locked ancestor)
         });
+
+        // Allow-create exceptions only make sense inside a lock
+        let mut seen_allow_create = HashSet::new();
+        let allow_create = std::mem::take(&mut self.explicitly_allow_create_paths);
+        self.explicitly_allow_create_paths = allow_create
+            .into_iter()
+            .filter(|path| self.is_path_effectively_locked(path))
+            .filter(|path| seen_allow_create.insert(path.clone()))
+            .collect();
     }
 
     pub fn toggle_expand_selected(&mut self) {
@@ -440,14 +629,161 @@ impl App {
 
     pub fn toggle_create_in_locked_selected(&mut self) {
         if self.selected < self.items.len() {
-            let path = self.items[self.selected].0.path.clone();
-            toggle_create_in_locked_at_path(&mut self.tree, &path);
-            self.update_items();
-            self.save_state();
+            let node = &self.items[self.selected].0;
+            if node.is_dir && node.is_locked {
+                let path = node.path.clone();
+                let allow = !self.explicitly_allow_create_paths.contains(&path);
+                self.set_allow_create(&path, allow);
+                self.save_state();
+            }
+        }
+    }
+
+    /// Allow (or stop allowing) new files inside the locked directory `path`.
+    /// Returns false when `path` is not a locked directory. The caller is responsible for saving.
+    pub fn set_allow_create(&mut self, path: &std::path::Path, allow: bool) -> bool {
+        if !path.is_dir() || !self.is_path_effectively_locked(path) {
+            return false;
+        }
+
+        self.explicitly_allow_create_paths.retain(|p| p != path);
+        if allow {
+            self.explicitly_allow_create_paths.push(path.to_path_buf());
+        }
+
+        self.reapply_explicit_locks();
+        self.update_items();
+        true
+    }
+
+    /// Apply a loaded state file to the tree and the explicit lock lists.
+    pub fn restore_state(&mut self, state: &crate::state::AppState) {
+        log_debug!(
+            "UI: Restoring state, locked: [{}], unlocked: [{}], allow_create: [{}]",
+            state.locked_patterns.join(", "),
+            state.unlocked_patterns.join(", "),
+            state.allow_create_patterns.join(", ")
+        );
+
+        self.active_quest = state.quest.clone();
+
+        // First restore expanded dirs
+        for expanded_dir in &state.expanded_dirs {
+            restore_expanded_state(&mut self.tree, expanded_dir);
+        }
+
+        // Apply locked patterns, then unlocked patterns (exceptions to locked patterns),
+        // then the allow-create exceptions inside locked directories
+        self.explicitly_locked_paths = patterns_to_paths(&state.root_path, &state.locked_patterns);
+        self.explicitly_unlocked_paths =
+            patterns_to_paths(&state.root_path, &state.unlocked_patterns);
+        self.explicitly_allow_create_paths =
+            patterns_to_paths(&state.root_path, &state.allow_create_patterns);
+
+        // Clean up any conflicts or duplicates
+        self.cleanup_lock_lists();
+
+        // Apply the explicit locks to the tree
+        self.reapply_explicit_locks();
+
+        self.update_items();
+        log_debug!("UI: State restoration complete");
+    }
+
+    /// Mark the selected path to be allowed by the next quest, or unmark it.
+    pub fn toggle_quest_mark_selected(&mut self) {
+        if let Some((node, _)) = self.items.get(self.selected) {
+            let path = node.path.clone();
+            self.toggle_quest_mark(&path);
+        }
+    }
+
+    pub fn toggle_quest_mark(&mut self, path: &std::path::Path) {
+        if let Some(index) = self.quest_marks.iter().position(|mark| mark == path) {
+            self.quest_marks.remove(index);
+        } else if path != self.root_path {
+            self.quest_marks.push(path.to_path_buf());
+        }
+    }
+
+    /// Lock everything except the marked paths. The quest is named after the
+    /// first marked path unless `name` is given.
+    pub fn start_quest(&mut self, name: Option<&str>) -> Result<()> {
+        let Some(first) = self.quest_marks.first() else {
+            anyhow::bail!("Mark the paths the quest may change first");
+        };
+        let name = match name {
+            Some(name) => name.to_string(),
+            None => first
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "quest".to_string()),
+        };
+        let scope = self
+            .quest_marks
+            .iter()
+            .filter_map(|path| {
+                let relative = path.strip_prefix(&self.root_path).ok()?;
+                Some(if path.is_dir() {
+                    format!("{}/**", relative.display())
+                } else {
+                    relative.display().to_string()
+                })
+            })
+            .collect();
+
+        self.begin_quest(&name, scope, Vec::new())?;
+        self.quest_marks.clear();
+        Ok(())
+    }
+
+    /// Start a quest that unlocks only the `scope` patterns and lets files be
+    /// created in the `allow_create` directories.
+    pub fn begin_quest(
+        &mut self,
+        name: &str,
+        scope: Vec<String>,
+        allow_create: Vec<String>,
+    ) -> Result<()> {
+        // Record the policy as the tree shows it
+        self.write_state()?;
+        let mut state = crate::state::AppState::load_from_file(&self.state_file)?;
+        state.start_quest(name, scope, allow_create)?;
+        state.save_to_file(&self.state_file)?;
+        self.restore_state(&state);
+        self.load_profiles();
+        Ok(())
+    }
+
+    /// Drop the quest's locks and bring back what was in effect before it.
+    pub fn end_quest(&mut self) -> Result<Quest> {
+        let mut state = crate::state::AppState::load_from_file(&self.state_file)?;
+        let quest = state.end_quest()?;
+        state.save_to_file(&self.state_file)?;
+        self.restore_state(&state);
+        self.load_profiles();
+        Ok(quest)
+    }
+
+    fn toggle_quest(&mut self) {
+        let result = if self.active_quest.is_some() {
+            self.end_quest().map(|_| ())
+        } else {
+            self.start_quest(None)
+        };
+        if let Err(e) = result {
+            log_debug!("UI: Quest not changed: {}", e);
         }
     }
 
     fn save_state(&self) {
+        if let Err(e) = self.write_state() {
+            eprintln!("Error saving state: {e}");
+        }
+    }
+
+    /// Write the current locks to the state file in canonical form, keeping its profiles.
+    pub fn write_state(&self) -> Result<()> {
         // Load existing state to preserve profiles, or create new one if it doesn't exist
         let mut state = crate::state::AppState::load_from_file(&self.state_file)
             .unwrap_or_else(|_| crate::state::AppState::new(self.root_path.clone()));
@@ -513,6 +849,7 @@ impl App {
 
         state.locked_patterns = locked_vec.clone();
         state.unlocked_patterns = unlocked_vec.clone();
+        state.allow_create_patterns = self.get_current_allow_create_patterns();
 
         if std::env::var("ICAROS_DEBUG").is_ok() {
             eprintln!("Saving patterns:");
@@ -528,9 +865,7 @@ impl App {
             );
         }
 
-        if let Err(e) = state.save_to_file(&self.state_file) {
-            eprintln!("Error saving state: {e}");
-        }
+        state.save_to_file(&self.state_file)
     }
 
     pub fn move_up(&mut self) {
@@ -571,13 +906,8 @@ impl App {
             None
         };
 
-        // Load ignore patterns from state or use defaults
-        let ignore_patterns =
-            if let Ok(state) = crate::state::AppState::load_from_file(&self.state_file) {
-                state.ignore_patterns
-            } else {
-                crate::state::default_ignore_patterns()
-            };
+        // Load ignore patterns from state (or defaults) plus the user's global patterns
+        let ignore_patterns = Settings::load(&self.state_file).ignore_patterns;
 
         // Rebuild tree with ignore patterns and hidden file filter
         self.tree =
@@ -666,6 +996,11 @@ impl App {
                 }
             }
         }
+
+        // Allow-create exceptions go on top of the final lock state
+        for path in &self.explicitly_allow_create_paths {
+            allow_create_at_path(&mut self.tree, path);
+        }
     }
 
     fn collect_expanded_dirs(&self, node: &TreeNode, expanded: &mut Vec<std::path::PathBuf>) {
@@ -681,12 +1016,26 @@ impl App {
     // Git-related methods
     pub fn refresh_git_status(&mut self) {
         if let Some(ref git) = self.git_manager {
-            if let Ok(files) = git.get_status_files() {
+            let files = match &mut self.git_review {
+                // Review mode lists what changed since its base instead
+                Some(review) => git.review_files(review.commit).map(|files| {
+                    review.files = files;
+                    review
+                        .files
+                        .iter()
+                        .map(|changed| changed.file.clone())
+                        .collect()
+                }),
+                None => git.get_status_files(),
+            };
+            if let Ok(files) = files {
+                self.git_violations = self.lock_violations(&files);
                 self.git_files = files;
                 // Reset selection if list is not empty
                 if !self.git_files.is_empty() && self.git_selected_file >= self.git_files.len() {
                     self.git_selected_file = 0;
                     self.git_file_list_state.select(Some(0));
+                    self.git_diff_target = DiffTarget::side(self.git_files[0].staged);
                 }
             }
         }
@@ -696,109 +1045,1045 @@ impl App {
         if let Some(ref git) = self.git_manager {
             if self.git_selected_file < self.git_files.len() {
                 let file = &self.git_files[self.git_selected_file];
-                if let Ok(hunks) = git.get_file_diff(&file.path, file.staged) {
-                    self.git_diff_hunks = hunks;
+                let (diff, (old, new)) = match &self.git_review {
+                    Some(review) => (
+                        git.review_diff(file, review.commit, self.git_ignore_whitespace),
+                        git.review_versions(file, review.commit),
+                    ),
+                    None => (
+                        git.diff_file(file, self.git_diff_target, self.git_ignore_whitespace),
+                        git.file_versions(file, self.git_diff_target),
+                    ),
+                };
+                if let Ok(diff) = diff {
+                    self.git_diff_notes = diff.notes();
+                    let old_path = file.renamed_from.as_deref().unwrap_or(&file.path);
+                    self.git_diff_highlights = DiffHighlights::new(
+                        old.as_deref().map(|old| (old_path, old)),
+                        new.as_deref().map(|new| (file.path.as_path(), new)),
+                        self.settings.theme,
+                    );
+                    self.git_diff_hunks = diff.hunks;
                     self.git_diff_scroll = 0;
                     self.git_selected_hunk = 0;
+                    self.leave_git_line_mode();
                 }
             }
         }
     }
 
     pub fn toggle_git_file_stage(&mut self) {
+        self.stage_git_file(false);
+    }
+
+    /// Stage or unstage the selected file. Staging a file that breaks a lock
+    /// asks first, unless `confirmed` or the policy only warns.
+    fn stage_git_file(&mut self, confirmed: bool) {
+        if self.refuse_in_review() {
+            return;
+        }
+        let staging =
+            matches!(self.git_files.get(self.git_selected_file), Some(file) if !file.staged);
+        let Some(warning) = self.check_locked_stage(staging, confirmed, LockedStage::File) else {
+            return;
+        };
         if let Some(ref git) = self.git_manager {
             if self.git_selected_file < self.git_files.len() {
                 let file = &self.git_files[self.git_selected_file];
-                let result = if file.staged {
-                    git.unstage_file(&file.path)
+                let (path, staged) = (file.path.clone(), file.staged);
+                let result = if staged {
+                    git.unstage(file)
                 } else {
-                    git.stage_file(&file.path)
+                    git.stage(file)
                 };
 
                 if result.is_ok() {
+                    self.git_message = warning;
                     self.refresh_git_status();
-                    self.load_git_diff();
+                    // Follow the file to the other section
+                    self.reselect_git_file(&path, !staged);
                 }
             }
         }
     }
 
+    /// Before staging the selected file, or part of it: `None` when staging
+    /// has to wait for the user to confirm it breaks a lock, otherwise the
+    /// warning to show afterwards, if any.
+    fn check_locked_stage(
+        &mut self,
+        staging: bool,
+        confirmed: bool,
+        kind: LockedStage,
+    ) -> Option<Option<String>> {
+        let violation = match self.selected_violation() {
+            Some(violation) if staging => violation,
+            _ => return Some(None),
+        };
+        if confirmed || self.settings.enforcement == EnforcementMode::Warn {
+            let path = self.git_files[self.git_selected_file].path.display();
+            return Some(Some(format!(
+                "Staged {path}, which breaks a lock ({})",
+                violation.label()
+            )));
+        }
+        self.git_pending_locked_stage = Some(kind);
+        None
+    }
+
+    /// How the selected file breaks the lock policy, if it does.
+    pub fn selected_violation(&self) -> Option<LockViolation> {
+        self.git_violations
+            .get(self.git_selected_file)
+            .copied()
+            .flatten()
+    }
+
+    /// The question asked before staging a file that breaks a lock.
+    pub fn locked_stage_prompt(&self) -> Option<String> {
+        let kind = self.git_pending_locked_stage?;
+        let file = self.git_files.get(self.git_selected_file)?;
+        let what = match kind {
+            LockedStage::File => "it",
+            LockedStage::Hunk => "this change",
+        };
+        Some(format!(
+            "{} breaks a lock ({}). Stage {what} anyway?",
+            file.path.display(),
+            self.selected_violation()?.label()
+        ))
+    }
+
+    pub fn handle_locked_stage_key(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Char('y') | KeyCode::Enter => match self.git_pending_locked_stage.take() {
+                Some(LockedStage::File) => self.stage_git_file(true),
+                Some(LockedStage::Hunk) => self.stage_selected_hunk(true, true),
+                None => {}
+            },
+            KeyCode::Char('n') | KeyCode::Esc => self.git_pending_locked_stage = None,
+            _ => {}
+        }
+    }
+
+    /// Ask to put a file that breaks a lock back as it is in HEAD, or to
+    /// delete it if it is new.
+    pub fn start_locked_revert(&mut self) {
+        if self.refuse_in_review() {
+            return;
+        }
+        let Some(file) = self.git_files.get(self.git_selected_file) else {
+            return;
+        };
+        if self.selected_violation().is_none() {
+            self.git_message = Some(format!("{} breaks no lock", file.path.display()));
+            return;
+        }
+        self.git_pending_discard = Some(Discard::Locked);
+    }
+
+    /// Accept the change to a file that breaks a lock: unlock its path, and
+    /// the path it was renamed from, then stage it.
+    pub fn unlock_and_accept(&mut self) {
+        if self.refuse_in_review() {
+            return;
+        }
+        let (Some(file), Some(workdir)) = (
+            self.git_files.get(self.git_selected_file).cloned(),
+            self.git_manager
+                .as_ref()
+                .and_then(GitManager::workdir)
+                .map(std::path::Path::to_path_buf),
+        ) else {
+            return;
+        };
+        if self.selected_violation().is_none() {
+            self.git_message = Some(format!("{} breaks no lock", file.path.display()));
+            return;
+        }
+
+        for path in std::iter::once(&file.path).chain(&file.renamed_from) {
+            let absolute = workdir.join(path);
+            if self.is_path_effectively_locked(&absolute) {
+                self.toggle_path(&absolute, absolute.is_dir());
+            }
+        }
+        self.save_state();
+
+        let result = match (&self.git_manager, file.staged) {
+            (Some(git), false) => git.stage(&file),
+            _ => Ok(()),
+        };
+        match result {
+            Ok(()) => {
+                self.git_message = Some(format!("Unlocked {} and staged it", file.path.display()));
+                self.refresh_git_status();
+                self.reselect_git_file(&file.path, true);
+            }
+            Err(e) => self.git_message = Some(format!("{e:#}")),
+        }
+    }
+
+    /// How each file breaks the lock policy in the state file, if it does.
+    fn lock_violations(&self, files: &[GitFile]) -> Vec<Option<LockViolation>> {
+        let (Some(git), Ok(state)) = (
+            self.git_manager.as_ref(),
+            crate::state::AppState::load_from_file(&self.state_file),
+        ) else {
+            return vec![None; files.len()];
+        };
+        let Some(workdir) = git.workdir() else {
+            return vec![None; files.len()];
+        };
+        let policy = state.current_policy();
+        // Paths relative to the root the policy is written for
+        let relative = |path: &std::path::Path| {
+            let absolute = workdir.join(path);
+            if absolute == self.state_file {
+                return None;
+            }
+            absolute
+                .strip_prefix(&self.root_path)
+                .ok()
+                .map(std::path::Path::to_path_buf)
+        };
+        let created = |path: &std::path::Path| {
+            let path = relative(path)?;
+            (policy.is_locked(&path) && !policy.can_create(&path)).then_some(LockViolation::Created)
+        };
+        let changed = |path: &std::path::Path, violation| {
+            let path = relative(path)?;
+            policy.is_locked(&path).then_some(violation)
+        };
+
+        files
+            .iter()
+            .map(|file| match file.status {
+                GitFileStatus::Added | GitFileStatus::Untracked => created(&file.path),
+                GitFileStatus::Modified => changed(&file.path, LockViolation::Modified),
+                GitFileStatus::Deleted => changed(&file.path, LockViolation::Deleted),
+                GitFileStatus::Renamed => file
+                    .renamed_from
+                    .as_deref()
+                    .and_then(|old_path| changed(old_path, LockViolation::Deleted))
+                    .or_else(|| created(&file.path)),
+            })
+            .collect()
+    }
+
     pub fn move_git_file_up(&mut self) {
         if self.git_selected_file > 0 {
-            self.git_selected_file -= 1;
-            self.git_file_list_state
-                .select(Some(self.git_selected_file));
-            self.load_git_diff();
+            self.select_git_file(self.git_selected_file - 1);
         }
     }
 
     pub fn move_git_file_down(&mut self) {
         if self.git_selected_file < self.git_files.len().saturating_sub(1) {
-            self.git_selected_file += 1;
-            self.git_file_list_state
-                .select(Some(self.git_selected_file));
-            self.load_git_diff();
+            self.select_git_file(self.git_selected_file + 1);
         }
     }
 
-    pub fn move_git_hunk_up(&mut self) {
-        if self.git_selected_hunk > 0 {
-            self.git_selected_hunk -= 1;
-            // TODO: Adjust scroll to ensure hunk is visible
-        }
+    /// Select an entry of the file list, showing the diff for its section.
+    pub fn select_git_file(&mut self, index: usize) {
+        let Some(file) = self.git_files.get(index) else {
+            return;
+        };
+        self.git_diff_target = DiffTarget::side(file.staged);
+        self.git_selected_file = index;
+        self.git_file_list_state.select(Some(index));
+        self.git_message = None;
+        self.load_git_diff();
     }
 
-    pub fn move_git_hunk_down(&mut self) {
-        if self.git_selected_hunk < self.git_diff_hunks.len().saturating_sub(1) {
-            self.git_selected_hunk += 1;
-            // TODO: Adjust scroll to ensure hunk is visible
+    /// After the file list changed, select `path` in the staged or unstaged
+    /// section again, or in the other one if it left that section.
+    fn reselect_git_file(&mut self, path: &std::path::Path, staged: bool) {
+        let index = self
+            .git_files
+            .iter()
+            .position(|file| file.path == path && file.staged == staged)
+            .or_else(|| self.git_files.iter().position(|file| file.path == path))
+            .unwrap_or(self.git_selected_file)
+            .min(self.git_files.len().saturating_sub(1));
+        let message = self.git_message.take();
+        self.select_git_file(index);
+        self.git_message = message;
+    }
+
+    /// Switch the diff pane between the staged, unstaged and combined changes.
+    pub fn cycle_git_diff_target(&mut self) {
+        if self.refuse_in_review() {
+            return;
         }
+        self.git_diff_target = self.git_diff_target.next();
+        self.git_message = None;
+        self.load_git_diff();
     }
 
-    pub fn scroll_git_diff_up(&mut self) {
-        self.git_diff_scroll = self.git_diff_scroll.saturating_sub(1);
+    /// Snapshot the working tree, so Review mode can show everything changed
+    /// since icaros started. Hashing every changed file can take a while, so
+    /// it runs in the background and Review mode waits for it.
+    pub fn take_session_checkpoint(&mut self) {
+        let Some(git_dir) = self
+            .git_manager
+            .as_ref()
+            .map(|git| git.git_dir().to_path_buf())
+        else {
+            return;
+        };
+        self.owns_checkpoint = true;
+        self.git_checkpoint = Some(std::thread::spawn(move || {
+            let git = GitManager::new(&git_dir)?;
+            // Sessions that crashed never removed theirs
+            if let Err(e) = git.prune_stale_checkpoints() {
+                log_debug!("UI: Cannot prune stale checkpoints: {}", e);
+            }
+            git.save_checkpoint()
+        }));
     }
 
-    pub fn scroll_git_diff_down(&mut self) {
-        // TODO: Add max scroll based on content
-        self.git_diff_scroll += 1;
+    /// Wait until the session checkpoint started by `take_session_checkpoint`
+    /// has been saved.
+    pub fn wait_for_checkpoint(&mut self) {
+        let Some(handle) = self.git_checkpoint.take() else {
+            return;
+        };
+        match handle.join() {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => {
+                log_debug!("UI: Cannot take a session checkpoint: {}", e);
+            }
+            Err(_) => {
+                log_debug!("UI: The session checkpoint thread panicked");
+            }
+        }
     }
 
-    // Profile management methods
-    pub fn load_profiles(&mut self) {
-        if let Ok(state) = crate::state::AppState::load_from_file(&self.state_file) {
-            self.profile_names = state.get_profile_names();
-            self.active_profile_name = state.get_active_profile_name().cloned();
-            if !self.profile_names.is_empty() {
-                self.profile_list_state.select(Some(0));
+    /// Remove the session checkpoint when icaros exits. Dropping the App does
+    /// this too, so it also happens when the UI stops with an error.
+    pub fn drop_session_checkpoint(&mut self) {
+        self.owns_checkpoint = false;
+        self.wait_for_checkpoint();
+        if let Some(git) = &self.git_manager {
+            if let Err(e) = git.drop_checkpoint() {
+                log_debug!("UI: Cannot remove the session checkpoint: {}", e);
             }
         }
     }
 
-    pub fn move_profile_up(&mut self) {
-        if let Some(selected) = self.profile_list_state.selected() {
-            if selected > 0 {
-                self.profile_list_state.select(Some(selected - 1));
+    /// Switch the Git Stage tab to Review mode, listing every file that
+    /// differs between `base` and the working tree.
+    pub fn open_review(&mut self, base: ReviewBase) {
+        if base == ReviewBase::Checkpoint {
+            self.wait_for_checkpoint();
+        }
+        let Some(git) = self.git_manager.as_ref() else {
+            return;
+        };
+        match git.resolve_review_base(&base) {
+            Ok(commit) => {
+                self.git_review = Some(Review {
+                    base,
+                    commit,
+                    files: Vec::new(),
+                });
+                self.reset_git_selection();
             }
+            Err(e) => self.git_message = Some(format!("{e:#}")),
         }
     }
 
-    pub fn move_profile_down(&mut self) {
-        if let Some(selected) = self.profile_list_state.selected() {
-            if selected < self.profile_names.len().saturating_sub(1) {
-                self.profile_list_state.select(Some(selected + 1));
+    /// Go back from Review mode to staging.
+    pub fn close_review(&mut self) {
+        self.git_review = None;
+        self.reset_git_selection();
+    }
+
+    /// Enter Review mode against the session checkpoint, or the merge base
+    /// with the default branch when there is none; or leave it.
+    pub fn toggle_review(&mut self) {
+        if self.git_review.is_some() {
+            self.close_review();
+            return;
+        }
+        self.wait_for_checkpoint();
+        let Some(git) = self.git_manager.as_ref() else {
+            return;
+        };
+        if git.checkpoint().is_some() {
+            self.open_review(ReviewBase::Checkpoint);
+        } else if let Some(branch) = git.default_branch() {
+            self.open_review(ReviewBase::MergeBase(branch));
+        } else {
+            self.git_message =
+                Some("No session checkpoint or default branch; g picks a base".to_string());
+        }
+    }
+
+    pub fn start_review_base_input(&mut self) {
+        self.git_review_input = Some(TextInput::default());
+    }
+
+    /// Handle a key while the review base prompt is open.
+    pub fn handle_review_base_key(&mut self, code: KeyCode) {
+        let Some(input) = self.git_review_input.as_mut() else {
+            return;
+        };
+        match code {
+            KeyCode::Enter => {
+                let base = ReviewBase::parse(input.value());
+                self.git_review_input = None;
+                self.open_review(base);
+            }
+            KeyCode::Esc => self.git_review_input = None,
+            code => {
+                input.handle_key(code);
             }
         }
     }
 
-    pub fn load_selected_profile(&mut self) {
-        log_debug!("UI: load_selected_profile called");
-        if let Some(selected) = self.profile_list_state.selected() {
-            log_debug!("UI: Selected profile index: {}", selected);
-            if selected < self.profile_names.len() {
-                let profile_name = self.profile_names[selected].clone();
-                log_debug!("UI: Loading profile: '{}'", profile_name);
-                log_debug!("UI: Animations enabled: {}", self.animations_enabled);
+    /// Reload the file list and select its first entry, after switching
+    /// between staging and Review mode.
+    fn reset_git_selection(&mut self) {
+        self.leave_git_line_mode();
+        self.git_selected_file = 0;
+        self.git_file_list_state.select(Some(0));
+        self.refresh_git_status();
+        if self.git_files.is_empty() {
+            self.git_diff_hunks.clear();
+            self.git_diff_notes.clear();
+            self.git_diff_highlights = DiffHighlights::default();
+            self.git_message = None;
+        } else {
+            self.select_git_file(0);
+        }
+    }
+
+    /// Whether Review mode is on, saying so when an action can't run in it.
+    fn refuse_in_review(&mut self) -> bool {
+        if self.git_review.is_some() {
+            self.git_message = Some(format!(
+                "Review is read-only; {} goes back to staging",
+                self.settings.key_for("git_review")
+            ));
+        }
+        self.git_review.is_some()
+    }
+
+    /// Switch the diff pane between the unified and side-by-side layouts.
+    pub fn toggle_git_side_by_side(&mut self) {
+        self.git_side_by_side = !self.git_side_by_side;
+        self.scroll_to_selected_hunk();
+    }
+
+    /// Hide or show changes that only touch whitespace.
+    pub fn toggle_git_ignore_whitespace(&mut self) {
+        self.git_ignore_whitespace = !self.git_ignore_whitespace;
+        self.git_message = None;
+        self.load_git_diff();
+    }
+
+    /// How many rows a hunk's lines take in the diff pane.
+    fn git_hunk_rows(&self, hunk: &GitHunk) -> usize {
+        if self.git_side_by_side && !self.git_line_mode {
+            diff_layout::side_by_side(hunk).len()
+        } else {
+            hunk.lines.len()
+        }
+    }
+
+    pub fn move_git_hunk_up(&mut self) {
+        if self.git_selected_hunk > 0 {
+            self.git_selected_hunk -= 1;
+            self.leave_git_line_mode();
+            self.scroll_to_selected_hunk();
+        }
+    }
+
+    pub fn move_git_hunk_down(&mut self) {
+        if self.git_selected_hunk < self.git_diff_hunks.len().saturating_sub(1) {
+            self.git_selected_hunk += 1;
+            self.leave_git_line_mode();
+            self.scroll_to_selected_hunk();
+        }
+    }
+
+    /// Start picking single lines of the selected hunk, with the cursor on its
+    /// first change.
+    pub fn enter_git_line_mode(&mut self) {
+        if self.refuse_in_review() {
+            return;
+        }
+        let Some(hunk) = self.git_diff_hunks.get(self.git_selected_hunk) else {
+            return;
+        };
+        if let Some(&first) = hunk.changed_lines().first() {
+            self.git_line_mode = true;
+            self.git_line_cursor = first;
+            self.git_selected_lines.clear();
+        }
+    }
+
+    pub fn leave_git_line_mode(&mut self) {
+        self.git_line_mode = false;
+        self.git_line_cursor = 0;
+        self.git_selected_lines.clear();
+    }
+
+    /// Move the line cursor to the previous or next added or removed line.
+    pub fn move_git_line_cursor(&mut self, down: bool) {
+        let Some(hunk) = self.git_diff_hunks.get(self.git_selected_hunk) else {
+            return;
+        };
+        let changed = hunk.changed_lines();
+        let next = if down {
+            changed
+                .into_iter()
+                .find(|&line| line > self.git_line_cursor)
+        } else {
+            changed
+                .into_iter()
+                .rev()
+                .find(|&line| line < self.git_line_cursor)
+        };
+        if let Some(line) = next {
+            self.git_line_cursor = line;
+        }
+    }
+
+    pub fn toggle_git_line_selected(&mut self) {
+        if !self.git_selected_lines.remove(&self.git_line_cursor) {
+            self.git_selected_lines.insert(self.git_line_cursor);
+        }
+    }
+
+    /// Scroll the diff so the selected hunk's header is the top line.
+    fn scroll_to_selected_hunk(&mut self) {
+        // Each hunk is a header, its lines and a blank separator; the notes and
+        // a blank line come first, and stay in view for the first hunk
+        let notes = match (self.git_selected_hunk, self.git_diff_notes.len()) {
+            (0, _) | (_, 0) => 0,
+            (_, notes) => notes + 1,
+        };
+        let offset: usize = notes
+            + self.git_diff_hunks[..self.git_selected_hunk]
+                .iter()
+                .map(|hunk| self.git_hunk_rows(hunk) + 2)
+                .sum::<usize>();
+        self.git_diff_scroll = offset.try_into().unwrap_or(u16::MAX);
+    }
+
+    /// Stage the selected hunk, or unstage it if it is a staged change. In line
+    /// mode only the picked lines are, or the line under the cursor if none is.
+    pub fn toggle_selected_hunk_stage(&mut self, stage: bool) {
+        self.stage_selected_hunk(stage, false);
+    }
+
+    fn stage_selected_hunk(&mut self, stage: bool, confirmed: bool) {
+        if self.refuse_in_review() {
+            return;
+        }
+        let (Some(_), Some(file), Some(hunk)) = (
+            self.git_manager.as_ref(),
+            self.git_files.get(self.git_selected_file),
+            self.git_diff_hunks.get(self.git_selected_hunk),
+        ) else {
+            return;
+        };
+        if self.git_diff_target == DiffTarget::All {
+            self.git_message =
+                Some("Switch to the staged or unstaged diff to stage hunks".to_string());
+            return;
+        }
+        if file.status == GitFileStatus::Untracked {
+            self.git_message =
+                Some("Untracked files are staged whole, with Space in the file list".to_string());
+            return;
+        }
+        // The index has only the old path until the rename is staged
+        if file.renamed_from.is_some() && !file.staged {
+            self.git_message =
+                Some("Unstaged renames are staged whole, with Space in the file list".to_string());
+            return;
+        }
+        // Hunks without their whitespace changes don't apply to the real file
+        if self.git_ignore_whitespace {
+            self.git_message = Some(format!(
+                "Show whitespace ({}) to stage or discard hunks",
+                self.settings.key_for("git_ignore_whitespace")
+            ));
+            return;
+        }
+        if hunk.staged == stage {
+            self.git_message = Some(if stage {
+                "Hunk is already staged".to_string()
+            } else {
+                "Hunk is not staged".to_string()
+            });
+            return;
+        }
+        let Some(warning) = self.check_locked_stage(stage, confirmed, LockedStage::Hunk) else {
+            return;
+        };
+
+        let (Some(git), Some(file), Some(hunk)) = (
+            self.git_manager.as_ref(),
+            self.git_files.get(self.git_selected_file),
+            self.git_diff_hunks.get(self.git_selected_hunk),
+        ) else {
+            return;
+        };
+        let (path, staged) = (file.path.clone(), file.staged);
+        let lines = self.picked_lines();
+        let (result, what) = match (self.git_line_mode, stage) {
+            (false, true) => (git.stage_hunk(&path, hunk), "hunk".to_string()),
+            (false, false) => (git.unstage_hunk(&path, hunk), "hunk".to_string()),
+            (true, true) => (
+                git.stage_lines(&path, hunk, &lines),
+                format!("{} line(s) of hunk", lines.len()),
+            ),
+            (true, false) => (
+                git.unstage_lines(&path, hunk, &lines),
+                format!("{} line(s) of hunk", lines.len()),
+            ),
+        };
+        let selected_hunk = self.git_selected_hunk;
+        match result {
+            Ok(()) => {
+                self.git_message = warning.or(Some(format!(
+                    "{} {what} {} of {}",
+                    if stage { "Staged" } else { "Unstaged" },
+                    selected_hunk + 1,
+                    path.display()
+                )));
+                self.refresh_git_status();
+                // Stay on the same file, and near the same place in it
+                self.reselect_git_file(&path, staged);
+                self.git_selected_hunk =
+                    selected_hunk.min(self.git_diff_hunks.len().saturating_sub(1));
+                self.scroll_to_selected_hunk();
+            }
+            Err(e) => self.git_message = Some(format!("{e:#}")),
+        }
+    }
+
+    /// The lines picked in line mode, or the one under the cursor if none is.
+    fn picked_lines(&self) -> Vec<usize> {
+        if self.git_selected_lines.is_empty() {
+            vec![self.git_line_cursor]
+        } else {
+            self.git_selected_lines.iter().copied().collect()
+        }
+    }
+
+    /// Ask to discard the selected file's changes, or in the diff the selected
+    /// hunk or picked lines.
+    pub fn start_discard(&mut self) {
+        if self.refuse_in_review() {
+            return;
+        }
+        if self.git_files.get(self.git_selected_file).is_none() {
+            return;
+        }
+        let file = &self.git_files[self.git_selected_file];
+        // Neither an untracked nor a deleted file has a working copy to patch, so goes as a whole
+        let whole = file.status == GitFileStatus::Untracked
+            || (file.status == GitFileStatus::Deleted && !file.staged);
+        let unstaged_rename = file.renamed_from.is_some() && !file.staged;
+        let discard = match self.git_pane {
+            GitPane::FileList => Discard::File,
+            GitPane::DiffView if whole => Discard::File,
+            GitPane::DiffView if unstaged_rename => {
+                self.git_message = Some(format!(
+                    "Unstaged renames are discarded whole, with {} in the file list",
+                    self.settings.key_for("git_discard")
+                ));
+                return;
+            }
+            GitPane::DiffView if self.git_diff_hunks.is_empty() => return,
+            GitPane::DiffView if self.git_ignore_whitespace => {
+                self.git_message = Some(format!(
+                    "Show whitespace ({}) to stage or discard hunks",
+                    self.settings.key_for("git_ignore_whitespace")
+                ));
+                return;
+            }
+            GitPane::DiffView if self.git_diff_target != DiffTarget::Unstaged => {
+                self.git_message =
+                    Some("Only unstaged hunks can be discarded; unstage it first".to_string());
+                return;
+            }
+            GitPane::DiffView if self.git_line_mode => Discard::Lines,
+            GitPane::DiffView => Discard::Hunk,
+        };
+        self.git_pending_discard = Some(discard);
+    }
+
+    /// The question the discard confirmation asks.
+    pub fn discard_prompt(&self) -> Option<String> {
+        let discard = self.git_pending_discard?;
+        let file = self.git_files.get(self.git_selected_file)?;
+        let path = file.path.display();
+        let hunk = self.git_selected_hunk + 1;
+        Some(match discard {
+            Discard::File if file.status == GitFileStatus::Untracked => {
+                format!("Delete untracked {path}?")
+            }
+            Discard::File if file.staged => {
+                format!("Discard all changes to {path}, staged and unstaged, back to HEAD?")
+            }
+            Discard::File => match &file.renamed_from {
+                Some(old_path) => {
+                    format!("Undo the rename of {} to {path}?", old_path.display())
+                }
+                None => format!("Discard unstaged changes to {path}?"),
+            },
+            Discard::Locked if file.status == GitFileStatus::Untracked => {
+                format!("Delete {path}, which breaks a lock?")
+            }
+            Discard::Locked => {
+                format!("Revert {path} to HEAD, staged and unstaged, to keep its lock?")
+            }
+            Discard::Hunk => format!("Discard hunk {hunk} of {path}?"),
+            Discard::Lines => format!(
+                "Discard {} line(s) of hunk {hunk} of {path}?",
+                self.picked_lines().len()
+            ),
+        })
+    }
+
+    pub fn handle_discard_key(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Char('y') | KeyCode::Enter => self.confirm_discard(),
+            KeyCode::Char('n') | KeyCode::Esc => self.git_pending_discard = None,
+            _ => {}
+        }
+    }
+
+    /// Carry out the pending discard, keeping what it throws away in the trash.
+    pub fn confirm_discard(&mut self) {
+        let (Some(discard), Some(git), Some(file)) = (
+            self.git_pending_discard.take(),
+            self.git_manager.as_ref(),
+            self.git_files.get(self.git_selected_file),
+        ) else {
+            return;
+        };
+        let (path, staged) = (file.path.clone(), file.staged);
+        let selected_hunk = self.git_selected_hunk;
+        let hunk = self.git_diff_hunks.get(selected_hunk);
+        let result = match (discard, hunk) {
+            (Discard::File, _) if file.status == GitFileStatus::Untracked => {
+                git.delete_untracked(&path)
+            }
+            (Discard::File, _) if file.renamed_from.is_some() => {
+                let old_path = file.renamed_from.as_deref().unwrap_or(&path);
+                git.discard_rename(old_path, &path, staged)
+            }
+            (Discard::File, _) if staged => git.discard_file(&path, DiscardSource::Head),
+            (Discard::File, _) => git.discard_file(&path, DiscardSource::Index),
+            (Discard::Locked, _) if file.status == GitFileStatus::Untracked => {
+                git.delete_untracked(&path)
+            }
+            // A rename goes back to the old path as well
+            (Discard::Locked, _) => match &file.renamed_from {
+                Some(old_path) => git.discard_rename(old_path, &path, true),
+                None => git.discard_file(&path, DiscardSource::Head),
+            },
+            (Discard::Hunk, Some(hunk)) => git.discard_hunk(&path, hunk),
+            (Discard::Lines, Some(hunk)) => git.discard_lines(&path, hunk, &self.picked_lines()),
+            (_, None) => return,
+        };
+        match result {
+            Ok(trash) => {
+                let trash = git
+                    .workdir()
+                    .and_then(|workdir| trash.strip_prefix(workdir).ok())
+                    .unwrap_or(&trash)
+                    .display()
+                    .to_string();
+                self.refresh_git_status();
+                self.reselect_git_file(&path, staged);
+                if !matches!(discard, Discard::File | Discard::Locked) {
+                    self.git_selected_hunk =
+                        selected_hunk.min(self.git_diff_hunks.len().saturating_sub(1));
+                    self.scroll_to_selected_hunk();
+                }
+                self.git_message = Some(format!("Discarded, saved to {trash}"));
+            }
+            Err(e) => self.git_message = Some(format!("{e:#}")),
+        }
+    }
+
+    /// Open the commit dialog over the Git Stage tab.
+    pub fn open_commit_dialog(&mut self) {
+        if self.refuse_in_review() {
+            return;
+        }
+        self.refresh_git_status();
+        let Some(git) = self.git_manager.as_ref() else {
+            return;
+        };
+        let author = git
+            .signature()
+            .map(|signature| {
+                format!(
+                    "{} <{}>",
+                    signature.name().unwrap_or(""),
+                    signature.email().unwrap_or("")
+                )
+            })
+            .unwrap_or_default();
+        let staged: Vec<GitFile> = self
+            .git_files
+            .iter()
+            .filter(|file| file.staged)
+            .cloned()
+            .collect();
+        let locked = self.locked_staged_paths(&staged);
+        self.commit_dialog = Some(CommitDialog {
+            author: TextInput::new(&author),
+            staged,
+            locked,
+            ..CommitDialog::default()
+        });
+    }
+
+    /// The staged paths that the policy in the state file keeps locked. Adding
+    /// a file where creating is allowed doesn't count.
+    fn locked_staged_paths(&self, staged: &[GitFile]) -> Vec<std::path::PathBuf> {
+        staged
+            .iter()
+            .zip(self.lock_violations(staged))
+            .filter(|(_, violation)| violation.is_some())
+            .map(|(file, _)| file.path.clone())
+            .collect()
+    }
+
+    pub fn handle_commit_key(&mut self, key: KeyEvent) {
+        let Some(dialog) = self.commit_dialog.as_mut() else {
+            return;
+        };
+        dialog.error = None;
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            match key.code {
+                KeyCode::Char('s') => self.commit_from_dialog(),
+                KeyCode::Char('a') => {
+                    dialog.amend = !dialog.amend;
+                    // Start from the message being amended
+                    if dialog.amend && dialog.message.is_blank() {
+                        let last = self
+                            .git_manager
+                            .as_ref()
+                            .and_then(GitManager::last_commit_message);
+                        if let Some(last) = last {
+                            dialog.message = TextArea::new(last.trim_end());
+                        }
+                    }
+                }
+                KeyCode::Char('t') => {
+                    dialog.custom_author = !dialog.custom_author;
+                    dialog.editing_author = dialog.custom_author;
+                }
+                KeyCode::Char('o') => dialog.sign_off = !dialog.sign_off,
+                KeyCode::Char('l') => dialog.override_locks = !dialog.override_locks,
+                _ => {}
+            }
+            return;
+        }
+        match key.code {
+            KeyCode::Esc => self.commit_dialog = None,
+            KeyCode::Tab if dialog.custom_author => {
+                dialog.editing_author = !dialog.editing_author;
+            }
+            code if dialog.editing_author => {
+                if code != KeyCode::Enter {
+                    dialog.author.handle_key(code);
+                }
+            }
+            code => {
+                dialog.message.handle_key(code);
+            }
+        }
+    }
+
+    /// Commit what is staged with the dialog's message and options, closing it
+    /// on success.
+    pub fn commit_from_dialog(&mut self) {
+        let (Some(git), Some(dialog)) = (self.git_manager.as_ref(), self.commit_dialog.as_mut())
+        else {
+            return;
+        };
+        // Under `enforcement: warn` locked paths are only reported
+        let warning = match (dialog.locked.len(), dialog.override_locks) {
+            (0, _) | (_, true) => None,
+            (locked, false) if self.settings.enforcement == EnforcementMode::Warn => {
+                Some(format!(" ({locked} staged path(s) are locked)"))
+            }
+            (locked, false) => {
+                dialog.error = Some(format!(
+                    "{locked} staged path(s) are locked; Ctrl+L to commit anyway"
+                ));
+                return;
+            }
+        };
+        let options = CommitOptions {
+            message: dialog.message.value(),
+            amend: dialog.amend,
+            sign_off: dialog.sign_off,
+            author: dialog
+                .custom_author
+                .then(|| dialog.author.value().to_string()),
+        };
+        match git.commit(&options) {
+            Ok(oid) => {
+                let summary = options.message.lines().next().unwrap_or("").to_string();
+                let id = oid.to_string();
+                self.git_message = Some(format!(
+                    "{} {}: {summary}{}",
+                    if options.amend {
+                        "Amended"
+                    } else {
+                        "Committed"
+                    },
+                    &id[..7],
+                    warning.unwrap_or_default()
+                ));
+                self.commit_dialog = None;
+                self.refresh_git_status();
+                self.git_selected_file = 0;
+                if let Some(file) = self.git_files.first() {
+                    self.git_diff_target = DiffTarget::side(file.staged);
+                    self.load_git_diff();
+                } else {
+                    self.git_diff_hunks.clear();
+                }
+            }
+            Err(e) => dialog.error = Some(format!("{e:#}")),
+        }
+    }
+
+    pub fn scroll_git_diff_up(&mut self) {
+        self.git_diff_scroll = self.git_diff_scroll.saturating_sub(1);
+    }
+
+    pub fn scroll_git_diff_down(&mut self) {
+        // TODO: Add max scroll based on content
+        self.git_diff_scroll += 1;
+    }
+
+    // Profile management methods
+    pub fn load_profiles(&mut self) {
+        if let Ok(state) = crate::state::AppState::load_from_file(&self.state_file) {
+            self.profile_names = state.get_profile_names();
+            self.active_profile_name = state.get_active_profile_name().cloned();
+            self.active_profile_stack = state.active_stack();
+            self.active_profile_modified = self.differs_from_active_profiles(&state);
+            self.profile_agents.clear();
+            for (agent, profile) in &state.agents {
+                self.profile_agents
+                    .entry(profile.clone())
+                    .or_default()
+                    .push(agent.clone());
+            }
+            if self.profile_names.is_empty() {
+                self.profile_list_state.select(None);
+            } else if self
+                .profile_list_state
+                .selected()
+                .is_none_or(|selected| selected >= self.profile_names.len())
+            {
+                self.profile_list_state.select(Some(0));
+            }
+        }
+        self.update_selected_profile_rules();
+    }
+
+    /// Whether any path's permissions differ from what the active profiles give it.
+    fn differs_from_active_profiles(&self, state: &crate::state::AppState) -> bool {
+        let stack = state.active_stack();
+        if stack.is_empty() {
+            return false;
+        }
+        match state.resolve_stack(&stack) {
+            Ok(resolved) => !policy_diff::diff_policies(
+                &self.tree,
+                &self.root_path,
+                &resolved.policy(),
+                &state.current_policy(),
+            )
+            .is_empty(),
+            // The profiles changed underneath the state
+            Err(_) => true,
+        }
+    }
+
+    pub fn selected_profile_name(&self) -> Option<&String> {
+        self.profile_list_state
+            .selected()
+            .and_then(|selected| self.profile_names.get(selected))
+    }
+
+    fn select_profile(&mut self, name: &str) {
+        if let Some(index) = self.profile_names.iter().position(|other| other == name) {
+            self.profile_list_state.select(Some(index));
+            self.update_selected_profile_rules();
+        }
+    }
+
+    /// Resolve the selected profile's inheritance for the Profiles tab, and preview
+    /// what switching to it would change.
+    pub fn update_selected_profile_rules(&mut self) {
+        let selected = self
+            .profile_list_state
+            .selected()
+            .and_then(|selected| self.profile_names.get(selected));
+        self.selected_profile_rules = None;
+        self.selected_profile_preview = None;
+        let (Some(name), Ok(state)) = (
+            selected,
+            crate::state::AppState::load_from_file(&self.state_file),
+        ) else {
+            return;
+        };
+        let resolved = state.resolve_profile(name);
+        if let Ok(resolved) = &resolved {
+            self.selected_profile_preview = Some(policy_diff::diff_policies(
+                &self.tree,
+                &self.root_path,
+                &state.current_policy(),
+                &resolved.policy(),
+            ));
+        }
+        self.selected_profile_rules = Some(resolved.map_err(|e| e.to_string()));
+    }
+
+    pub fn move_profile_up(&mut self) {
+        if let Some(selected) = self.profile_list_state.selected() {
+            if selected > 0 {
+                self.profile_list_state.select(Some(selected - 1));
+                self.update_selected_profile_rules();
+            }
+        }
+    }
+
+    pub fn move_profile_down(&mut self) {
+        if let Some(selected) = self.profile_list_state.selected() {
+            if selected < self.profile_names.len().saturating_sub(1) {
+                self.profile_list_state.select(Some(selected + 1));
+                self.update_selected_profile_rules();
+            }
+        }
+    }
+
+    pub fn load_selected_profile(&mut self) {
+        log_debug!("UI: load_selected_profile called");
+        if let Some(selected) = self.profile_list_state.selected() {
+            log_debug!("UI: Selected profile index: {}", selected);
+            if selected < self.profile_names.len() {
+                let profile_name = self.profile_names[selected].clone();
+                log_debug!("UI: Loading profile: '{}'", profile_name);
+                log_debug!("UI: Animations enabled: {}", self.animations_enabled);
 
                 if self.animations_enabled {
                     log_debug!("UI: Starting profile switch animation");
@@ -844,83 +2129,204 @@ impl App {
         }
     }
 
-    pub fn handle_profile_input(&mut self) {
-        if !self.profile_input_buffer.trim().is_empty()
-            && self.profile_action == ProfileAction::Save
-        {
-            // Load existing state and add the profile
-            if let Ok(mut state) = crate::state::AppState::load_from_file(&self.state_file) {
-                let description = format!(
-                    "Saved on {}",
-                    chrono::Utc::now().format("%Y-%m-%d %H:%M:%S")
-                );
+    /// Open the prompt for `action`. Actions on an existing profile apply to the
+    /// selected one, and do nothing when none is selected.
+    pub fn start_profile_action(&mut self, action: ProfileAction) {
+        let selected = self.selected_profile_name().cloned();
+        let initial = match (action, selected) {
+            (ProfileAction::None, _) => return,
+            (ProfileAction::Save, _) => String::new(),
+            (_, None) => return,
+            (ProfileAction::Rename, Some(name)) => name,
+            (ProfileAction::Duplicate, Some(name)) => format!("{name}-copy"),
+            (ProfileAction::Describe, Some(name)) => {
+                crate::state::AppState::load_from_file(&self.state_file)
+                    .ok()
+                    .and_then(|state| state.profiles.get(&name).cloned())
+                    .map(|profile| profile.description)
+                    .unwrap_or_default()
+            }
+            (ProfileAction::Overwrite, Some(_)) => String::new(),
+        };
+        self.profile_action = action;
+        self.profile_input = TextInput::new(&initial);
+        self.profile_input_mode = true;
+    }
+
+    pub fn cancel_profile_input(&mut self) {
+        self.profile_input_mode = false;
+        self.profile_input.clear();
+        self.profile_action = ProfileAction::None;
+    }
 
-                // Get current patterns from UI state, not the loaded file
-                let current_locked = self.get_current_locked_patterns();
-                let current_unlocked = self.get_current_unlocked_patterns();
+    /// Handle a key while the prompt is open. Every key goes to the prompt, so
+    /// typing a name can't quit or switch tabs.
+    pub fn handle_profile_key(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Enter => self.handle_profile_input(),
+            KeyCode::Esc => self.cancel_profile_input(),
+            // The overwrite prompt is a confirmation, with nothing to type
+            _ if self.profile_action == ProfileAction::Overwrite => {}
+            code => {
+                self.profile_input.handle_key(code);
+            }
+        }
+    }
 
-                // Create profile from current UI state
-                let profile = crate::state::LockProfile {
-                    locked_patterns: current_locked,
-                    unlocked_patterns: current_unlocked,
-                    allow_create_patterns: vec![], // TODO: implement if needed
-                    description,
-                };
+    /// Why the prompt's current input can't be accepted, if it can't.
+    pub fn profile_input_problem(&self) -> Option<String> {
+        if !matches!(
+            self.profile_action,
+            ProfileAction::Save | ProfileAction::Rename | ProfileAction::Duplicate
+        ) {
+            return None;
+        }
+        let name = self.profile_input.value().trim();
+        if name.is_empty() {
+            return Some("Name cannot be empty".to_string());
+        }
+        if name.contains(['/', '\\']) {
+            return Some("Name cannot contain '/' or '\\'".to_string());
+        }
+        let renaming_to_itself = self.profile_action == ProfileAction::Rename
+            && self.selected_profile_name() == Some(&name.to_string());
+        if self.profile_names.iter().any(|other| other == name) && !renaming_to_itself {
+            return Some(format!("Profile '{name}' already exists"));
+        }
+        None
+    }
 
-                state
-                    .profiles
-                    .insert(self.profile_input_buffer.clone(), profile);
-                state.active_profile = Some(self.profile_input_buffer.clone());
-                self.active_profile_name = Some(self.profile_input_buffer.clone());
-
-                if std::env::var("ICAROS_DEBUG").is_ok() {
-                    eprintln!(
-                        "Saving profile '{}' with {} profiles total",
-                        self.profile_input_buffer,
-                        state.profiles.len()
+    /// Carry out the open prompt's action. An invalid name keeps the prompt open.
+    pub fn handle_profile_input(&mut self) {
+        if self.profile_input_problem().is_some() {
+            return;
+        }
+        let input = self.profile_input.value().trim().to_string();
+        let selected = self.selected_profile_name().cloned();
+
+        // Write the UI state first so a saved profile is exactly what is active
+        if matches!(
+            self.profile_action,
+            ProfileAction::Save | ProfileAction::Overwrite
+        ) {
+            if let Err(e) = self.write_state() {
+                eprintln!("Error saving state: {e}");
+            }
+        }
+
+        self.profile_message = None;
+        if let Ok(mut state) = crate::state::AppState::load_from_file(&self.state_file) {
+            // The profile to select once the list is reloaded
+            let target = match (self.profile_action, selected) {
+                (ProfileAction::Save, _) => {
+                    let description = format!(
+                        "Saved on {}",
+                        chrono::Utc::now().format("%Y-%m-%d %H:%M:%S")
                     );
+                    self.profile_message =
+                        state.save_current_as_profile(input.clone(), description);
+                    Some(input)
+                }
+                (ProfileAction::Rename, Some(name)) => {
+                    if name != input {
+                        state.rename_profile(&name, &input);
+                    }
+                    Some(input)
+                }
+                (ProfileAction::Duplicate, Some(name)) => {
+                    state.copy_profile(&name, &input);
+                    Some(input)
+                }
+                (ProfileAction::Describe, Some(name)) => {
+                    if let Some(profile) = state.profiles.get_mut(&name) {
+                        profile.description = input;
+                    }
+                    Some(name)
                 }
+                (ProfileAction::Overwrite, Some(name)) => {
+                    let description = state
+                        .profiles
+                        .get(&name)
+                        .map(|profile| profile.description.clone())
+                        .unwrap_or_default();
+                    self.profile_message = state.save_current_as_profile(name.clone(), description);
+                    Some(name)
+                }
+                _ => None,
+            };
 
+            if let Some(target) = target {
+                log_debug!("UI: {:?} profile '{}'", self.profile_action, target);
                 if let Err(e) = state.save_to_file(&self.state_file) {
                     eprintln!("Error saving profile: {e}");
-                } else if std::env::var("ICAROS_DEBUG").is_ok() {
-                    eprintln!("Profile saved successfully");
                 }
-
                 self.load_profiles();
+                self.select_profile(&target);
             }
         }
-        self.profile_input_mode = false;
-        self.profile_input_buffer.clear();
-        self.profile_action = ProfileAction::None;
+        self.cancel_profile_input();
     }
 
     pub fn switch_to_profile(&mut self, name: &str) {
+        self.activate_profiles(&[name.to_string()]);
+    }
+
+    /// Layer the selected profile on top of the active ones.
+    pub fn push_selected_profile(&mut self) {
+        let Some(name) = self
+            .profile_list_state
+            .selected()
+            .and_then(|selected| self.profile_names.get(selected))
+            .cloned()
+        else {
+            return;
+        };
+        let mut stack = self.active_profile_stack.clone();
+        stack.retain(|layer| *layer != name);
+        stack.push(name);
+        self.activate_profiles(&stack);
+        self.load_profiles();
+    }
+
+    fn activate_profiles(&mut self, stack: &[String]) {
         if let Ok(mut state) = crate::state::AppState::load_from_file(&self.state_file) {
-            if state.switch_to_profile(name) {
-                let _ = state.save_to_file(&self.state_file);
-                self.active_profile_name = Some(name.to_string());
-
-                // Apply the new patterns to the tree
-                self.explicitly_locked_paths.clear();
-                self.explicitly_unlocked_paths.clear();
-
-                // Convert patterns back to paths
-                for pattern in &state.locked_patterns {
-                    if let Some(path) = pattern_to_path(&self.root_path, pattern) {
-                        self.explicitly_locked_paths.push(path);
-                    }
+            match state.activate(stack) {
+                Ok(()) => {
+                    let _ = state.save_to_file(&self.state_file);
+                    self.active_profile_name = state.active_profile.clone();
+                    self.active_profile_stack = state.active_stack();
+
+                    // Apply every part of the profile's policy to the tree
+                    self.restore_state(&state);
+                    self.update_selected_profile_rules();
                 }
-
-                for pattern in &state.unlocked_patterns {
-                    if let Some(path) = pattern_to_path(&self.root_path, pattern) {
-                        self.explicitly_unlocked_paths.push(path);
-                    }
+                Err(e) => {
+                    log_debug!("UI: Cannot activate {:?}: {}", stack, e);
                 }
+            }
+        }
+    }
 
-                // Reapply locks to the tree
-                self.reapply_explicit_locks();
-                self.update_items();
+    /// Switch to the profile the branch rules map the checked-out branch to.
+    pub fn follow_branch(&mut self) {
+        let Some(branch) = self
+            .git_manager
+            .as_ref()
+            .and_then(GitManager::current_branch)
+        else {
+            return;
+        };
+        let Ok(state) = crate::state::AppState::load_from_file(&self.state_file) else {
+            return;
+        };
+        if state.quest.is_some() {
+            return;
+        }
+        if let Some(profile) = state.profile_for_branch(&branch) {
+            if state.active_profile.as_deref() != Some(profile) {
+                log_debug!("UI: Branch '{}' selects profile '{}'", branch, profile);
+                self.switch_to_profile(profile);
+                self.load_profiles();
             }
         }
     }
@@ -968,52 +2374,82 @@ impl App {
         }
     }
 
-    fn get_current_locked_patterns(&self) -> Vec<String> {
+    fn get_current_allow_create_patterns(&self) -> Vec<String> {
         let mut patterns = Vec::new();
-
-        // If no explicit locks, check if everything is locked via tree state
-        if self.explicitly_locked_paths.is_empty() {
-            // Check if the root is locked in the tree
-            if self.tree.is_locked {
-                patterns.push("**".to_string());
-            }
-        } else {
-            for path in &self.explicitly_locked_paths {
-                if let Ok(relative) = path.strip_prefix(&self.root_path) {
-                    if relative.as_os_str().is_empty() {
-                        patterns.push("**".to_string());
-                    } else {
-                        let pattern = if path.is_dir() {
-                            format!("{}/**", relative.display())
-                        } else {
-                            relative.display().to_string()
-                        };
-                        patterns.push(pattern);
-                    }
+        for path in &self.explicitly_allow_create_paths {
+            if let Ok(relative) = path.strip_prefix(&self.root_path) {
+                if relative.as_os_str().is_empty() {
+                    patterns.push("**".to_string());
+                } else {
+                    patterns.push(relative.display().to_string());
                 }
             }
         }
-
         patterns.sort();
         patterns.dedup();
         patterns
     }
+}
 
-    fn get_current_unlocked_patterns(&self) -> Vec<String> {
-        let mut patterns = Vec::new();
-        for path in &self.explicitly_unlocked_paths {
-            if let Ok(relative) = path.strip_prefix(&self.root_path) {
-                let pattern = if path.is_dir() {
-                    format!("{}/**", relative.display())
-                } else {
-                    relative.display().to_string()
-                };
-                patterns.push(pattern);
-            }
+impl Drop for App {
+    fn drop(&mut self) {
+        if self.owns_checkpoint {
+            self.drop_session_checkpoint();
         }
-        patterns.sort();
-        patterns.dedup();
-        patterns
+    }
+}
+
+fn key_matches(binding: &str, code: KeyCode) -> bool {
+    match code {
+        KeyCode::Char(' ') => binding.eq_ignore_ascii_case("space"),
+        KeyCode::Char(c) => {
+            let mut chars = binding.chars();
+            chars.next() == Some(c) && chars.next().is_none()
+        }
+        KeyCode::Enter => binding.eq_ignore_ascii_case("enter"),
+        KeyCode::Esc => binding.eq_ignore_ascii_case("esc"),
+        KeyCode::Backspace => binding.eq_ignore_ascii_case("backspace"),
+        KeyCode::Delete => binding.eq_ignore_ascii_case("delete"),
+        KeyCode::F(n) => binding.eq_ignore_ascii_case(&format!("f{n}")),
+        _ => false,
+    }
+}
+
+struct Palette {
+    lock_icon: Color,
+    locked: Color,
+    allow_create: Color,
+    directory: Color,
+    file: Color,
+    accent: Color,
+}
+
+fn palette(theme: ThemeName) -> Palette {
+    match theme {
+        ThemeName::Desert => Palette {
+            lock_icon: Color::Rgb(255, 107, 53),
+            locked: Color::Rgb(255, 127, 80),      // Coral
+            allow_create: Color::Rgb(0, 206, 209), // Cyan
+            directory: Color::Rgb(0, 206, 209),    // Static cyan for directories
+            file: Color::Rgb(255, 215, 0),         // Gold
+            accent: Color::Rgb(138, 43, 226),      // Violet
+        },
+        ThemeName::Ocean => Palette {
+            lock_icon: Color::Rgb(191, 97, 106),
+            locked: Color::Rgb(208, 135, 112),       // Orange
+            allow_create: Color::Rgb(163, 190, 140), // Green
+            directory: Color::Rgb(143, 161, 179),    // Slate blue
+            file: Color::Rgb(192, 197, 206),         // Pale grey
+            accent: Color::Rgb(180, 142, 173),       // Mauve
+        },
+        ThemeName::Mono => Palette {
+            lock_icon: Color::White,
+            locked: Color::White,
+            allow_create: Color::Gray,
+            directory: Color::Gray,
+            file: Color::Reset,
+            accent: Color::DarkGray,
+        },
     }
 }
 
@@ -1031,15 +2467,17 @@ fn toggle_expand_at_path(node: &mut TreeNode, target_path: &std::path::Path) ->
     false
 }
 
-fn toggle_create_in_locked_at_path(node: &mut TreeNode, target_path: &std::path::Path) -> bool {
+fn allow_create_at_path(node: &mut TreeNode, target_path: &std::path::Path) -> bool {
     if node.path == target_path {
-        node.toggle_create_in_locked();
+        node.allow_create_in_locked = node.is_dir && node.is_locked;
         return true;
     }
 
-    for child in &mut node.children {
-        if toggle_create_in_locked_at_path(child, target_path) {
-            return true;
+    if target_path.starts_with(&node.path) {
+        for child in &mut node.children {
+            if allow_create_at_path(child, target_path) {
+                return true;
+            }
         }
     }
     false
@@ -1216,7 +2654,15 @@ fn pattern_to_path(root: &std::path::Path, pattern: &str) -> Option<std::path::P
     Some(root.join(pattern))
 }
 
+fn patterns_to_paths(root: &std::path::Path, patterns: &[String]) -> Vec<std::path::PathBuf> {
+    patterns
+        .iter()
+        .filter_map(|pattern| pattern_to_path(root, pattern))
+        .collect()
+}
+
 fn render_file_guardian(f: &mut ratatui::Frame, app: &mut App, area: Rect) {
+    let colors = palette(app.settings.theme);
     let items: Vec<ListItem> = app
         .items
         .iter()
@@ -1230,14 +2676,11 @@ fn render_file_guardian(f: &mut ratatui::Frame, app: &mut App, area: Rect) {
             }
 
             if node.is_locked {
-                spans.push(Span::styled(
-                    "🔒 ",
-                    Style::default().fg(Color::Rgb(255, 107, 53)),
-                ));
+                spans.push(Span::styled("🔒 ", Style::default().fg(colors.lock_icon)));
                 if node.is_dir && node.allow_create_in_locked {
                     spans.push(Span::styled(
                         "➕ ",
-                        Style::default().fg(Color::Rgb(0, 206, 209)),
+                        Style::default().fg(colors.allow_create),
                     ));
                 } else {
                     spans.push(Span::raw("   "));
@@ -1249,18 +2692,22 @@ fn render_file_guardian(f: &mut ratatui::Frame, app: &mut App, area: Rect) {
 
             let style = if node.is_locked {
                 Style::default()
-                    .fg(Color::Rgb(255, 127, 80)) // Coral
+                    .fg(colors.locked)
                     .add_modifier(Modifier::BOLD)
             } else if node.is_dir {
                 Style::default()
-                    .fg(Color::Rgb(0, 206, 209)) // Static cyan for directories
+                    .fg(colors.directory)
                     .add_modifier(Modifier::BOLD)
             } else {
-                Style::default().fg(Color::Rgb(255, 215, 0)) // Gold
+                Style::default().fg(colors.file)
             };
 
             spans.push(Span::styled(&node.name, style));
 
+            if app.quest_marks.contains(&node.path) {
+                spans.push(Span::styled(" 🎯", Style::default().fg(colors.accent)));
+            }
+
             ListItem::new(Line::from(spans))
         })
         .collect();
@@ -1269,13 +2716,13 @@ fn render_file_guardian(f: &mut ratatui::Frame, app: &mut App, area: Rect) {
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .border_style(Style::default().fg(Color::Rgb(138, 43, 226))) // Static violet
+                .border_style(Style::default().fg(colors.accent))
                 .title(" 🦙 File Guardian 🦙 ")
                 .style(Style::default().bg(Color::Rgb(0, 0, 0))),
         )
         .highlight_style(
             Style::default()
-                .bg(Color::Rgb(138, 43, 226))
+                .bg(colors.accent)
                 .add_modifier(Modifier::BOLD),
         );
 
@@ -1295,16 +2742,41 @@ fn render_git_stage(f: &mut ratatui::Frame, app: &mut App, area: Rect) {
         )
         .split(area);
 
-    // Render file list
-    let file_items: Vec<ListItem> = app
-        .git_files
-        .iter()
-        .map(|file| {
+    // Render file list, in a Staged and an Unstaged section
+    let staged_count = app.git_files.iter().filter(|file| file.staged).count();
+    let unstaged_count = app.git_files.len() - staged_count;
+    let section_header = |title: &str, count: usize| {
+        ListItem::new(Line::from(Span::styled(
+            format!("{title} ({count})"),
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        )))
+    };
+    let mut file_items: Vec<ListItem> = Vec::new();
+    let review_files = app
+        .git_review
+        .as_ref()
+        .map(|review| review.files.as_slice());
+    for (index, file) in app.git_files.iter().enumerate() {
+        // Review mode has no sections, and shows each file's line counts
+        let violation = app.git_violations.get(index).copied().flatten();
+        if let Some(changed) = review_files.and_then(|files| files.get(index)) {
+            file_items.push(review_item(changed, violation));
+            continue;
+        }
+        if index == 0 && file.staged {
+            file_items.push(section_header("Staged", staged_count));
+        }
+        if index == staged_count {
+            file_items.push(section_header("Unstaged", unstaged_count));
+        }
+        file_items.push({
             let status_color = file.status.color();
             let status_str = file.status.to_str();
             let staged_indicator = if file.staged { "●" } else { "○" };
 
-            let spans = vec![
+            let mut spans = vec![
                 Span::styled(
                     staged_indicator,
                     Style::default().fg(if file.staged {
@@ -1317,14 +2789,32 @@ fn render_git_stage(f: &mut ratatui::Frame, app: &mut App, area: Rect) {
                 Span::styled(status_str, Style::default().fg(status_color)),
                 Span::raw(" "),
                 Span::styled(
-                    file.path.display().to_string(),
+                    match &file.renamed_from {
+                        Some(old_path) => {
+                            format!("{} → {}", old_path.display(), file.path.display())
+                        }
+                        None => file.path.display().to_string(),
+                    },
                     Style::default().fg(Color::White),
                 ),
             ];
+            spans.extend(violation_span(violation));
 
             ListItem::new(Line::from(spans))
-        })
-        .collect();
+        });
+    }
+    // Point the list's selection past the section headers
+    if !app.git_files.is_empty() && app.git_review.is_some() {
+        app.git_file_list_state.select(Some(app.git_selected_file));
+    } else if !app.git_files.is_empty() {
+        let headers = if app.git_selected_file >= staged_count && staged_count > 0 {
+            2
+        } else {
+            1
+        };
+        app.git_file_list_state
+            .select(Some(app.git_selected_file + headers));
+    }
 
     let file_list = List::new(file_items)
         .block(
@@ -1335,7 +2825,16 @@ fn render_git_stage(f: &mut ratatui::Frame, app: &mut App, area: Rect) {
                 } else {
                     Color::Gray
                 }))
-                .title(" Changed Files ")
+                .title({
+                    let title = match &app.git_review {
+                        Some(review) => review_title(review),
+                        None => " Changed Files ".to_string(),
+                    };
+                    match app.git_violations.iter().flatten().count() {
+                        0 => title,
+                        count => format!("{title}- {count} break locks "),
+                    }
+                })
                 .style(Style::default().bg(Color::Rgb(0, 0, 0))),
         )
         .highlight_style(
@@ -1346,8 +2845,19 @@ fn render_git_stage(f: &mut ratatui::Frame, app: &mut App, area: Rect) {
 
     f.render_stateful_widget(file_list, chunks[0], &mut app.git_file_list_state);
 
-    // Render diff view
+    // Render diff view, starting with what the hunks don't show
     let mut diff_lines = Vec::new();
+    for note in &app.git_diff_notes {
+        diff_lines.push(Line::from(Span::styled(
+            note.clone(),
+            Style::default()
+                .fg(Color::Magenta)
+                .add_modifier(Modifier::BOLD),
+        )));
+    }
+    if !app.git_diff_notes.is_empty() && !app.git_diff_hunks.is_empty() {
+        diff_lines.push(Line::from(""));
+    }
     let mut _current_line = 0;
 
     for (hunk_idx, hunk) in app.git_diff_hunks.iter().enumerate() {
@@ -1362,16 +2872,60 @@ fn render_git_stage(f: &mut ratatui::Frame, app: &mut App, area: Rect) {
         diff_lines.push(Line::from(Span::styled(&hunk.header, hunk_style)));
         _current_line += 1;
 
-        // Add hunk lines
-        for line in &hunk.lines {
-            let (style, prefix) = match line.origin {
-                '+' => (Style::default().fg(Color::Green), "+"),
-                '-' => (Style::default().fg(Color::Red), "-"),
-                _ => (Style::default().fg(Color::Gray), " "),
+        if app.git_side_by_side && !app.git_line_mode {
+            let width = usize::from(chunks[1].width.saturating_sub(2));
+            for row in diff_layout::side_by_side(hunk) {
+                diff_lines.push(side_by_side_line(&row, width, &app.git_diff_highlights));
+                _current_line += 1;
+            }
+            if hunk_idx < app.git_diff_hunks.len() - 1 {
+                diff_lines.push(Line::from(""));
+                _current_line += 1;
+            }
+            continue;
+        }
+
+        // Add hunk lines, with a pick column when choosing lines of this hunk
+        let picking = app.git_line_mode && hunk_idx == app.git_selected_hunk;
+        for (line_idx, line) in hunk.lines.iter().enumerate() {
+            let (mut style, prefix, background) = match line.origin {
+                '+' => (Style::default().fg(Color::Green), "+", Some(ADDED_BG)),
+                '-' => (Style::default().fg(Color::Red), "-", Some(REMOVED_BG)),
+                _ => (Style::default().fg(Color::Gray), " ", None),
             };
 
-            let content = format!("{}{}", prefix, line.content);
-            diff_lines.push(Line::from(Span::styled(content, style)));
+            let mut spans = Vec::new();
+            if picking {
+                let marker = if app.git_selected_lines.contains(&line_idx) {
+                    "✓"
+                } else {
+                    " "
+                };
+                spans.push(Span::styled(marker, Style::default().fg(Color::Yellow)));
+                if line_idx == app.git_line_cursor {
+                    style = style.add_modifier(Modifier::REVERSED);
+                }
+            }
+            match app.git_diff_highlights.line(line) {
+                // Syntax colors, over the added or removed line's background
+                Some(tokens) => {
+                    let layered = |style: Style| match background {
+                        Some(background) => style.bg(background),
+                        None => style,
+                    };
+                    spans.push(Span::styled(prefix, layered(style)));
+                    for token in tokens {
+                        spans.push(Span::styled(
+                            token.text.clone(),
+                            layered(style.fg(token.color)),
+                        ));
+                    }
+                }
+                None => {
+                    spans.push(Span::styled(format!("{}{}", prefix, line.content), style));
+                }
+            }
+            diff_lines.push(Line::from(spans));
             _current_line += 1;
         }
 
@@ -1391,12 +2945,439 @@ fn render_git_stage(f: &mut ratatui::Frame, app: &mut App, area: Rect) {
                 } else {
                     Color::Gray
                 }))
-                .title(" Diff ")
+                .title(match &app.git_message {
+                    Some(message) => format!(" Diff ({}) - {message} ", diff_mode(app)),
+                    None => format!(" Diff ({}) ", diff_mode(app)),
+                })
                 .style(Style::default().bg(Color::Rgb(0, 0, 0))),
         )
         .scroll((app.git_diff_scroll, 0));
 
     f.render_widget(diff_widget, chunks[1]);
+
+    if let Some(dialog) = &app.commit_dialog {
+        render_commit_dialog(f, dialog, area);
+    }
+    if let Some(input) = &app.git_review_input {
+        let popup_area = centered_rect(60, 20, area);
+        let (before, under, after) = input.split_at_cursor();
+        f.render_widget(
+            Block::default().style(Style::default().bg(Color::Rgb(0, 0, 0))),
+            popup_area,
+        );
+        f.render_widget(
+            Paragraph::new(vec![
+                Line::from(vec![
+                    Span::styled("Review against: ", Style::default().fg(Color::Yellow)),
+                    Span::raw(before.to_string()),
+                    Span::styled(
+                        under.unwrap_or(' ').to_string(),
+                        Style::default().add_modifier(Modifier::REVERSED),
+                    ),
+                    Span::raw(after.to_string()),
+                ]),
+                Line::from(""),
+                Line::from(Span::styled(
+                    "A branch or commit, main... for the merge base with main, or empty for the session checkpoint",
+                    Style::default().fg(Color::Gray),
+                )),
+            ])
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(Color::Yellow))
+                    .title(" Enter to review, Esc to cancel "),
+            )
+            .wrap(ratatui::widgets::Wrap { trim: true }),
+            popup_area,
+        );
+    }
+    if let Some(prompt) = app.discard_prompt() {
+        let popup_area = centered_rect(60, 20, area);
+        f.render_widget(
+            Block::default().style(Style::default().bg(Color::Rgb(0, 0, 0))),
+            popup_area,
+        );
+        f.render_widget(
+            Paragraph::new(vec![
+                Line::from(prompt),
+                Line::from(""),
+                Line::from(Span::styled(
+                    "A copy is kept under .git/icaros/trash/",
+                    Style::default().fg(Color::Gray),
+                )),
+            ])
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(Color::Red))
+                    .title(" y: Discard | n: Cancel "),
+            )
+            .wrap(ratatui::widgets::Wrap { trim: true }),
+            popup_area,
+        );
+    }
+    if let Some(prompt) = app.locked_stage_prompt() {
+        let popup_area = centered_rect(60, 20, area);
+        f.render_widget(
+            Block::default().style(Style::default().bg(Color::Rgb(0, 0, 0))),
+            popup_area,
+        );
+        f.render_widget(
+            Paragraph::new(vec![
+                Line::from(prompt),
+                Line::from(""),
+                Line::from(Span::styled(
+                    "Or cancel, then a to unlock it or x to revert it",
+                    Style::default().fg(Color::Gray),
+                )),
+            ])
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(Color::Red))
+                    .title(" y: Stage | n: Cancel "),
+            )
+            .wrap(ratatui::widgets::Wrap { trim: true }),
+            popup_area,
+        );
+    }
+}
+
+/// The mark after a file that breaks a lock.
+fn violation_span(violation: Option<LockViolation>) -> Option<Span<'static>> {
+    violation.map(|violation| {
+        Span::styled(
+            format!(" 🔒 {}", violation.label()),
+            Style::default().fg(Color::Red),
+        )
+    })
+}
+
+/// A file in Review mode's list: its status, path and lines added and removed.
+fn review_item(changed: &ReviewFile, violation: Option<LockViolation>) -> ListItem<'static> {
+    let file = &changed.file;
+    let path = match &file.renamed_from {
+        Some(old_path) => format!("{} → {}", old_path.display(), file.path.display()),
+        None => file.path.display().to_string(),
+    };
+    let mut spans = vec![
+        Span::styled(
+            file.status.to_str(),
+            Style::default().fg(file.status.color()),
+        ),
+        Span::raw(" "),
+        Span::styled(path, Style::default().fg(Color::White)),
+        Span::raw(" "),
+    ];
+    if changed.binary {
+        spans.push(Span::styled("binary", Style::default().fg(Color::Magenta)));
+    } else {
+        spans.push(Span::styled(
+            format!("+{}", changed.additions),
+            Style::default().fg(Color::Green),
+        ));
+        spans.push(Span::raw(" "));
+        spans.push(Span::styled(
+            format!("-{}", changed.deletions),
+            Style::default().fg(Color::Red),
+        ));
+    }
+    spans.extend(violation_span(violation));
+    ListItem::new(Line::from(spans))
+}
+
+/// The file list title in Review mode, with the base and the total changes.
+fn review_title(review: &Review) -> String {
+    let additions: usize = review.files.iter().map(|file| file.additions).sum();
+    let deletions: usize = review.files.iter().map(|file| file.deletions).sum();
+    format!(
+        " Review: {} ({}) - {} file(s) +{additions} -{deletions} ",
+        review.base.label(),
+        &review.commit.to_string()[..7],
+        review.files.len()
+    )
+}
+
+/// The diff title's description of what is shown and how.
+fn diff_mode(app: &App) -> String {
+    let mut mode = match &app.git_review {
+        Some(review) => format!("{} → working tree", review.base.label()),
+        None => app.git_diff_target.label().to_string(),
+    };
+    if app.git_side_by_side {
+        mode.push_str(", side by side");
+    }
+    if app.git_ignore_whitespace {
+        mode.push_str(", ignoring whitespace");
+    }
+    mode
+}
+
+// Backgrounds of added and removed lines, and of the words that changed in them
+const ADDED_BG: Color = Color::Rgb(20, 45, 20);
+const REMOVED_BG: Color = Color::Rgb(55, 20, 20);
+const ADDED_WORD_BG: Color = Color::Rgb(20, 70, 20);
+const REMOVED_WORD_BG: Color = Color::Rgb(90, 20, 20);
+
+/// One row of the side-by-side diff: the old line on the left, the new one on
+/// the right, with the words that changed between them highlighted.
+fn side_by_side_line(
+    row: &SideBySideRow,
+    width: usize,
+    highlights: &DiffHighlights,
+) -> Line<'static> {
+    let side_width = width.saturating_sub(3) / 2;
+    let (old_segments, new_segments) = match (&row.old, &row.new) {
+        (Some((_, old)), Some((_, new))) if row.is_modified() => {
+            let (old, new) = diff_layout::word_diff(old, new);
+            (Some(old), Some(new))
+        }
+        _ => (None, None),
+    };
+    let changed = row.is_modified() || row.old.is_none() || row.new.is_none();
+
+    let old = row.old.as_ref().map(|(number, text)| {
+        let pieces = side_pieces(
+            text,
+            highlights.old_line(*number, text),
+            old_segments,
+            changed.then_some((Color::Red, REMOVED_BG, REMOVED_WORD_BG)),
+        );
+        (*number, pieces)
+    });
+    let new = row.new.as_ref().map(|(number, text)| {
+        let pieces = side_pieces(
+            text,
+            highlights.new_line(*number, text),
+            new_segments,
+            changed.then_some((Color::Green, ADDED_BG, ADDED_WORD_BG)),
+        );
+        (*number, pieces)
+    });
+
+    let mut spans = side_spans(old, side_width);
+    spans.push(Span::styled(" │ ", Style::default().fg(Color::DarkGray)));
+    spans.extend(side_spans(new, side_width));
+    Line::from(spans)
+}
+
+/// The styled pieces of one side's text. `change` is the plain color, line
+/// background and changed-word background of a removed or added line; syntax
+/// colors, when there are any, take the place of the plain color.
+fn side_pieces(
+    text: &str,
+    tokens: Option<&[Token]>,
+    segments: Option<Vec<Segment>>,
+    change: Option<(Color, Color, Color)>,
+) -> Vec<(String, Style)> {
+    let plain_color = change.map_or(Color::Gray, |(color, ..)| color);
+    let plain = [Token {
+        text: text.to_string(),
+        color: plain_color,
+    }];
+    let highlighted = tokens.is_some();
+    let segments = segments.unwrap_or_default();
+
+    highlight::mark_changes(tokens.unwrap_or(&plain), &segments)
+        .into_iter()
+        .map(|(token, word_changed)| {
+            let style = match change {
+                Some((_, _, word_bg)) if word_changed => {
+                    let fg = if highlighted {
+                        token.color
+                    } else {
+                        Color::White
+                    };
+                    Style::default().fg(fg).bg(word_bg)
+                }
+                Some((_, line_bg, _)) if highlighted => {
+                    Style::default().fg(token.color).bg(line_bg)
+                }
+                _ => Style::default().fg(token.color),
+            };
+            (token.text, style)
+        })
+        .collect()
+}
+
+/// One side of a side-by-side row, cut or padded to `width` columns.
+fn side_spans(side: Option<(u32, Vec<(String, Style)>)>, width: usize) -> Vec<Span<'static>> {
+    let Some((number, pieces)) = side else {
+        return vec![Span::raw(" ".repeat(width))];
+    };
+
+    let mut spans = vec![Span::styled(
+        format!("{number:>4} "),
+        Style::default().fg(Color::DarkGray),
+    )];
+    let mut room = width.saturating_sub(5);
+    for (text, style) in pieces {
+        if room == 0 {
+            break;
+        }
+        let text: String = text.chars().take(room).collect();
+        room -= text.chars().count();
+        spans.push(Span::styled(text, style));
+    }
+    spans.push(Span::raw(" ".repeat(room)));
+    spans
+}
+
+fn render_commit_dialog(f: &mut ratatui::Frame, dialog: &CommitDialog, area: Rect) {
+    let popup_area = centered_rect(70, 80, area);
+    f.render_widget(
+        Block::default().style(Style::default().bg(Color::Rgb(0, 0, 0))),
+        popup_area,
+    );
+    let summary_height = (dialog.staged.len() as u16).clamp(1, 8) + 2;
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Min(4),
+            Constraint::Length(summary_height),
+            Constraint::Length(3),
+        ])
+        .split(popup_area);
+
+    // Options, with the author when it's overridden
+    let check = |on: bool| if on { "[x]" } else { "[ ]" };
+    let mut options = vec![Span::raw(format!(
+        "{} Amend (^A)  {} Sign-off (^O)  {} Author (^T)",
+        check(dialog.amend),
+        check(dialog.sign_off),
+        check(dialog.custom_author)
+    ))];
+    if dialog.custom_author {
+        options.push(Span::raw(": "));
+        if dialog.editing_author {
+            let (before, under, after) = dialog.author.split_at_cursor();
+            options.push(Span::raw(before.to_string()));
+            options.push(Span::styled(
+                under.unwrap_or(' ').to_string(),
+                Style::default().add_modifier(Modifier::REVERSED),
+            ));
+            options.push(Span::raw(after.to_string()));
+        } else {
+            options.push(Span::raw(dialog.author.value().to_string()));
+        }
+    }
+    f.render_widget(
+        Paragraph::new(Line::from(options)).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Yellow))
+                .title(if dialog.amend {
+                    " Amend Last Commit "
+                } else {
+                    " Commit "
+                }),
+        ),
+        rows[0],
+    );
+
+    let message_lines: Vec<Line> = dialog
+        .message
+        .lines()
+        .iter()
+        .enumerate()
+        .map(|(row, line)| {
+            if row != dialog.message.row() || dialog.editing_author {
+                return Line::from(line.value().to_string());
+            }
+            let (before, under, after) = line.split_at_cursor();
+            Line::from(vec![
+                Span::raw(before.to_string()),
+                Span::styled(
+                    under.unwrap_or(' ').to_string(),
+                    Style::default().add_modifier(Modifier::REVERSED),
+                ),
+                Span::raw(after.to_string()),
+            ])
+        })
+        .collect();
+    // Keep the cursor's line in view
+    let message_height = rows[1].height.saturating_sub(2) as usize;
+    let scroll = (dialog.message.row() + 1).saturating_sub(message_height);
+    f.render_widget(
+        Paragraph::new(message_lines)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(if dialog.editing_author {
+                        Color::Gray
+                    } else {
+                        Color::Yellow
+                    }))
+                    .title(" Message "),
+            )
+            .scroll((scroll.try_into().unwrap_or(u16::MAX), 0)),
+        rows[1],
+    );
+
+    let mut summary: Vec<Line> = dialog
+        .staged
+        .iter()
+        .map(|file| {
+            let locked = dialog.locked.contains(&file.path);
+            Line::from(vec![
+                Span::styled(
+                    file.status.to_str(),
+                    Style::default().fg(file.status.color()),
+                ),
+                Span::raw(" "),
+                Span::styled(
+                    file.path.display().to_string(),
+                    Style::default().fg(if locked { Color::Red } else { Color::White }),
+                ),
+                Span::raw(if locked { " 🔒" } else { "" }),
+            ])
+        })
+        .collect();
+    if summary.is_empty() {
+        summary.push(Line::from(Span::styled(
+            "Nothing staged",
+            Style::default().fg(Color::Gray),
+        )));
+    }
+    let summary_title = match (dialog.locked.len(), dialog.override_locks) {
+        (0, _) => format!(" Staged ({}) ", dialog.staged.len()),
+        (locked, false) => format!(" Staged ({}) - {locked} locked ", dialog.staged.len()),
+        (locked, true) => format!(
+            " Staged ({}) - {locked} locked, committing anyway ",
+            dialog.staged.len()
+        ),
+    };
+    f.render_widget(
+        Paragraph::new(summary).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(if dialog.locked.is_empty() {
+                    Color::Gray
+                } else {
+                    Color::Red
+                }))
+                .title(summary_title),
+        ),
+        rows[2],
+    );
+
+    let (footer, color) = match &dialog.error {
+        Some(error) => (error.clone(), Color::Red),
+        None => (
+            "Ctrl+S: Commit | Ctrl+L: Override locks | Tab: Message/author | Esc: Cancel"
+                .to_string(),
+            Color::Gray,
+        ),
+    };
+    f.render_widget(
+        Paragraph::new(footer)
+            .style(Style::default().fg(color))
+            .block(Block::default().borders(Borders::ALL))
+            .wrap(ratatui::widgets::Wrap { trim: true }),
+        rows[3],
+    );
 }
 
 fn render_profiles(f: &mut ratatui::Frame, app: &mut App, area: Rect) {
@@ -1484,24 +3465,39 @@ fn render_profiles(f: &mut ratatui::Frame, app: &mut App, area: Rect) {
         .map(|name| {
             let mut spans = vec![Span::raw("  ")];
 
-            // Active profile indicator
+            // Active profile indicator; lower layers of a stack are half-filled
             if Some(name) == app.active_profile_name.as_ref() {
                 spans.push(Span::styled("● ", Style::default().fg(Color::Green)));
+            } else if app.active_profile_stack.contains(name) {
+                spans.push(Span::styled("◐ ", Style::default().fg(Color::Green)));
             } else {
                 spans.push(Span::raw("  "));
             }
 
             spans.push(Span::styled(name, Style::default().fg(Color::Cyan)));
 
+            // Agents this profile governs
+            if let Some(agents) = app.profile_agents.get(name) {
+                spans.push(Span::styled(
+                    format!("  🤖 {}", agents.join(", ")),
+                    Style::default().fg(Color::Yellow),
+                ));
+            }
+
             ListItem::new(Line::from(spans))
         })
         .collect();
 
-    let active_profile_text = app
-        .active_profile_name
-        .as_ref()
-        .map(|name| format!(" Active: {name} "))
-        .unwrap_or_else(|| " No Active Profile ".to_string());
+    let active_profile_text = if app.active_profile_stack.is_empty() {
+        " No Active Profile ".to_string()
+    } else if app.active_profile_modified {
+        format!(
+            " Active: {} (unsaved changes) ",
+            app.active_profile_stack.join(" + ")
+        )
+    } else {
+        format!(" Active: {} ", app.active_profile_stack.join(" + "))
+    };
 
     let list = List::new(profile_items)
         .block(
@@ -1517,21 +3513,63 @@ fn render_profiles(f: &mut ratatui::Frame, app: &mut App, area: Rect) {
                 .add_modifier(Modifier::BOLD),
         );
 
-    f.render_stateful_widget(list, chunks[0], &mut app.profile_list_state);
+    let panes = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
+        .split(chunks[0]);
+
+    let details = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .split(panes[1]);
+
+    f.render_stateful_widget(list, panes[0], &mut app.profile_list_state);
+    render_resolved_profile(f, app, details[0]);
+    render_profile_preview(f, app, details[1]);
 
     // Input area (when in input mode)
     if app.profile_input_mode {
-        let input_text = match app.profile_action {
-            ProfileAction::Save => format!("Save current profile as: {}", app.profile_input_buffer),
-            _ => app.profile_input_buffer.clone(),
+        let selected = app.selected_profile_name().cloned().unwrap_or_default();
+        let prompt = match app.profile_action {
+            ProfileAction::Save => "Save current locks as: ".to_string(),
+            ProfileAction::Rename => format!("Rename '{selected}' to: "),
+            ProfileAction::Duplicate => format!("Duplicate '{selected}' as: "),
+            ProfileAction::Describe => format!("Description of '{selected}': "),
+            ProfileAction::Overwrite => {
+                format!("Overwrite '{selected}' with the current locks?")
+            }
+            ProfileAction::None => String::new(),
+        };
+
+        let mut spans = vec![Span::styled(prompt, Style::default().fg(Color::Yellow))];
+        if app.profile_action != ProfileAction::Overwrite {
+            let (before, under, after) = app.profile_input.split_at_cursor();
+            spans.push(Span::raw(before.to_string()));
+            spans.push(Span::styled(
+                under.unwrap_or(' ').to_string(),
+                Style::default().add_modifier(Modifier::REVERSED),
+            ));
+            spans.push(Span::raw(after.to_string()));
+        }
+
+        let (title, border) = match app.profile_input_problem() {
+            Some(problem) => (format!(" {problem} "), Color::Red),
+            None if app.profile_action == ProfileAction::Overwrite => (
+                " Enter to overwrite, Esc to cancel ".to_string(),
+                Color::Yellow,
+            ),
+            None => (
+                " Enter to confirm, Esc to cancel ".to_string(),
+                Color::Yellow,
+            ),
         };
 
-        let input_paragraph = Paragraph::new(input_text)
+        let input_paragraph = Paragraph::new(Line::from(spans))
             .block(
                 Block::default()
                     .borders(Borders::ALL)
-                    .border_style(Style::default().fg(Color::Yellow))
-                    .title(" Enter Profile Name (Enter to save, Esc to cancel) "),
+                    .border_style(Style::default().fg(border))
+                    .title(title),
             )
             .style(Style::default().fg(Color::White));
 
@@ -1539,7 +3577,7 @@ fn render_profiles(f: &mut ratatui::Frame, app: &mut App, area: Rect) {
     } else {
         // Help text
         let help_text = vec![Line::from(vec![Span::raw(
-            "Enter: Load | s: Save current | d: Delete | r: Refresh | Up/Down: Navigate",
+            "Enter: Load | p: Layer | s: Save new | o: Overwrite | n: Rename | c: Duplicate | e: Describe | d: Delete | r: Refresh",
         )])];
 
         let help_paragraph = Paragraph::new(help_text)
@@ -1547,7 +3585,10 @@ fn render_profiles(f: &mut ratatui::Frame, app: &mut App, area: Rect) {
                 Block::default()
                     .borders(Borders::ALL)
                     .border_style(Style::default().fg(Color::Gray))
-                    .title(" Commands "),
+                    .title(match &app.profile_message {
+                        Some(message) => format!(" Commands - {message} "),
+                        None => " Commands ".to_string(),
+                    }),
             )
             .style(Style::default().fg(Color::Gray));
 
@@ -1555,6 +3596,137 @@ fn render_profiles(f: &mut ratatui::Frame, app: &mut App, area: Rect) {
     }
 }
 
+/// The selected profile's rules after inheritance, each with the profile it came from.
+fn render_resolved_profile(f: &mut ratatui::Frame, app: &App, area: Rect) {
+    let selected = app
+        .profile_list_state
+        .selected()
+        .and_then(|selected| app.profile_names.get(selected));
+
+    let lines: Vec<Line> = match &app.selected_profile_rules {
+        None => vec![Line::from(Span::styled(
+            "No profile selected",
+            Style::default().fg(Color::Gray),
+        ))],
+        Some(Err(error)) => vec![Line::from(Span::styled(
+            error.clone(),
+            Style::default().fg(Color::Red),
+        ))],
+        Some(Ok(resolved)) => {
+            let width = resolved
+                .rules
+                .iter()
+                .map(|rule| rule.pattern.chars().count())
+                .max()
+                .unwrap_or(0);
+            let mut lines = vec![
+                Line::from(Span::styled(
+                    format!("Layers: {}", resolved.layers.join(" → ")),
+                    Style::default().fg(Color::Yellow),
+                )),
+                Line::from(""),
+            ];
+            lines.extend(resolved.rules.iter().map(|rule| {
+                let (icon, color) = match rule.kind {
+                    RuleKind::Locked => ("🔒", Color::Red),
+                    RuleKind::Unlocked => ("🔓", Color::Green),
+                    RuleKind::AllowCreate => ("➕", Color::Cyan),
+                };
+                let inherited = Some(&rule.origin) != selected;
+                Line::from(vec![
+                    Span::raw(format!("{icon} ")),
+                    Span::styled(
+                        format!("{:width$}  ", rule.pattern),
+                        Style::default().fg(color),
+                    ),
+                    Span::styled(
+                        if inherited {
+                            format!("from {}", rule.origin)
+                        } else {
+                            "own".to_string()
+                        },
+                        Style::default().fg(if inherited {
+                            Color::Magenta
+                        } else {
+                            Color::DarkGray
+                        }),
+                    ),
+                ])
+            }));
+            if resolved.rules.is_empty() {
+                lines.push(Line::from(Span::styled(
+                    "No rules",
+                    Style::default().fg(Color::Gray),
+                )));
+            }
+            lines
+        }
+    };
+
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Magenta))
+            .title(" Resolved Rules ")
+            .style(Style::default().bg(Color::Rgb(0, 0, 0))),
+    );
+    f.render_widget(paragraph, area);
+}
+
+/// Paths whose permissions change if the selected profile is loaded, as a tree.
+fn render_profile_preview(f: &mut ratatui::Frame, app: &App, area: Rect) {
+    let (title, lines) = match &app.selected_profile_preview {
+        None => (" Preview ".to_string(), Vec::new()),
+        Some(diff) if diff.is_empty() => (
+            " Preview ".to_string(),
+            vec![Line::from(Span::styled(
+                "No changes: the current locks already match",
+                Style::default().fg(Color::Gray),
+            ))],
+        ),
+        Some(diff) => {
+            let lines = diff
+                .tree_lines()
+                .into_iter()
+                .map(|line| {
+                    let indent = "  ".repeat(line.depth);
+                    let Some(change) = line.diff else {
+                        return Line::from(Span::styled(
+                            format!("{indent}{}", line.name),
+                            Style::default().fg(Color::Gray),
+                        ));
+                    };
+                    let (icon, color) = match change.change {
+                        PathChange::Locked => ("🔒", Color::Red),
+                        PathChange::Unlocked => ("🔓", Color::Green),
+                        PathChange::CreateAllowed => ("➕", Color::Cyan),
+                        PathChange::CreateRevoked => ("➖", Color::Yellow),
+                    };
+                    let mut label = change.change.label().to_string();
+                    if change.is_dir && change.files > 0 {
+                        label.push_str(&format!(" ({} files)", change.files));
+                    }
+                    Line::from(vec![
+                        Span::raw(format!("{indent}{icon} ")),
+                        Span::styled(line.name, Style::default().fg(color)),
+                        Span::styled(format!("  {label}"), Style::default().fg(Color::DarkGray)),
+                    ])
+                })
+                .collect();
+            (format!(" Preview: {} ", diff.summary()), lines)
+        }
+    };
+
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Magenta))
+            .title(title)
+            .style(Style::default().bg(Color::Rgb(0, 0, 0))),
+    );
+    f.render_widget(paragraph, area);
+}
+
 fn render_animation_frame(f: &mut ratatui::Frame, area: Rect, content: &str) {
     // Clear the entire area first
     let clear_widget = Block::default().style(Style::default().bg(Color::Black));
@@ -1722,18 +3894,27 @@ pub fn run_ui(mut app: App) -> Result<App> {
     let mut terminal = Terminal::new(backend)?;
 
     // Get ignore patterns for the file watcher
-    let watcher_ignore_patterns =
-        if let Ok(state) = crate::state::AppState::load_from_file(&app.state_file) {
-            state.ignore_patterns
-        } else {
-            crate::state::default_ignore_patterns()
-        };
+    let watcher_ignore_patterns = app.settings.ignore_patterns.clone();
+
+    let head_file = app
+        .git_manager
+        .as_ref()
+        .map(|git| git.git_dir().join("HEAD"));
+    let watched_head = head_file.clone();
 
     // Set up file watcher
     let (tx, rx) = channel();
     let mut watcher =
         notify::recommended_watcher(move |res: Result<NotifyEvent, notify::Error>| {
             if let Ok(event) = res {
+                if watched_head
+                    .as_ref()
+                    .is_some_and(|head| event.paths.contains(head))
+                {
+                    let _ = tx.send(FsEvent::Head);
+                    return;
+                }
+
                 // Filter out events from ignored directories
                 let should_process = event.paths.iter().any(|path| {
                     let path_str = path.to_string_lossy();
@@ -1751,13 +3932,18 @@ pub fn run_ui(mut app: App) -> Result<App> {
                 });
 
                 if should_process {
-                    let _ = tx.send(());
+                    let _ = tx.send(FsEvent::Tree);
                 }
             }
         })?;
 
-    // Watch the root path
+    // Watch the root path, and the git dir for HEAD if it lives elsewhere
     watcher.watch(&app.root_path, RecursiveMode::Recursive)?;
+    if let Some(git_dir) = head_file.as_ref().and_then(|head| head.parent()) {
+        if !git_dir.starts_with(&app.root_path) {
+            watcher.watch(git_dir, RecursiveMode::NonRecursive)?;
+        }
+    }
 
     let result = run_app(&mut terminal, &mut app, rx);
 
@@ -1810,7 +3996,7 @@ fn get_native_pattern(frame: u64, offset: usize) -> &'static str {
 fn run_app<B: ratatui::backend::Backend>(
     terminal: &mut Terminal<B>,
     app: &mut App,
-    fs_events: Receiver<()>,
+    fs_events: Receiver<FsEvent>,
 ) -> Result<()> {
     let mut last_tick = Instant::now();
     let tick_rate = Duration::from_millis(50);
@@ -1931,10 +4117,22 @@ fn run_app<B: ratatui::backend::Backend>(
             }
 
             let title = vec![Line::from(title_spans)];
+            let mut title_block = Block::default().borders(Borders::ALL);
+            if let Some(quest) = &app.active_quest {
+                title_block = title_block.title(Span::styled(
+                    format!(
+                        " ⚔ Quest '{}': only {} ",
+                        quest.name,
+                        quest.scope.join(", ")
+                    ),
+                    Style::default()
+                        .fg(Color::Rgb(255, 215, 0))
+                        .add_modifier(Modifier::BOLD),
+                ));
+            }
             let title_widget = Paragraph::new(title)
                 .block(
-                    Block::default()
-                        .borders(Borders::ALL)
+                    title_block
                         .border_style(Style::default().fg(if app.animations_enabled {
                             get_gradient_color(0.0, app.wave_offset * 2.0, EARTH_COLORS)
                         } else {
@@ -2001,11 +4199,20 @@ fn run_app<B: ratatui::backend::Backend>(
         })?;
 
         // Check for file system events (non-blocking)
-        if fs_events.try_recv().is_ok() {
-            // Set flag to refresh, but debounce to avoid too many updates
-            if app.last_refresh.elapsed() > debounce_duration {
+        match fs_events.try_recv() {
+            Ok(FsEvent::Head) => {
+                // A checkout can change both the branch's profile and the working tree
+                app.follow_branch();
+                app.refresh_git_status();
                 app.needs_refresh = true;
             }
+            Ok(FsEvent::Tree) => {
+                // Set flag to refresh, but debounce to avoid too many updates
+                if app.last_refresh.elapsed() > debounce_duration {
+                    app.needs_refresh = true;
+                }
+            }
+            Err(_) => {}
         }
 
         // Refresh tree if needed
@@ -2029,10 +4236,32 @@ fn run_app<B: ratatui::backend::Backend>(
 
         if crossterm::event::poll(timeout)? {
             if let Event::Key(key) = event::read()? {
+                // An open prompt takes every key, so typing can't quit or switch tabs
+                if app.profile_input_mode {
+                    app.handle_profile_key(key.code);
+                    continue;
+                }
+                if app.commit_dialog.is_some() {
+                    app.handle_commit_key(key);
+                    continue;
+                }
+                if app.git_pending_discard.is_some() {
+                    app.handle_discard_key(key.code);
+                    continue;
+                }
+                if app.git_pending_locked_stage.is_some() {
+                    app.handle_locked_stage_key(key.code);
+                    continue;
+                }
+                if app.git_review_input.is_some() {
+                    app.handle_review_base_key(key.code);
+                    continue;
+                }
+
                 // Global keys
                 match key.code {
-                    KeyCode::Char('q') => break,
-                    KeyCode::Char('?') => app.show_help = !app.show_help,
+                    code if app.is_key("quit", code) => break,
+                    code if app.is_key("help", code) => app.show_help = !app.show_help,
                     KeyCode::Tab => {
                         app.active_tab = match app.active_tab {
                             TabIndex::FileGuardian => {
@@ -2077,116 +4306,150 @@ fn run_app<B: ratatui::backend::Backend>(
                             TabIndex::FileGuardian => match key.code {
                                 KeyCode::Up => app.move_up(),
                                 KeyCode::Down => app.move_down(),
-                                KeyCode::Char(' ') => app.toggle_selected(),
-                                KeyCode::Enter => app.toggle_expand_selected(),
-                                KeyCode::Char('c') => app.toggle_create_in_locked_selected(),
-                                KeyCode::Char('a') => {
+                                code if app.is_key("toggle_lock", code) => app.toggle_selected(),
+                                code if app.is_key("toggle_expand", code) => {
+                                    app.toggle_expand_selected()
+                                }
+                                code if app.is_key("toggle_allow_create", code) => {
+                                    app.toggle_create_in_locked_selected()
+                                }
+                                code if app.is_key("toggle_animations", code) => {
                                     app.animations_enabled = !app.animations_enabled
                                 }
-                                KeyCode::Char('r') => app.needs_refresh = true,
-                                KeyCode::Char('h') => {
+                                code if app.is_key("refresh", code) => app.needs_refresh = true,
+                                code if app.is_key("toggle_hidden", code) => {
                                     app.show_hidden = !app.show_hidden;
                                     app.update_items();
                                 }
+                                code if app.is_key("quest_mark", code) => {
+                                    app.toggle_quest_mark_selected()
+                                }
+                                code if app.is_key("quest", code) => app.toggle_quest(),
                                 _ => {}
                             },
-                            TabIndex::GitStage => {
+                            TabIndex::GitStage => match key.code {
+                                KeyCode::Up if app.git_line_mode => app.move_git_line_cursor(false),
+                                KeyCode::Down if app.git_line_mode => {
+                                    app.move_git_line_cursor(true)
+                                }
+                                KeyCode::Char(' ') if app.git_line_mode => {
+                                    app.toggle_git_line_selected();
+                                }
+                                KeyCode::Esc if app.git_line_mode => app.leave_git_line_mode(),
+                                code if app.git_line_mode && app.is_key("git_line_mode", code) => {
+                                    app.leave_git_line_mode();
+                                }
+                                code if app.git_pane == GitPane::DiffView
+                                    && app.is_key("git_line_mode", code) =>
+                                {
+                                    app.enter_git_line_mode();
+                                }
+                                KeyCode::Left => {
+                                    app.leave_git_line_mode();
+                                    app.git_pane = GitPane::FileList;
+                                }
+                                KeyCode::Right if !app.git_files.is_empty() => {
+                                    app.git_pane = GitPane::DiffView;
+                                }
+                                KeyCode::Up => match app.git_pane {
+                                    GitPane::FileList => app.move_git_file_up(),
+                                    GitPane::DiffView => app.scroll_git_diff_up(),
+                                },
+                                KeyCode::Down => match app.git_pane {
+                                    GitPane::FileList => app.move_git_file_down(),
+                                    GitPane::DiffView => app.scroll_git_diff_down(),
+                                },
+                                KeyCode::Char(' ') if app.git_pane == GitPane::FileList => {
+                                    app.toggle_git_file_stage();
+                                }
+                                KeyCode::Char('n') if app.git_pane == GitPane::DiffView => {
+                                    app.move_git_hunk_down();
+                                }
+                                KeyCode::Char('p') if app.git_pane == GitPane::DiffView => {
+                                    app.move_git_hunk_up();
+                                }
+                                code if app.git_pane == GitPane::DiffView
+                                    && !app.git_diff_hunks.is_empty()
+                                    && app.is_key("git_stage_hunk", code) =>
+                                {
+                                    app.toggle_selected_hunk_stage(true);
+                                }
+                                code if app.git_pane == GitPane::DiffView
+                                    && !app.git_diff_hunks.is_empty()
+                                    && app.is_key("git_unstage_hunk", code) =>
+                                {
+                                    app.toggle_selected_hunk_stage(false);
+                                }
+                                code if app.is_key("git_commit", code) => app.open_commit_dialog(),
+                                code if app.is_key("git_discard", code) => app.start_discard(),
+                                code if !app.git_files.is_empty()
+                                    && app.is_key("git_diff_target", code) =>
+                                {
+                                    app.cycle_git_diff_target();
+                                }
+                                code if app.is_key("git_side_by_side", code) => {
+                                    app.toggle_git_side_by_side()
+                                }
+                                code if app.is_key("git_review", code) => app.toggle_review(),
+                                code if app.is_key("git_revert_locked", code) => {
+                                    app.start_locked_revert()
+                                }
+                                code if app.is_key("git_accept_locked", code) => {
+                                    app.unlock_and_accept()
+                                }
+                                code if app.is_key("git_review_base", code) => {
+                                    app.start_review_base_input()
+                                }
+                                code if !app.git_files.is_empty()
+                                    && app.is_key("git_ignore_whitespace", code) =>
+                                {
+                                    app.toggle_git_ignore_whitespace();
+                                }
+                                code if app.is_key("refresh", code) => {
+                                    app.refresh_git_status();
+                                    if !app.git_files.is_empty() {
+                                        app.load_git_diff();
+                                    }
+                                }
+                                _ => {}
+                            },
+                            TabIndex::Profiles => {
+                                // A reported outcome lasts until the next key
+                                app.profile_message = None;
                                 match key.code {
-                                    KeyCode::Left => app.git_pane = GitPane::FileList,
-                                    KeyCode::Right => {
-                                        if !app.git_files.is_empty() {
-                                            app.git_pane = GitPane::DiffView;
-                                        }
+                                    KeyCode::Up => app.move_profile_up(),
+                                    KeyCode::Down => app.move_profile_down(),
+                                    KeyCode::Enter => {
+                                        log_debug!("UI: Enter key pressed in profiles tab");
+                                        app.load_selected_profile();
+                                    }
+                                    KeyCode::Char('s') => {
+                                        app.start_profile_action(ProfileAction::Save);
                                     }
-                                    KeyCode::Up => match app.git_pane {
-                                        GitPane::FileList => app.move_git_file_up(),
-                                        GitPane::DiffView => app.scroll_git_diff_up(),
-                                    },
-                                    KeyCode::Down => match app.git_pane {
-                                        GitPane::FileList => app.move_git_file_down(),
-                                        GitPane::DiffView => app.scroll_git_diff_down(),
-                                    },
-                                    KeyCode::Char(' ') => {
-                                        if app.git_pane == GitPane::FileList {
-                                            app.toggle_git_file_stage();
-                                        }
+                                    KeyCode::Char('o') => {
+                                        app.start_profile_action(ProfileAction::Overwrite);
                                     }
                                     KeyCode::Char('n') => {
-                                        if app.git_pane == GitPane::DiffView {
-                                            app.move_git_hunk_down();
-                                        }
+                                        app.start_profile_action(ProfileAction::Rename);
                                     }
-                                    KeyCode::Char('p') => {
-                                        if app.git_pane == GitPane::DiffView {
-                                            app.move_git_hunk_up();
-                                        }
+                                    KeyCode::Char('c') => {
+                                        app.start_profile_action(ProfileAction::Duplicate);
                                     }
-                                    KeyCode::Char('s') => {
-                                        // Stage current hunk (not implemented yet)
-                                        if app.git_pane == GitPane::DiffView
-                                            && !app.git_diff_hunks.is_empty()
-                                        {
-                                            // TODO: Implement hunk staging
-                                        }
+                                    KeyCode::Char('e') => {
+                                        app.start_profile_action(ProfileAction::Describe);
+                                    }
+                                    KeyCode::Char('p') => {
+                                        app.push_selected_profile();
                                     }
-                                    KeyCode::Char('u') => {
-                                        // Unstage current hunk (not implemented yet)
-                                        if app.git_pane == GitPane::DiffView
-                                            && !app.git_diff_hunks.is_empty()
-                                        {
-                                            // TODO: Implement hunk unstaging
-                                        }
+                                    KeyCode::Char('d') => {
+                                        app.delete_selected_profile();
                                     }
                                     KeyCode::Char('r') => {
-                                        app.refresh_git_status();
-                                        if !app.git_files.is_empty() {
-                                            app.load_git_diff();
-                                        }
+                                        app.load_profiles();
                                     }
                                     _ => {}
                                 }
                             }
-                            TabIndex::Profiles => {
-                                if app.profile_input_mode {
-                                    match key.code {
-                                        KeyCode::Enter => {
-                                            app.handle_profile_input();
-                                        }
-                                        KeyCode::Esc => {
-                                            app.profile_input_mode = false;
-                                            app.profile_input_buffer.clear();
-                                        }
-                                        KeyCode::Backspace => {
-                                            app.profile_input_buffer.pop();
-                                        }
-                                        KeyCode::Char(c) => {
-                                            app.profile_input_buffer.push(c);
-                                        }
-                                        _ => {}
-                                    }
-                                } else {
-                                    match key.code {
-                                        KeyCode::Up => app.move_profile_up(),
-                                        KeyCode::Down => app.move_profile_down(),
-                                        KeyCode::Enter => {
-                                            log_debug!("UI: Enter key pressed in profiles tab");
-                                            app.load_selected_profile();
-                                        }
-                                        KeyCode::Char('s') => {
-                                            app.profile_action = ProfileAction::Save;
-                                            app.profile_input_mode = true;
-                                            app.profile_input_buffer.clear();
-                                        }
-                                        KeyCode::Char('d') => {
-                                            app.delete_selected_profile();
-                                        }
-                                        KeyCode::Char('r') => {
-                                            app.load_profiles();
-                                        }
-                                        _ => {}
-                                    }
-                                }
-                            }
                         }
                     }
                 }
@@ -2245,23 +4508,34 @@ fn render_help_overlay(f: &mut ratatui::Frame, app: &App, area: Rect) {
             Line::from("Navigation:"),
             Line::from("  ↑↓        Navigate files"),
             Line::from("  Tab       Switch to Git Stage"),
-            Line::from("  Enter     Expand/collapse directories"),
+            help_line(app, "toggle_expand", "Expand/collapse directories"),
             Line::from(""),
             Line::from("Actions:"),
-            Line::from("  Space     Lock/unlock file or directory"),
-            Line::from("  c         Toggle 'allow create' in locked dirs"),
-            Line::from("  h         Show/hide hidden files"),
-            Line::from("  r         Refresh file tree"),
-            Line::from("  a         Toggle animations"),
+            help_line(app, "toggle_lock", "Lock/unlock file or directory"),
+            help_line(
+                app,
+                "toggle_allow_create",
+                "Toggle 'allow create' in locked dirs",
+            ),
+            help_line(app, "toggle_hidden", "Show/hide hidden files"),
+            help_line(app, "quest_mark", "Mark/unmark a path for the next quest"),
+            help_line(
+                app,
+                "quest",
+                "Start a quest allowing only the marked paths, or end it",
+            ),
+            help_line(app, "refresh", "Refresh file tree"),
+            help_line(app, "toggle_animations", "Toggle animations"),
             Line::from(""),
             Line::from("Visual Indicators:"),
             Line::from("  🔒        Locked file/directory"),
             Line::from("  🔒 ➕      Locked dir with create allowed"),
+            Line::from("  🎯        Marked for the next quest"),
             Line::from("  ▶▼        Collapsed/expanded directory"),
             Line::from(""),
             Line::from("Global:"),
-            Line::from("  ?         Toggle this help"),
-            Line::from("  q         Quit"),
+            help_line(app, "help", "Toggle this help"),
+            help_line(app, "quit", "Quit"),
         ],
         TabIndex::GitStage => vec![
             Line::from(Span::styled(
@@ -2278,12 +4552,71 @@ fn render_help_overlay(f: &mut ratatui::Frame, app: &App, area: Rect) {
             Line::from(""),
             Line::from("File List Actions:"),
             Line::from("  Space     Stage/unstage file"),
-            Line::from("  r         Refresh Git status"),
+            help_line(app, "refresh", "Refresh Git status"),
+            help_line(app, "git_commit", "Commit what is staged"),
+            help_line(
+                app,
+                "git_discard",
+                "Discard the file's changes (asks first)",
+            ),
             Line::from(""),
             Line::from("Diff View Actions:"),
             Line::from("  n/p       Next/previous hunk"),
-            Line::from("  s         Stage hunk (TODO)"),
-            Line::from("  u         Unstage hunk (TODO)"),
+            help_line(
+                app,
+                "git_diff_target",
+                "Diff staged / unstaged / both together",
+            ),
+            help_line(
+                app,
+                "git_side_by_side",
+                "Side-by-side diff with word highlights",
+            ),
+            help_line(app, "git_ignore_whitespace", "Ignore whitespace changes"),
+            help_line(app, "git_stage_hunk", "Stage selected hunk"),
+            help_line(app, "git_unstage_hunk", "Unstage selected hunk"),
+            help_line(
+                app,
+                "git_discard",
+                "Discard the hunk, or picked lines (asks first)",
+            ),
+            help_line(app, "git_line_mode", "Pick single lines of the hunk"),
+            Line::from(""),
+            Line::from("Line Mode:"),
+            Line::from("  ↑↓        Move between changed lines"),
+            Line::from("  Space     Pick/unpick the line"),
+            Line::from(format!(
+                "  {:<10}Stage/unstage picked lines (or the current one)",
+                format!(
+                    "{}/{}",
+                    app.settings.key_for("git_stage_hunk"),
+                    app.settings.key_for("git_unstage_hunk")
+                )
+            )),
+            Line::from(format!(
+                "  {:<10}Back to whole hunks",
+                format!("{}/Esc", app.settings.key_for("git_line_mode"))
+            )),
+            Line::from(""),
+            Line::from("Review Mode:"),
+            help_line(
+                app,
+                "git_review",
+                "Review changes since the session checkpoint, or leave",
+            ),
+            help_line(
+                app,
+                "git_review_base",
+                "Review against a branch, commit or main...",
+            ),
+            Line::from(""),
+            Line::from("Locked Files (🔒):"),
+            help_line(
+                app,
+                "git_revert_locked",
+                "Revert the file to HEAD (asks first)",
+            ),
+            help_line(app, "git_accept_locked", "Unlock the file and stage it"),
             Line::from(""),
             Line::from("File Status Indicators:"),
             Line::from("  M         Modified file"),
@@ -2294,8 +4627,8 @@ fn render_help_overlay(f: &mut ratatui::Frame, app: &App, area: Rect) {
             Line::from("  ●○        Staged/unstaged indicator"),
             Line::from(""),
             Line::from("Global:"),
-            Line::from("  ?         Toggle this help"),
-            Line::from("  q         Quit"),
+            help_line(app, "help", "Toggle this help"),
+            help_line(app, "quit", "Quit"),
         ],
         TabIndex::Profiles => vec![
             Line::from(Span::styled(
@@ -2311,21 +4644,28 @@ fn render_help_overlay(f: &mut ratatui::Frame, app: &App, area: Rect) {
             Line::from(""),
             Line::from("Profile Actions:"),
             Line::from("  Enter     Load selected profile"),
+            Line::from("  p         Layer selected profile on top of the active ones"),
             Line::from("  s         Save current patterns as new profile"),
+            Line::from("  o         Overwrite selected profile with current patterns"),
+            Line::from("  n         Rename selected profile"),
+            Line::from("  c         Duplicate selected profile"),
+            Line::from("  e         Edit selected profile's description"),
             Line::from("  d         Delete selected profile"),
             Line::from("  r         Refresh profile list"),
             Line::from(""),
             Line::from("Profile Input Mode:"),
-            Line::from("  Enter     Confirm profile name"),
+            Line::from("  Enter     Confirm"),
             Line::from("  Esc       Cancel operation"),
-            Line::from("  Text      Type profile name"),
+            Line::from("  ←→ Home End  Move the cursor"),
+            Line::from("  Bksp Del  Delete before/under the cursor"),
             Line::from(""),
             Line::from("Visual Indicators:"),
             Line::from("  ● Green   Active profile"),
+            Line::from("  (unsaved changes)  Locks differ from the active profile"),
             Line::from(""),
             Line::from("Global:"),
-            Line::from("  ?         Toggle this help"),
-            Line::from("  q         Quit"),
+            help_line(app, "help", "Toggle this help"),
+            help_line(app, "quit", "Quit"),
         ],
     };
 
@@ -2348,6 +4688,13 @@ fn render_help_overlay(f: &mut ratatui::Frame, app: &App, area: Rect) {
     f.render_widget(help_widget, popup_area);
 }
 
+fn help_line(app: &App, action: &str, description: &str) -> Line<'static> {
+    Line::from(format!(
+        "  {:<10}{description}",
+        app.settings.key_for(action)
+    ))
+}
+
 // Helper function to create a centered rectangle
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
@@ -0,0 +1,66 @@
+//! Fixtures shared by the integration tests.
+#![allow(dead_code)]
+
+use assert_cmd::Command;
+use git2::{Commit, IndexAddOption, Repository, Signature};
+use icaros::state::LockProfile;
+use std::path::Path;
+
+/// The icaros binary, run in `root` without the user's own config or agent.
+pub fn icaros(root: &Path) -> Command {
+    let mut cmd = Command::cargo_bin("icaros").unwrap();
+    cmd.current_dir(root)
+        .env("ICAROS_CONFIG", root.join("no-user-config.yaml"))
+        .env_remove("ICAROS_AGENT");
+    cmd
+}
+
+/// A profile with only locked and unlocked patterns.
+pub fn profile(locked: &[&str], unlocked: &[&str]) -> LockProfile {
+    LockProfile {
+        locked_patterns: locked.iter().map(|p| p.to_string()).collect(),
+        unlocked_patterns: unlocked.iter().map(|p| p.to_string()).collect(),
+        ..LockProfile::default()
+    }
+}
+
+/// Commit everything in the working tree, deletions included, on top of HEAD.
+pub fn commit_all(repo: &Repository, message: &str) {
+    let mut index = repo.index().unwrap();
+    index
+        .add_all(["*"].iter(), IndexAddOption::DEFAULT, None)
+        .unwrap();
+    index.update_all(["*"].iter(), None).unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = Signature::now("Test", "test@example.com").unwrap();
+    let parents: Vec<Commit> = repo
+        .head()
+        .ok()
+        .and_then(|head| head.peel_to_commit().ok())
+        .into_iter()
+        .collect();
+    let parents: Vec<&Commit> = parents.iter().collect();
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &parents,
+    )
+    .unwrap();
+}
+
+/// Twenty numbered lines, with the `changed` ones marked as changed.
+pub fn numbered_lines(changed: &[usize]) -> String {
+    (1..=20)
+        .map(|n| {
+            if changed.contains(&n) {
+                format!("line {n} changed\n")
+            } else {
+                format!("line {n}\n")
+            }
+        })
+        .collect()
+}
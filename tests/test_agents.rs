@@ -0,0 +1,237 @@
+mod common;
+
+use assert_cmd::Command;
+use common::{commit_all, icaros, profile};
+use git2::Repository;
+use icaros::git::GitManager;
+use icaros::merge::merge_states;
+use icaros::state::AppState;
+use icaros::{file_tree, ui};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn agent_state(root: &Path) -> AppState {
+    let mut state = AppState::new(root.to_path_buf());
+    state
+        .profiles
+        .insert("frontend".to_string(), profile(&["**"], &["web/**"]));
+    state
+        .profiles
+        .insert("backend".to_string(), profile(&["**"], &["api/**"]));
+    assert!(state.bind_agent("ui-bot", "frontend"));
+    assert!(state.bind_agent("api-bot", "backend"));
+    state
+}
+
+#[test]
+fn test_agents_get_their_own_policy() {
+    let mut state = agent_state(Path::new("/test/root"));
+    assert!(!state.bind_agent("docs-bot", "missing"));
+
+    let ui = state.agent_policy("ui-bot").unwrap();
+    assert!(!ui.is_locked(Path::new("web/app.ts")));
+    assert!(ui.is_locked(Path::new("api/server.rs")));
+    let api = state.agent_policy("api-bot").unwrap();
+    assert!(api.is_locked(Path::new("web/app.ts")));
+    // The shared active state is untouched
+    assert!(!state.is_locked(Path::new("api/server.rs")));
+
+    assert_eq!(
+        state.agent_policy("docs-bot").unwrap_err().to_string(),
+        "Agent 'docs-bot' has no profile (known agents: api-bot, ui-bot)"
+    );
+
+    assert!(!state.delete_profile("frontend"));
+    assert!(state.rename_profile("frontend", "web"));
+    assert_eq!(state.agents["ui-bot"], "web");
+    assert_eq!(state.agents_using("web"), vec!["ui-bot"]);
+}
+
+#[test]
+fn test_merge_keeps_agents_from_both_sides() {
+    let base = agent_state(Path::new("/test/root"));
+    let mut ours = base.clone();
+    ours.unbind_agent("api-bot");
+    let mut theirs = base.clone();
+    theirs.bind_agent("review-bot", "frontend");
+
+    let merged = merge_states(&base, &ours, &theirs).state;
+
+    let agents: Vec<(&str, &str)> = merged
+        .agents
+        .iter()
+        .map(|(agent, profile)| (agent.as_str(), profile.as_str()))
+        .collect();
+    assert_eq!(
+        agents,
+        vec![("review-bot", "frontend"), ("ui-bot", "frontend")]
+    );
+}
+
+#[test]
+fn test_check_applies_the_selected_agent() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::create_dir_all(root.join("web")).unwrap();
+    fs::create_dir_all(root.join("api")).unwrap();
+    fs::write(root.join("web/app.ts"), "").unwrap();
+    fs::write(root.join("api/server.rs"), "").unwrap();
+    AppState::new(root.to_path_buf())
+        .save_to_file(&root.join(".icaros"))
+        .unwrap();
+
+    let icaros = || {
+        let mut cmd = Command::cargo_bin("icaros").unwrap();
+        cmd.current_dir(root)
+            .env("ICAROS_CONFIG", root.join("no-user-config.yaml"))
+            .env_remove("ICAROS_AGENT");
+        cmd
+    };
+
+    icaros().args(["lock", "."]).assert().success();
+    icaros().args(["unlock", "web"]).assert().success();
+    icaros()
+        .args(["profile", "save", "frontend"])
+        .assert()
+        .success();
+    icaros()
+        .args(["agent", "bind", "ui-bot", "frontend"])
+        .assert()
+        .success();
+    icaros().args(["unlock", "."]).assert().success();
+
+    // Without an agent the active state applies; with one, its profile does
+    icaros().args(["check", "api/server.rs"]).assert().success();
+    icaros()
+        .args(["check", "--agent", "ui-bot", "api/server.rs"])
+        .assert()
+        .code(1);
+    let output = icaros()
+        .env("ICAROS_AGENT", "ui-bot")
+        .args(["check", "web/app.ts", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(result["agent"], "ui-bot");
+    assert_eq!(result["active_profile"], "frontend");
+
+    icaros()
+        .args(["check", "--agent", "nobody", "web/app.ts"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Agent 'nobody' has no profile"));
+
+    icaros()
+        .args(["agent", "list"])
+        .assert()
+        .success()
+        .stdout("ui-bot  frontend\n");
+}
+
+#[test]
+fn test_agent_variable_without_agents_uses_active_profile() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::write(root.join("README.md"), "").unwrap();
+    let mut state = AppState::new(root.to_path_buf());
+    state.locked_patterns = vec!["README.md".to_string()];
+    state.save_to_file(&root.join(".icaros")).unwrap();
+
+    // The variable may be set for every repository; this one has no agents
+    icaros(root)
+        .env("ICAROS_AGENT", "ui-bot")
+        .args(["check", "README.md"])
+        .assert()
+        .code(1)
+        .stderr(predicates::str::contains(
+            "Warning: ICAROS_AGENT is 'ui-bot', but no agents are configured",
+        ));
+    // Naming the agent outright still has to match one
+    icaros(root)
+        .args(["check", "--agent", "ui-bot", "README.md"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("no agents are configured"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_pre_commit_hook_checks_staged_paths_for_agent() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let repo = Repository::init(root).unwrap();
+    fs::create_dir_all(root.join("web")).unwrap();
+    fs::create_dir_all(root.join("api")).unwrap();
+    fs::write(root.join("web/app.ts"), "").unwrap();
+    fs::write(root.join("api/server.rs"), "").unwrap();
+    fs::write(root.join(".gitignore"), ".icaros\n").unwrap();
+    commit_all(&repo, "Initial commit");
+    agent_state(root)
+        .save_to_file(&root.join(".icaros"))
+        .unwrap();
+
+    icaros(root)
+        .args(["hook", "install", "--pre-commit", "--agent", "ui-bot"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            "runs: icaros check --agent ui-bot on the staged paths",
+        ));
+    let hook = root.join(".git/hooks/pre-commit");
+    // Installing twice doesn't add the check twice
+    icaros(root)
+        .args(["hook", "install", "--pre-commit", "--agent", "ui-bot"])
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(&hook)
+            .unwrap()
+            .matches("xargs -0 icaros check --agent ui-bot")
+            .count(),
+        1
+    );
+
+    // The hook finds icaros on the PATH, as it would for a commit
+    let bin_dir = assert_cmd::cargo::cargo_bin("icaros")
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    let path = format!("{}:{}", bin_dir.display(), std::env::var("PATH").unwrap());
+    let run_hook = || {
+        std::process::Command::new(&hook)
+            .current_dir(root)
+            .env("PATH", &path)
+            .env("ICAROS_CONFIG", root.join("no-user-config.yaml"))
+            .env_remove("ICAROS_AGENT")
+            .output()
+            .unwrap()
+    };
+    let git = GitManager::new(root).unwrap();
+    fs::write(root.join("web/app.ts"), "export {}\n").unwrap();
+    git.stage_file(Path::new("web/app.ts")).unwrap();
+    assert!(run_hook().status.success());
+
+    fs::write(root.join("api/server.rs"), "fn main() {}\n").unwrap();
+    git.stage_file(Path::new("api/server.rs")).unwrap();
+    let output = run_hook();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("locked         api/server.rs"));
+}
+
+#[test]
+fn test_profiles_tab_lists_agents() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    let mut state = agent_state(&root);
+    state.bind_agent("review-bot", "frontend");
+    state.save_to_file(&root.join(".icaros")).unwrap();
+
+    let tree = file_tree::build_tree(&root, &[], false).unwrap();
+    let mut app = ui::App::new(tree, root.join(".icaros"), root.clone());
+    app.load_profiles();
+
+    assert_eq!(app.profile_agents["frontend"], vec!["review-bot", "ui-bot"]);
+    assert_eq!(app.profile_agents["backend"], vec!["api-bot"]);
+}
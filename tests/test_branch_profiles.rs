@@ -0,0 +1,129 @@
+mod common;
+
+use assert_cmd::Command;
+use common::profile;
+use icaros::git::GitManager;
+use icaros::state::{AppState, BranchRule};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+fn rule(branch: &str, profile: &str) -> BranchRule {
+    BranchRule {
+        branch: branch.to_string(),
+        profile: profile.to_string(),
+    }
+}
+
+fn scoped_state(root: &Path) -> AppState {
+    let mut state = AppState::new(root.to_path_buf());
+    state
+        .profiles
+        .insert("ui".to_string(), profile(&["**"], &["web/**"]));
+    state
+        .profiles
+        .insert("db".to_string(), profile(&["**"], &["migrations/**"]));
+    state.branch_profiles = vec![rule("feat/ui-*", "ui"), rule("fix/db-*", "db")];
+    state
+}
+
+/// A repository with no commits whose HEAD points at `branch`.
+fn init_repo(root: &Path, branch: &str) {
+    let repo = git2::Repository::init(root).unwrap();
+    repo.set_head(&format!("refs/heads/{branch}")).unwrap();
+}
+
+#[test]
+fn test_branch_rules_select_profile() {
+    let mut state = scoped_state(Path::new("/test/root"));
+
+    assert_eq!(state.profile_for_branch("feat/ui-login"), Some("ui"));
+    assert_eq!(state.profile_for_branch("fix/db-index"), Some("db"));
+    assert_eq!(state.profile_for_branch("main"), None);
+
+    assert_eq!(state.follow_branch("feat/ui-login"), Some("ui".to_string()));
+    assert_eq!(state.unlocked_patterns, vec!["web/**"]);
+    // Already active: manual changes since the switch are kept
+    state.locked_patterns.push("web/legacy/**".to_string());
+    assert_eq!(state.follow_branch("feat/ui-other"), None);
+    assert!(state.locked_patterns.contains(&"web/legacy/**".to_string()));
+}
+
+#[test]
+fn test_is_locked_uses_most_specific_pattern() {
+    let mut state = AppState::new(PathBuf::from("/test/root"));
+    state.locked_patterns = vec!["**".to_string(), "src/api/**".to_string()];
+    state.unlocked_patterns = vec!["src/**".to_string(), "README.md".to_string()];
+    state.allow_create_patterns = vec!["docs".to_string()];
+
+    assert!(state.is_locked(Path::new("Cargo.toml")));
+    assert!(!state.is_locked(Path::new("README.md")));
+    assert!(!state.is_locked(Path::new("src/main.rs")));
+    assert!(state.is_locked(Path::new("src/api/mod.rs")));
+
+    assert!(state.can_create(Path::new("docs/new.md")));
+    assert!(state.can_create(Path::new("src/new.rs")));
+    assert!(!state.can_create(Path::new("new.rs")));
+    assert!(!state.can_create(Path::new("docs")));
+}
+
+#[test]
+fn test_current_branch_and_post_checkout_hook() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    init_repo(root, "feat/ui-login");
+
+    let git = GitManager::new(root).unwrap();
+    assert_eq!(git.current_branch().as_deref(), Some("feat/ui-login"));
+
+    let hook = git
+        .install_post_checkout_hook("icaros profile sync")
+        .unwrap();
+    git.install_post_checkout_hook("icaros profile sync")
+        .unwrap();
+
+    let content = fs::read_to_string(&hook).unwrap();
+    assert!(content.starts_with("#!/bin/sh\n"));
+    assert_eq!(content.matches("icaros profile sync").count(), 1);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_ne!(fs::metadata(&hook).unwrap().permissions().mode() & 0o111, 0);
+    }
+}
+
+#[test]
+fn test_check_follows_branch_and_reports_locks() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::create_dir_all(root.join("web")).unwrap();
+    fs::create_dir_all(root.join("migrations")).unwrap();
+    fs::write(root.join("web/app.ts"), "").unwrap();
+    fs::write(root.join("migrations/001.sql"), "").unwrap();
+    init_repo(root, "fix/db-index");
+    scoped_state(root)
+        .save_to_file(&root.join(".icaros"))
+        .unwrap();
+
+    let mut cmd = Command::cargo_bin("icaros").unwrap();
+    cmd.current_dir(root)
+        .env("ICAROS_CONFIG", root.join("no-user-config.yaml"))
+        .args(["check", "migrations/002.sql", "--json"]);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(result["active_profile"], "db");
+    assert_eq!(result["paths"][0]["status"], "unlocked");
+
+    // Checking doesn't switch the saved profile; `profile sync` does that
+    let state = AppState::load_from_file(&root.join(".icaros")).unwrap();
+    assert_eq!(state.active_profile, None);
+
+    let mut cmd = Command::cargo_bin("icaros").unwrap();
+    cmd.current_dir(root)
+        .env("ICAROS_CONFIG", root.join("no-user-config.yaml"))
+        .args(["check", "web/app.ts", "migrations/001.sql"])
+        .assert()
+        .code(1)
+        .stdout(predicates::str::contains("locked         web/app.ts"));
+}
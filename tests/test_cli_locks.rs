@@ -0,0 +1,176 @@
+mod common;
+
+use common::icaros;
+use icaros::{file_tree, state::AppState, ui};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn create_project(root: &Path) {
+    fs::create_dir_all(root.join("src/api")).unwrap();
+    fs::create_dir_all(root.join("tests")).unwrap();
+    fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+    fs::write(root.join("src/lib.rs"), "").unwrap();
+    fs::write(root.join("src/api/mod.rs"), "").unwrap();
+    fs::write(root.join("tests/cli.rs"), "").unwrap();
+    fs::write(root.join("README.md"), "# Test").unwrap();
+}
+
+#[test]
+fn test_cli_matches_file_guardian_toggles() {
+    let temp_dir = TempDir::new().unwrap();
+    let tui_root = temp_dir.path().canonicalize().unwrap().join("tui");
+    let cli_root = temp_dir.path().canonicalize().unwrap().join("cli");
+    create_project(&tui_root);
+    create_project(&cli_root);
+
+    // Toggle in the File Guardian: lock everything, unlock src, lock src/api
+    let tree = file_tree::build_tree(&tui_root, &[], false).unwrap();
+    let mut app = ui::App::new(tree, tui_root.join(".icaros"), tui_root.clone());
+    app.toggle_path(&tui_root, true);
+    app.toggle_path(&tui_root.join("src"), true);
+    app.toggle_path(&tui_root.join("src/api"), true);
+    app.set_allow_create(&tui_root.join("src/api"), true);
+    app.write_state().unwrap();
+
+    icaros(&cli_root).args(["lock", "."]).assert().success();
+    icaros(&cli_root).args(["unlock", "src"]).assert().success();
+    icaros(&cli_root)
+        .args(["lock", "src/api"])
+        .assert()
+        .success();
+    icaros(&cli_root)
+        .args(["allow-create", "src/api"])
+        .assert()
+        .success();
+
+    assert_eq!(
+        fs::read_to_string(cli_root.join(".icaros")).unwrap(),
+        fs::read_to_string(tui_root.join(".icaros")).unwrap()
+    );
+}
+
+#[test]
+fn test_locking_directory_prunes_child_entries() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    create_project(root);
+
+    icaros(root)
+        .args(["lock", "src/main.rs", "src/api"])
+        .assert()
+        .success();
+    icaros(root).args(["lock", "src"]).assert().success();
+
+    let state = AppState::load_from_file(&root.join(".icaros")).unwrap();
+    assert_eq!(state.locked_patterns, vec!["src/**"]);
+    assert!(state.unlocked_patterns.is_empty());
+}
+
+#[test]
+fn test_unlock_inside_locked_directory_adds_exception() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    create_project(root);
+
+    icaros(root).args(["lock", "src"]).assert().success();
+    // Targets are relative to the current directory inside the project
+    icaros(root)
+        .current_dir(root.join("src"))
+        .arg(root)
+        .args(["unlock", "api"])
+        .assert()
+        .success();
+
+    let state = AppState::load_from_file(&root.join(".icaros")).unwrap();
+    assert_eq!(state.locked_patterns, vec!["src/**"]);
+    assert_eq!(state.unlocked_patterns, vec!["src/api/**"]);
+}
+
+#[test]
+fn test_glob_targets_and_ls_json() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    create_project(root);
+
+    icaros(root)
+        .args(["lock", "src/*.rs", "--json"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("\"changed\""));
+
+    let output = icaros(root)
+        .args(["ls", "--locked", "--json"])
+        .output()
+        .unwrap();
+    let entries: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let paths: Vec<&str> = entries
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["path"].as_str().unwrap())
+        .collect();
+    assert_eq!(paths, vec!["src/lib.rs", "src/main.rs"]);
+
+    icaros(root)
+        .args(["lock", "docs/*"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("No files match 'docs/*'"));
+}
+
+#[test]
+fn test_allow_create_requires_locked_directory() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    create_project(root);
+
+    icaros(root)
+        .args(["allow-create", "tests"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("tests is not a locked directory"));
+
+    icaros(root).args(["lock", "tests"]).assert().success();
+    icaros(root)
+        .args(["allow-create", "tests"])
+        .assert()
+        .success();
+
+    let output = icaros(root).args(["status", "--json"]).output().unwrap();
+    let status: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(status["locked_patterns"], serde_json::json!(["tests/**"]));
+    assert_eq!(
+        status["allow_create_patterns"],
+        serde_json::json!(["tests"])
+    );
+    assert_eq!(status["locked_files"], 1);
+
+    icaros(root)
+        .args(["allow-create", "--remove", "tests"])
+        .assert()
+        .success();
+    let state = AppState::load_from_file(&root.join(".icaros")).unwrap();
+    assert!(state.allow_create_patterns.is_empty());
+}
+
+#[test]
+fn test_repeated_targets_are_locked_once() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    create_project(root);
+
+    let output = icaros(root)
+        .args(["lock", "src/main.rs", "README.md", "src/*.rs", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let changed: Vec<&str> = result["changed"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|path| path.as_str().unwrap())
+        .collect();
+    assert_eq!(changed, vec!["src/main.rs", "README.md", "src/lib.rs"]);
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use assert_cmd::Command;
+use common::icaros;
+use icaros::state::AppState;
+use std::fs;
+use tempfile::TempDir;
+
+fn json_output(cmd: &mut Command) -> serde_json::Value {
+    let output = cmd.output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+fn setup() -> TempDir {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::create_dir_all(root.join("web")).unwrap();
+    fs::create_dir_all(root.join("api")).unwrap();
+    fs::write(root.join("web/app.ts"), "").unwrap();
+    fs::write(root.join("api/server.rs"), "").unwrap();
+    temp_dir
+}
+
+#[test]
+fn test_profile_save_switch_and_list() {
+    let temp_dir = setup();
+    let root = temp_dir.path();
+
+    icaros(root).args(["lock", "api"]).assert().success();
+    icaros(root)
+        .args(["profile", "save", "frontend-only", "-d", "UI work"])
+        .assert()
+        .success();
+    icaros(root).args(["unlock", "api"]).assert().success();
+    icaros(root).args(["lock", "web"]).assert().success();
+    icaros(root)
+        .args(["profile", "save", "backend-only"])
+        .assert()
+        .success();
+
+    let result = json_output(icaros(root).args(["profile", "switch", "frontend-only", "--json"]));
+    assert_eq!(result["active_profile"], "frontend-only");
+    assert_eq!(result["locked_patterns"], serde_json::json!(["api/**"]));
+
+    let state = AppState::load_from_file(&root.join(".icaros")).unwrap();
+    assert_eq!(state.locked_patterns, vec!["api/**"]);
+    assert_eq!(state.active_profile.as_deref(), Some("frontend-only"));
+
+    let profiles = json_output(icaros(root).args(["profile", "list", "--json"]));
+    assert_eq!(
+        profiles,
+        serde_json::json!([
+            {"name": "backend-only", "description": profiles[0]["description"], "extends": [], "active": false},
+            {"name": "frontend-only", "description": "UI work", "extends": [], "active": true},
+        ])
+    );
+
+    let shown = json_output(icaros(root).args(["profile", "show", "backend-only", "--json"]));
+    assert_eq!(shown["locked_patterns"], serde_json::json!(["web/**"]));
+}
+
+#[test]
+fn test_profile_rename_copy_delete() {
+    let temp_dir = setup();
+    let root = temp_dir.path();
+
+    icaros(root).args(["lock", "web"]).assert().success();
+    icaros(root)
+        .args(["profile", "save", "ui"])
+        .assert()
+        .success();
+
+    icaros(root)
+        .args(["profile", "rename", "ui", "frontend"])
+        .assert()
+        .success();
+    icaros(root)
+        .args(["profile", "copy", "frontend", "frontend-strict"])
+        .assert()
+        .success();
+    icaros(root)
+        .args(["profile", "copy", "frontend", "frontend-strict"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "'frontend-strict' already exists",
+        ));
+
+    let state = AppState::load_from_file(&root.join(".icaros")).unwrap();
+    assert_eq!(state.active_profile.as_deref(), Some("frontend"));
+    assert_eq!(
+        state.get_profile_names(),
+        vec!["frontend", "frontend-strict"]
+    );
+
+    icaros(root)
+        .args(["profile", "delete", "frontend"])
+        .assert()
+        .success();
+    let state = AppState::load_from_file(&root.join(".icaros")).unwrap();
+    assert_eq!(state.active_profile, None);
+    assert_eq!(state.get_profile_names(), vec!["frontend-strict"]);
+}
+
+#[test]
+fn test_switch_to_unknown_profile_fails() {
+    let temp_dir = setup();
+    let root = temp_dir.path();
+
+    icaros(root)
+        .args(["profile", "switch", "nope"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("No state file"));
+
+    icaros(root)
+        .args(["profile", "save", "baseline"])
+        .assert()
+        .success();
+    icaros(root)
+        .args(["profile", "switch", "nope"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "Profile 'nope' not found (available: baseline)",
+        ));
+}
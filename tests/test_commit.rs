@@ -0,0 +1,231 @@
+mod common;
+
+use common::commit_all;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use git2::{Commit, Repository};
+use icaros::config::EnforcementMode;
+use icaros::git::{CommitOptions, GitManager};
+use icaros::state::AppState;
+use icaros::{file_tree, ui};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// A repository with one commit and an edit to `src/main.rs` staged.
+fn staged_repo(root: &Path) -> Repository {
+    let repo = Repository::init(root).unwrap();
+    let mut config = repo.config().unwrap();
+    config.set_str("user.name", "Dev").unwrap();
+    config.set_str("user.email", "dev@example.com").unwrap();
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::write(root.join(".gitignore"), ".icaros\n").unwrap();
+    fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+    commit_all(&repo, "Initial commit");
+
+    fs::write(root.join("src/main.rs"), "fn main() { run() }\n").unwrap();
+    GitManager::new(root)
+        .unwrap()
+        .stage_file(Path::new("src/main.rs"))
+        .unwrap();
+    repo
+}
+
+fn head_commit(repo: &Repository) -> Commit<'_> {
+    repo.head().unwrap().peel_to_commit().unwrap()
+}
+
+fn key(code: KeyCode) -> KeyEvent {
+    KeyEvent::new(code, KeyModifiers::NONE)
+}
+
+fn ctrl(c: char) -> KeyEvent {
+    KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL)
+}
+
+#[test]
+fn test_commit_amend_author_and_sign_off() {
+    let temp_dir = TempDir::new().unwrap();
+    let repo = staged_repo(temp_dir.path());
+    let git = GitManager::new(temp_dir.path()).unwrap();
+
+    git.commit(&CommitOptions {
+        message: "Run on start\n\n# a comment git strips\n".to_string(),
+        sign_off: true,
+        author: Some("Agent <agent@example.com>".to_string()),
+        ..CommitOptions::default()
+    })
+    .unwrap();
+    let commit = head_commit(&repo);
+    assert_eq!(
+        commit.message(),
+        Some("Run on start\n\nSigned-off-by: Dev <dev@example.com>\n")
+    );
+    assert_eq!(commit.author().name(), Some("Agent"));
+    assert_eq!(commit.committer().name(), Some("Dev"));
+    assert!(!git.has_staged_changes().unwrap());
+
+    // Nothing left to commit, but the last commit can still be reworded
+    let error = git
+        .commit(&CommitOptions {
+            message: "Again".to_string(),
+            ..CommitOptions::default()
+        })
+        .unwrap_err();
+    assert_eq!(error.to_string(), "Nothing staged to commit");
+    git.commit(&CommitOptions {
+        message: "Run main on start".to_string(),
+        amend: true,
+        ..CommitOptions::default()
+    })
+    .unwrap();
+    let amended = head_commit(&repo);
+    assert_eq!(amended.message(), Some("Run main on start\n"));
+    assert_eq!(amended.author().name(), Some("Agent"));
+    assert_eq!(amended.parent_count(), 1);
+    assert_eq!(amended.parent(0).unwrap().message(), Some("Initial commit"));
+
+    assert!(git
+        .commit(&CommitOptions {
+            message: "Run".to_string(),
+            amend: true,
+            author: Some("nobody".to_string()),
+            ..CommitOptions::default()
+        })
+        .is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_commit_runs_hooks() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let repo = staged_repo(root);
+    let git = GitManager::new(root).unwrap();
+    let hooks = root.join(".git/hooks");
+    fs::create_dir_all(&hooks).unwrap();
+    let install = |name: &str, script: &str| {
+        let path = hooks.join(name);
+        fs::write(&path, format!("#!/bin/sh\n{script}\n")).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    };
+    let options = |message: &str| CommitOptions {
+        message: message.to_string(),
+        ..CommitOptions::default()
+    };
+
+    install(
+        "commit-msg",
+        "grep -q '^[A-Z]' \"$1\" || { echo 'Start with a capital letter' >&2; exit 1; }\necho 'Reviewed-by: hook' >> \"$1\"",
+    );
+    let error = git.commit(&options("lowercase")).unwrap_err();
+    assert_eq!(
+        format!("{error:#}"),
+        "The commit-msg hook failed\nStart with a capital letter"
+    );
+    assert_eq!(head_commit(&repo).message(), Some("Initial commit"));
+
+    git.commit(&options("Capitalised")).unwrap();
+    assert_eq!(
+        head_commit(&repo).message(),
+        Some("Capitalised\nReviewed-by: hook\n")
+    );
+
+    install("pre-commit", "echo 'lint failed'; exit 1");
+    fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+    git.stage_file(Path::new("src/main.rs")).unwrap();
+    let error = git.commit(&options("Revert")).unwrap_err();
+    assert!(format!("{error:#}").contains("lint failed"));
+}
+
+#[test]
+fn test_commit_refused_during_merge() {
+    let temp_dir = TempDir::new().unwrap();
+    let repo = staged_repo(temp_dir.path());
+    let git = GitManager::new(temp_dir.path()).unwrap();
+    let head = head_commit(&repo).id();
+    // What `git merge` leaves behind when it stops on a conflict
+    fs::write(repo.path().join("MERGE_HEAD"), format!("{head}\n")).unwrap();
+
+    let options = CommitOptions {
+        message: "Merge".to_string(),
+        ..CommitOptions::default()
+    };
+    assert_eq!(
+        git.commit(&options).unwrap_err().to_string(),
+        "A merge is in progress; finish it with git first"
+    );
+    assert_eq!(head_commit(&repo).id(), head);
+
+    fs::remove_file(repo.path().join("MERGE_HEAD")).unwrap();
+    git.commit(&options).unwrap();
+}
+
+#[test]
+fn test_commit_dialog_refuses_locked_paths() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    let repo = staged_repo(&root);
+    let mut state = AppState::new(root.clone());
+    state.locked_patterns = vec!["src/**".to_string()];
+    state.save_to_file(&root.join(".icaros")).unwrap();
+
+    let tree = file_tree::build_tree(&root, &[], false).unwrap();
+    let mut app = ui::App::new(tree, root.join(".icaros"), root.clone());
+    app.open_commit_dialog();
+    let dialog = app.commit_dialog.as_ref().unwrap();
+    assert_eq!(dialog.staged.len(), 1);
+    assert_eq!(dialog.locked, vec![Path::new("src/main.rs")]);
+
+    for c in "Run".chars() {
+        app.handle_commit_key(key(KeyCode::Char(c)));
+    }
+    app.handle_commit_key(key(KeyCode::Enter));
+    app.handle_commit_key(key(KeyCode::Enter));
+    for c in "Details".chars() {
+        app.handle_commit_key(key(KeyCode::Char(c)));
+    }
+    app.handle_commit_key(ctrl('s'));
+    let dialog = app.commit_dialog.as_ref().unwrap();
+    assert_eq!(
+        dialog.error.as_deref(),
+        Some("1 staged path(s) are locked; Ctrl+L to commit anyway")
+    );
+    assert_eq!(head_commit(&repo).message(), Some("Initial commit"));
+
+    app.handle_commit_key(ctrl('l'));
+    app.handle_commit_key(ctrl('s'));
+    assert!(app.commit_dialog.is_none());
+    assert!(app
+        .git_message
+        .as_deref()
+        .unwrap()
+        .starts_with("Committed "));
+    assert_eq!(head_commit(&repo).message(), Some("Run\n\nDetails\n"));
+}
+
+#[test]
+fn test_commit_dialog_warns_about_locked_paths_under_warn_enforcement() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    let repo = staged_repo(&root);
+    let mut state = AppState::new(root.clone());
+    state.locked_patterns = vec!["src/**".to_string()];
+    state.settings.enforcement = Some(EnforcementMode::Warn);
+    state.save_to_file(&root.join(".icaros")).unwrap();
+
+    let tree = file_tree::build_tree(&root, &[], false).unwrap();
+    let mut app = ui::App::new(tree, root.join(".icaros"), root.clone());
+    app.open_commit_dialog();
+    for c in "Run".chars() {
+        app.handle_commit_key(key(KeyCode::Char(c)));
+    }
+    app.handle_commit_key(ctrl('s'));
+
+    assert!(app.commit_dialog.is_none());
+    let message = app.git_message.as_deref().unwrap();
+    assert!(message.starts_with("Committed "));
+    assert!(message.ends_with(": Run (1 staged path(s) are locked)"));
+    assert_eq!(head_commit(&repo).message(), Some("Run\n"));
+}
@@ -0,0 +1,205 @@
+mod common;
+
+use common::icaros;
+use icaros::config::{self, EnforcementMode, Settings, ThemeName, UserConfig};
+use icaros::state::{self, AppState};
+use std::fs;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+fn user_config(yaml: &str) -> UserConfig {
+    serde_yaml::from_str(yaml).unwrap()
+}
+
+#[test]
+fn test_defaults_without_any_config() {
+    let settings = Settings::default();
+
+    assert!(settings.animations);
+    assert_eq!(settings.theme, ThemeName::Desert);
+    assert_eq!(settings.enforcement, EnforcementMode::Block);
+    assert_eq!(settings.key_for("toggle_lock"), "Space");
+    assert_eq!(settings.ignore_patterns, state::default_ignore_patterns());
+    assert_eq!(settings.log_file, PathBuf::from("logs/unified.log"));
+}
+
+#[test]
+fn test_repo_settings_override_user_config() {
+    let user = user_config(
+        r#"
+animations: false
+theme: mono
+enforcement: warn
+keybindings:
+  toggle_lock: l
+  quit: x
+"#,
+    );
+    let mut repo = AppState::new(PathBuf::from("/test/root"));
+    repo.settings.enforcement = Some(EnforcementMode::Block);
+    repo.settings
+        .keybindings
+        .insert("quit".to_string(), "Q".to_string());
+
+    let settings = Settings::resolve(&user, Some(&repo));
+
+    // Unset in the repo, so the user's values apply
+    assert!(!settings.animations);
+    assert_eq!(settings.theme, ThemeName::Mono);
+    assert_eq!(settings.key_for("toggle_lock"), "l");
+    // Set in the repo, which wins
+    assert_eq!(settings.enforcement, EnforcementMode::Block);
+    assert_eq!(settings.key_for("quit"), "Q");
+    // Untouched bindings keep their defaults
+    assert_eq!(settings.key_for("refresh"), "r");
+}
+
+#[test]
+fn test_global_ignore_patterns_extend_repo_list() {
+    let user = user_config("ignore_patterns:\n- .idea/\n- target/\n");
+    let mut repo = AppState::new(PathBuf::from("/test/root"));
+    repo.ignore_patterns = vec!["target/".to_string(), "dist/".to_string()];
+
+    let settings = Settings::resolve(&user, Some(&repo));
+
+    assert_eq!(settings.ignore_patterns, vec!["target/", "dist/", ".idea/"]);
+}
+
+#[test]
+fn test_unknown_keybindings_are_reported_and_ignored() {
+    let user = user_config("keybindings:\n  teleport: t\n  help: H\n");
+
+    let settings = Settings::resolve(&user, None);
+
+    assert_eq!(settings.key_for("help"), "H");
+    assert!(!settings.keybindings.contains_key("teleport"));
+    assert_eq!(Settings::unknown_keybindings(&user, None), vec!["teleport"]);
+}
+
+#[test]
+fn test_duplicate_keybindings_are_reported() {
+    let user = user_config("keybindings:\n  git_discard: x\n  toggle_lock: s\n  help: c\n");
+
+    let settings = Settings::resolve(&user, None);
+
+    // A File Guardian key may repeat a Git Stage one, but not a key every tab reads
+    let duplicate = |key: &str, first: &str, second: &str| {
+        (key.to_string(), first.to_string(), second.to_string())
+    };
+    assert_eq!(
+        settings.duplicate_keybindings,
+        vec![
+            duplicate("c", "git_commit", "help"),
+            duplicate("x", "git_discard", "git_revert_locked"),
+            duplicate("c", "help", "toggle_allow_create"),
+        ]
+    );
+    assert!(Settings::default().duplicate_keybindings.is_empty());
+}
+
+#[test]
+fn test_new_repo_uses_default_profile() {
+    let user = user_config(
+        r#"
+default_profile: careful
+profiles:
+  careful:
+    locked_patterns:
+    - '**'
+    unlocked_patterns:
+    - tests/**
+    allow_create_patterns: []
+    description: Lock everything but tests
+"#,
+    );
+
+    let state = config::new_repo_state(PathBuf::from("/test/root"), &user);
+
+    assert_eq!(state.active_profile.as_deref(), Some("careful"));
+    assert_eq!(state.locked_patterns, vec!["**"]);
+    assert_eq!(state.unlocked_patterns, vec!["tests/**"]);
+}
+
+#[test]
+fn test_repo_settings_round_trip_and_stay_hidden_when_empty() {
+    let temp_dir = TempDir::new().unwrap();
+    let state_file = temp_dir.path().join(".icaros");
+
+    let mut state = AppState::new(temp_dir.path().to_path_buf());
+    state.save_to_file(&state_file).unwrap();
+    assert!(!fs::read_to_string(&state_file)
+        .unwrap()
+        .contains("settings"));
+
+    state.settings.theme = Some(ThemeName::Mono);
+    state.save_to_file(&state_file).unwrap();
+
+    let loaded = AppState::load_from_file(&state_file).unwrap();
+    assert_eq!(loaded.settings.theme, Some(ThemeName::Mono));
+}
+
+#[test]
+fn test_config_path_from_env() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_file = temp_dir.path().join("config.yaml");
+    fs::write(&config_file, "theme: mono\nlog_file: /tmp/icaros.log\n").unwrap();
+
+    std::env::set_var("ICAROS_CONFIG", &config_file);
+    let loaded = UserConfig::load().unwrap();
+    std::env::remove_var("ICAROS_CONFIG");
+
+    assert_eq!(loaded.preferences.theme, Some(ThemeName::Mono));
+    assert_eq!(loaded.log_file, Some(PathBuf::from("/tmp/icaros.log")));
+}
+
+#[test]
+fn test_warn_enforcement_lets_check_pass() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::write(root.join("main.rs"), "").unwrap();
+    let mut state = AppState::new(root.to_path_buf());
+    state.locked_patterns = vec!["main.rs".to_string()];
+    state.save_to_file(&root.join(".icaros")).unwrap();
+
+    icaros(root).args(["check", "main.rs"]).assert().code(1);
+
+    state.settings.enforcement = Some(EnforcementMode::Warn);
+    state.save_to_file(&root.join(".icaros")).unwrap();
+    icaros(root)
+        .args(["check", "main.rs"])
+        .assert()
+        .success()
+        .stdout("locked         main.rs\n")
+        .stderr(predicates::str::contains("1 path(s) are locked"));
+    let output = icaros(root)
+        .args(["check", "main.rs", "--json"])
+        .output()
+        .unwrap();
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(result["enforcement"], "warn");
+}
+
+#[test]
+fn test_broken_user_config_falls_back_to_defaults() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::write(root.join("main.rs"), "").unwrap();
+    let config = root.join("broken.yaml");
+    fs::write(&config, "preferences: {animations: [\n").unwrap();
+
+    icaros(root)
+        .env("ICAROS_CONFIG", &config)
+        .args(["check", "main.rs"])
+        .assert()
+        .success()
+        .stdout("unlocked       main.rs\n")
+        .stderr(predicates::str::contains(
+            "Warning: Failed to parse config file",
+        ));
+    icaros(root)
+        .env("ICAROS_CONFIG", &config)
+        .args(["config", "show"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Failed to parse config file"));
+}
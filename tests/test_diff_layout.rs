@@ -0,0 +1,147 @@
+mod common;
+
+use common::commit_all;
+use git2::Repository;
+use icaros::diff_layout::{side_by_side, word_diff, Segment};
+use icaros::git::{DiffTarget, GitManager};
+use icaros::{file_tree, ui};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// `main.rs` is committed, then reindented throughout with one real change.
+fn reindented_repo(root: &Path) -> GitManager {
+    let repo = Repository::init(root).unwrap();
+    fs::write(root.join(".gitignore"), ".icaros\n").unwrap();
+    fs::write(
+        root.join("main.rs"),
+        "fn main() {\n  let x = 1;\n  println!(\"{x}\");\n}\n",
+    )
+    .unwrap();
+    commit_all(&repo, "Initial commit");
+    fs::write(
+        root.join("main.rs"),
+        "fn main() {\n    let x = 2;\n    println!(\"{x}\");\n}\n",
+    )
+    .unwrap();
+    GitManager::new(root).unwrap()
+}
+
+fn segment(text: &str, changed: bool) -> Segment {
+    Segment {
+        text: text.to_string(),
+        changed,
+    }
+}
+
+#[test]
+fn test_side_by_side_pairs_removed_with_added() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let repo = Repository::init(root).unwrap();
+    fs::write(root.join("notes.txt"), "a\nb\nc\nd\n").unwrap();
+    commit_all(&repo, "Initial commit");
+    fs::write(root.join("notes.txt"), "a\nB\nc\nd\ne\nf\n").unwrap();
+
+    let git = GitManager::new(root).unwrap();
+    let hunks = git
+        .get_diff(Path::new("notes.txt"), DiffTarget::Unstaged)
+        .unwrap();
+    let side = |number: u32, text: &str| Some((number, text.to_string()));
+    let rows: Vec<_> = side_by_side(&hunks[0])
+        .into_iter()
+        .map(|row| (row.old, row.new))
+        .collect();
+    assert_eq!(
+        rows,
+        vec![
+            (side(1, "a"), side(1, "a")),
+            (side(2, "b"), side(2, "B")),
+            (side(3, "c"), side(3, "c")),
+            (side(4, "d"), side(4, "d")),
+            (None, side(5, "e")),
+            (None, side(6, "f")),
+        ]
+    );
+    assert!(side_by_side(&hunks[0])[1].is_modified());
+    assert!(!side_by_side(&hunks[0])[0].is_modified());
+}
+
+#[test]
+fn test_word_diff_marks_changed_words() {
+    let (old, new) = word_diff("let total = count + 1;", "let total = count * 2;");
+    assert_eq!(
+        old,
+        vec![
+            segment("let total = count ", false),
+            segment("+", true),
+            segment(" ", false),
+            segment("1", true),
+            segment(";", false),
+        ]
+    );
+    assert_eq!(
+        new,
+        vec![
+            segment("let total = count ", false),
+            segment("*", true),
+            segment(" ", false),
+            segment("2", true),
+            segment(";", false),
+        ]
+    );
+
+    let (old, new) = word_diff("same", "same");
+    assert_eq!(
+        (old, new),
+        (vec![segment("same", false)], vec![segment("same", false)])
+    );
+}
+
+#[test]
+fn test_ignore_whitespace_hides_reindents() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    let git = reindented_repo(&root);
+
+    let file = git
+        .get_status_files()
+        .unwrap()
+        .into_iter()
+        .find(|file| file.path == Path::new("main.rs"))
+        .unwrap();
+    let changed = |ignore_whitespace: bool| -> Vec<String> {
+        git.diff_file(&file, DiffTarget::Unstaged, ignore_whitespace)
+            .unwrap()
+            .hunks
+            .iter()
+            .flat_map(|hunk| hunk.lines.iter())
+            .filter(|line| line.origin != ' ')
+            .map(|line| format!("{}{}", line.origin, line.content.trim_end()))
+            .collect()
+    };
+    assert_eq!(changed(false).len(), 4);
+    assert_eq!(changed(true), vec!["-  let x = 1;", "+    let x = 2;"]);
+
+    // The UI toggles both modes, and won't stage hunks without their whitespace
+    let tree = file_tree::build_tree(&root, &[], false).unwrap();
+    let mut app = ui::App::new(tree, root.join(".icaros"), root.clone());
+    app.refresh_git_status();
+    app.select_git_file(0);
+    app.toggle_git_side_by_side();
+    assert!(app.git_side_by_side);
+    app.toggle_git_ignore_whitespace();
+    assert!(app.git_ignore_whitespace);
+    assert_eq!(app.git_diff_hunks[0].changed_lines().len(), 2);
+
+    app.toggle_selected_hunk_stage(true);
+    assert_eq!(
+        app.git_message.as_deref(),
+        Some("Show whitespace (w) to stage or discard hunks")
+    );
+    assert!(!git.has_staged_changes().unwrap());
+
+    app.toggle_git_ignore_whitespace();
+    app.toggle_selected_hunk_stage(true);
+    assert!(git.has_staged_changes().unwrap());
+}
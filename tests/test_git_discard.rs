@@ -0,0 +1,177 @@
+mod common;
+
+use common::{commit_all, numbered_lines};
+use crossterm::event::KeyCode;
+use git2::Repository;
+use icaros::git::{DiscardSource, GitManager};
+use icaros::{file_tree, ui};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// `notes.txt` is committed, then has a staged edit to line 2 and unstaged
+/// edits to lines 10 and 18, which diff as two hunks.
+fn edited_repo(root: &Path) -> GitManager {
+    let repo = Repository::init(root).unwrap();
+    fs::write(root.join(".gitignore"), ".icaros\n").unwrap();
+    fs::write(root.join("notes.txt"), numbered_lines(&[])).unwrap();
+    commit_all(&repo, "Initial commit");
+
+    let git = GitManager::new(root).unwrap();
+    fs::write(root.join("notes.txt"), numbered_lines(&[2])).unwrap();
+    git.stage_file(Path::new("notes.txt")).unwrap();
+    fs::write(root.join("notes.txt"), numbered_lines(&[2, 10, 18])).unwrap();
+    git
+}
+
+fn trashed(trash: &Path, side: &str) -> String {
+    fs::read_to_string(trash.join(side).join("notes.txt")).unwrap()
+}
+
+#[test]
+fn test_discard_file_from_index_or_head() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let git = edited_repo(root);
+    let path = Path::new("notes.txt");
+
+    // From the index, the staged edit stays
+    let trash = git.discard_file(path, DiscardSource::Index).unwrap();
+    assert!(trash.starts_with(root.join(".git/icaros/trash")));
+    assert_eq!(trashed(&trash, "worktree"), numbered_lines(&[2, 10, 18]));
+    assert_eq!(
+        fs::read_to_string(root.join(path)).unwrap(),
+        numbered_lines(&[2])
+    );
+    assert!(git.has_staged_changes().unwrap());
+
+    // From HEAD, both go, and the staged version is kept too
+    let trash = git.discard_file(path, DiscardSource::Head).unwrap();
+    assert_eq!(trashed(&trash, "index"), numbered_lines(&[2]));
+    assert_eq!(
+        fs::read_to_string(root.join(path)).unwrap(),
+        numbered_lines(&[])
+    );
+    assert!(!git.has_staged_changes().unwrap());
+
+    // A file added since HEAD is removed altogether
+    fs::write(root.join("new.txt"), "new\n").unwrap();
+    git.stage_file(Path::new("new.txt")).unwrap();
+    git.discard_file(Path::new("new.txt"), DiscardSource::Head)
+        .unwrap();
+    assert!(!root.join("new.txt").exists());
+    assert!(git.get_status_files().unwrap().is_empty());
+}
+
+#[test]
+fn test_discard_hunk_lines_and_untracked() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let git = edited_repo(root);
+    let path = Path::new("notes.txt");
+
+    let hunks = git.get_file_diff(path, false).unwrap();
+    assert_eq!(hunks.len(), 2);
+    let trash = git.discard_hunk(path, &hunks[1]).unwrap();
+    assert_eq!(trashed(&trash, "worktree"), numbered_lines(&[2, 10, 18]));
+    assert_eq!(
+        fs::read_to_string(root.join(path)).unwrap(),
+        numbered_lines(&[2, 10])
+    );
+
+    // Undoing only the removal of line 10 brings it back beside the new one
+    let hunk = &git.get_file_diff(path, false).unwrap()[0];
+    let removed = hunk
+        .lines
+        .iter()
+        .position(|line| line.origin == '-')
+        .unwrap();
+    git.discard_lines(path, hunk, &[removed]).unwrap();
+    let content = fs::read_to_string(root.join(path)).unwrap();
+    assert!(content.contains("line 9\nline 10\nline 10 changed\nline 11\n"));
+
+    fs::create_dir_all(root.join("scratch/deep")).unwrap();
+    fs::write(root.join("scratch/deep/notes.md"), "keep me").unwrap();
+    let trash = git.delete_untracked(Path::new("scratch/")).unwrap();
+    assert!(!root.join("scratch").exists());
+    assert_eq!(
+        fs::read_to_string(trash.join("worktree/scratch/deep/notes.md")).unwrap(),
+        "keep me"
+    );
+}
+
+#[test]
+fn test_git_stage_tab_confirms_discards() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    let _git = edited_repo(&root);
+
+    let tree = file_tree::build_tree(&root, &[], false).unwrap();
+    let mut app = ui::App::new(tree, root.join(".icaros"), root.clone());
+    app.refresh_git_status();
+    let unstaged = app.git_files.iter().position(|file| !file.staged).unwrap();
+    app.select_git_file(unstaged);
+    app.git_pane = ui::GitPane::DiffView;
+
+    // Cancelling leaves the file alone
+    app.start_discard();
+    assert_eq!(
+        app.discard_prompt().as_deref(),
+        Some("Discard hunk 1 of notes.txt?")
+    );
+    app.handle_discard_key(KeyCode::Esc);
+    assert!(app.git_pending_discard.is_none());
+    assert_eq!(
+        fs::read_to_string(root.join("notes.txt")).unwrap(),
+        numbered_lines(&[2, 10, 18])
+    );
+
+    app.start_discard();
+    app.handle_discard_key(KeyCode::Char('y'));
+    assert!(app
+        .git_message
+        .as_deref()
+        .unwrap()
+        .starts_with("Discarded, saved to .git/icaros/trash/"));
+    assert_eq!(
+        fs::read_to_string(root.join("notes.txt")).unwrap(),
+        numbered_lines(&[2, 18])
+    );
+
+    // Staged hunks have to be unstaged before they can be discarded
+    let staged = app.git_files.iter().position(|file| file.staged).unwrap();
+    app.select_git_file(staged);
+    app.start_discard();
+    assert!(app.git_pending_discard.is_none());
+    assert_eq!(
+        app.git_message.as_deref(),
+        Some("Only unstaged hunks can be discarded; unstage it first")
+    );
+}
+
+#[test]
+fn test_deleted_file_is_discarded_whole_from_the_diff() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    let _git = edited_repo(&root);
+    fs::remove_file(root.join("notes.txt")).unwrap();
+
+    let tree = file_tree::build_tree(&root, &[], false).unwrap();
+    let mut app = ui::App::new(tree, root.join(".icaros"), root.clone());
+    app.refresh_git_status();
+    let deleted = app.git_files.iter().position(|file| !file.staged).unwrap();
+    app.select_git_file(deleted);
+    app.git_pane = ui::GitPane::DiffView;
+
+    app.start_discard();
+    assert_eq!(
+        app.discard_prompt().as_deref(),
+        Some("Discard unstaged changes to notes.txt?")
+    );
+    app.handle_discard_key(KeyCode::Char('y'));
+    // Back to the staged version
+    assert_eq!(
+        fs::read_to_string(root.join("notes.txt")).unwrap(),
+        numbered_lines(&[2])
+    );
+}
@@ -0,0 +1,207 @@
+mod common;
+
+use common::commit_all;
+use crossterm::event::KeyCode;
+use git2::{Oid, Repository};
+use icaros::git::{DiffTarget, GitFile, GitFileStatus, GitManager};
+use icaros::{file_tree, ui};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn status_file(git: &GitManager, path: &str, staged: bool) -> GitFile {
+    git.get_status_files()
+        .unwrap()
+        .into_iter()
+        .find(|file| file.path == Path::new(path) && file.staged == staged)
+        .unwrap()
+}
+
+fn short_id(bytes: &[u8]) -> String {
+    Oid::hash_object(git2::ObjectType::Blob, bytes)
+        .unwrap()
+        .to_string()[..7]
+        .to_string()
+}
+
+#[test]
+fn test_untracked_files_show_in_full() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let repo = Repository::init(root).unwrap();
+    fs::write(root.join("README.md"), "readme\n").unwrap();
+    commit_all(&repo, "Initial commit");
+    fs::write(root.join("todo.txt"), "one\ntwo\n").unwrap();
+    fs::create_dir_all(root.join("notes")).unwrap();
+    fs::write(root.join("notes/a.md"), "a\n").unwrap();
+
+    let git = GitManager::new(root).unwrap();
+    let file = status_file(&git, "todo.txt", false);
+    assert_eq!(file.status, GitFileStatus::Untracked);
+    let diff = git.diff_file(&file, DiffTarget::Unstaged, false).unwrap();
+    let lines: Vec<(char, &str)> = diff.hunks[0]
+        .lines
+        .iter()
+        .map(|line| (line.origin, line.content.as_str()))
+        .collect();
+    assert_eq!(lines, vec![('+', "one\n"), ('+', "two\n")]);
+
+    // A new directory shows each file in it, headed by its path
+    let diff = git
+        .diff_file(
+            &status_file(&git, "notes/", false),
+            DiffTarget::Unstaged,
+            false,
+        )
+        .unwrap();
+    assert!(diff.hunks[0].header.starts_with("notes/a.md @@ -0,0 +1 @@"));
+
+    git.stage(&status_file(&git, "notes/", false)).unwrap();
+    assert_eq!(
+        status_file(&git, "notes/a.md", true).status,
+        GitFileStatus::Added
+    );
+}
+
+#[test]
+fn test_binary_and_mode_changes_are_summarised() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let repo = Repository::init(root).unwrap();
+    let (old, new) = (
+        b"\x00\x01binary".to_vec(),
+        b"\x00\x02binary, longer".to_vec(),
+    );
+    fs::write(root.join("logo.png"), &old).unwrap();
+    fs::write(root.join("run.sh"), "echo hi\n").unwrap();
+    commit_all(&repo, "Initial commit");
+    fs::write(root.join("logo.png"), &new).unwrap();
+
+    let git = GitManager::new(root).unwrap();
+    let diff = git
+        .diff_file(
+            &status_file(&git, "logo.png", false),
+            DiffTarget::Unstaged,
+            false,
+        )
+        .unwrap();
+    assert!(diff.hunks.is_empty());
+    assert_eq!(
+        diff.notes(),
+        vec![format!(
+            "Binary file: 8 bytes ({}) → 16 bytes ({})",
+            short_id(&old),
+            short_id(&new)
+        )]
+    );
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(root.join("run.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+        let diff = git
+            .diff_file(
+                &status_file(&git, "run.sh", false),
+                DiffTarget::Unstaged,
+                false,
+            )
+            .unwrap();
+        assert!(diff.hunks.is_empty());
+        assert_eq!(diff.notes(), vec!["Mode changed 100644 → 100755"]);
+    }
+}
+
+#[test]
+fn test_renames_pair_source_and_target() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let repo = Repository::init(root).unwrap();
+    let content: String = (1..=10).map(|n| format!("line {n}\n")).collect();
+    fs::write(root.join("old.txt"), &content).unwrap();
+    commit_all(&repo, "Initial commit");
+
+    // Renamed and edited in the working tree only
+    fs::remove_file(root.join("old.txt")).unwrap();
+    fs::write(root.join("new.txt"), content.replace("line 10", "line ten")).unwrap();
+    let git = GitManager::new(root).unwrap();
+    let files = git.get_status_files().unwrap();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].status, GitFileStatus::Renamed);
+    assert_eq!(files[0].renamed_from.as_deref(), Some(Path::new("old.txt")));
+
+    let diff = git
+        .diff_file(&files[0], DiffTarget::Unstaged, false)
+        .unwrap();
+    assert_eq!(diff.notes(), vec!["Renamed from old.txt (90% similar)"]);
+    let changed: Vec<&str> = diff.hunks[0]
+        .lines
+        .iter()
+        .filter(|line| line.origin != ' ')
+        .map(|line| line.content.as_str())
+        .collect();
+    assert_eq!(changed, vec!["line 10\n", "line ten\n"]);
+
+    // Staging it stages both paths, and it stays one rename
+    git.stage(&files[0]).unwrap();
+    let staged = status_file(&git, "new.txt", true);
+    assert_eq!(staged.status, GitFileStatus::Renamed);
+    assert_eq!(staged.renamed_from.as_deref(), Some(Path::new("old.txt")));
+    assert_eq!(git.get_path_statuses().unwrap()[0].code(), "R ");
+}
+
+#[test]
+fn test_discarding_a_rename_restores_the_old_path() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    let repo = Repository::init(&root).unwrap();
+    fs::write(root.join(".gitignore"), ".icaros\n").unwrap();
+    let content: String = (1..=10).map(|n| format!("line {n}\n")).collect();
+    fs::write(root.join("old.txt"), &content).unwrap();
+    commit_all(&repo, "Initial commit");
+    let git = GitManager::new(&root).unwrap();
+    let tree = file_tree::build_tree(&root, &[], false).unwrap();
+    let mut app = ui::App::new(tree, root.join(".icaros"), root.clone());
+    let rename = |staged: bool| {
+        fs::remove_file(root.join("old.txt")).unwrap();
+        fs::write(root.join("new.txt"), content.replace("line 10", "line ten")).unwrap();
+        if staged {
+            git.stage(&status_file(&git, "new.txt", false)).unwrap();
+        }
+    };
+    let assert_restored = |app: &ui::App| {
+        assert!(app.git_message.as_deref().unwrap().starts_with("Discarded"));
+        assert!(git.get_path_statuses().unwrap().is_empty());
+        assert_eq!(fs::read_to_string(root.join("old.txt")).unwrap(), content);
+        assert!(!root.join("new.txt").exists());
+    };
+
+    // Unstaged, its hunks can't be staged or discarded against the index
+    rename(false);
+    app.refresh_git_status();
+    app.select_git_file(0);
+    app.toggle_selected_hunk_stage(true);
+    assert_eq!(
+        app.git_message.as_deref(),
+        Some("Unstaged renames are staged whole, with Space in the file list")
+    );
+    app.start_discard();
+    assert_eq!(
+        app.discard_prompt().as_deref(),
+        Some("Undo the rename of old.txt to new.txt?")
+    );
+    app.handle_discard_key(KeyCode::Char('y'));
+    assert_restored(&app);
+
+    rename(true);
+    app.refresh_git_status();
+    app.select_git_file(0);
+    assert!(app.git_files[0].staged);
+    app.start_discard();
+    app.handle_discard_key(KeyCode::Char('y'));
+    assert_restored(&app);
+
+    // Checking out a path the index doesn't have is an error, not a no-op
+    assert!(git
+        .discard_file(Path::new("new.txt"), icaros::git::DiscardSource::Index)
+        .is_err());
+}
@@ -0,0 +1,141 @@
+mod common;
+
+use common::{commit_all, numbered_lines};
+use git2::{Repository, Status};
+use icaros::git::GitManager;
+use icaros::{file_tree, ui};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// A committed 20-line file, then changed near the top and the bottom so the
+/// working tree has two hunks.
+fn two_hunk_repo(root: &Path) -> Repository {
+    let repo = Repository::init(root).unwrap();
+    fs::write(root.join("notes.txt"), numbered_lines(&[])).unwrap();
+    commit_all(&repo, "Initial commit");
+    fs::write(root.join("notes.txt"), numbered_lines(&[2, 18])).unwrap();
+    repo
+}
+
+fn changed_lines(git: &GitManager, staged: bool) -> Vec<String> {
+    git.get_file_diff(Path::new("notes.txt"), staged)
+        .unwrap()
+        .iter()
+        .flat_map(|hunk| hunk.lines.iter())
+        .filter(|line| line.origin == '+')
+        .map(|line| line.content.trim_end().to_string())
+        .collect()
+}
+
+#[test]
+fn test_stage_and_unstage_single_hunk() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let _repo = two_hunk_repo(root);
+    let git = GitManager::new(root).unwrap();
+    let path = Path::new("notes.txt");
+
+    let hunks = git.get_file_diff(path, false).unwrap();
+    assert_eq!(hunks.len(), 2);
+    git.stage_hunk(path, &hunks[1]).unwrap();
+
+    assert_eq!(changed_lines(&git, true), vec!["line 18 changed"]);
+    assert_eq!(changed_lines(&git, false), vec!["line 2 changed"]);
+    // The working tree is untouched
+    assert_eq!(
+        fs::read_to_string(root.join("notes.txt")).unwrap(),
+        numbered_lines(&[2, 18])
+    );
+
+    // Staging the other hunk too, then taking the first back out
+    git.stage_hunk(path, &git.get_file_diff(path, false).unwrap()[0])
+        .unwrap();
+    let staged = git.get_file_diff(path, true).unwrap();
+    assert_eq!(staged.len(), 2);
+    git.unstage_hunk(path, &staged[0]).unwrap();
+    assert_eq!(changed_lines(&git, true), vec!["line 18 changed"]);
+    assert_eq!(changed_lines(&git, false), vec!["line 2 changed"]);
+
+    // A hunk that no longer matches the index is refused
+    assert!(git.stage_hunk(path, &staged[1]).is_err());
+}
+
+#[test]
+fn test_stage_hunk_without_trailing_newline() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let repo = Repository::init(root).unwrap();
+    fs::write(root.join("notes.txt"), "first\nlast").unwrap();
+    commit_all(&repo, "Initial commit");
+    fs::write(root.join("notes.txt"), "first\nlast, edited").unwrap();
+
+    let git = GitManager::new(root).unwrap();
+    let path = Path::new("notes.txt");
+    let hunks = git.get_file_diff(path, false).unwrap();
+    git.stage_hunk(path, &hunks[0]).unwrap();
+
+    assert!(git.get_file_diff(path, false).unwrap().is_empty());
+    assert_eq!(changed_lines(&git, true), vec!["last, edited"]);
+}
+
+#[test]
+fn test_stage_only_hunk_of_deleted_file_stages_the_deletion() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let repo = two_hunk_repo(root);
+    fs::remove_file(root.join("notes.txt")).unwrap();
+
+    let git = GitManager::new(root).unwrap();
+    let path = Path::new("notes.txt");
+    let hunks = git.get_file_diff(path, false).unwrap();
+    assert_eq!(hunks.len(), 1);
+    git.stage_hunk(path, &hunks[0]).unwrap();
+
+    assert_eq!(repo.status_file(path).unwrap(), Status::INDEX_DELETED);
+}
+
+#[test]
+fn test_unstage_only_hunk_of_new_file_leaves_it_untracked() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let repo = two_hunk_repo(root);
+    fs::write(root.join("added.txt"), "brand new\n").unwrap();
+
+    let git = GitManager::new(root).unwrap();
+    let path = Path::new("added.txt");
+    git.stage_file(path).unwrap();
+    let hunks = git.get_file_diff(path, true).unwrap();
+    assert_eq!(hunks.len(), 1);
+    git.unstage_hunk(path, &hunks[0]).unwrap();
+
+    assert_eq!(repo.status_file(path).unwrap(), Status::WT_NEW);
+}
+
+#[test]
+fn test_git_stage_tab_stages_selected_hunk() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    let _repo = two_hunk_repo(&root);
+    fs::write(root.join(".gitignore"), ".icaros\n.gitignore\n").unwrap();
+
+    let tree = file_tree::build_tree(&root, &[], false).unwrap();
+    let mut app = ui::App::new(tree, root.join(".icaros"), root.clone());
+    app.refresh_git_status();
+    app.load_git_diff();
+    assert_eq!(app.git_diff_hunks.len(), 2);
+
+    app.move_git_hunk_down();
+    assert_eq!(
+        app.git_diff_scroll as usize,
+        app.git_diff_hunks[0].lines.len() + 2
+    );
+    app.toggle_selected_hunk_stage(true);
+    assert_eq!(
+        app.git_message.as_deref(),
+        Some("Staged hunk 2 of notes.txt")
+    );
+
+    let git = GitManager::new(&root).unwrap();
+    assert_eq!(changed_lines(&git, true), vec!["line 18 changed"]);
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use common::commit_all;
+use git2::Repository;
+use icaros::git::GitManager;
+use icaros::{file_tree, ui};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// A committed file, then edited so one hunk both replaces a line and adds one.
+fn mixed_hunk_repo(root: &Path) -> Repository {
+    let repo = Repository::init(root).unwrap();
+    fs::write(root.join("notes.txt"), "a\nb\nc\n").unwrap();
+    commit_all(&repo, "Initial commit");
+    fs::write(root.join("notes.txt"), "a\nB\nc\nd\n").unwrap();
+    repo
+}
+
+fn index_content(repo: &Repository) -> String {
+    let mut index = repo.index().unwrap();
+    index.read(true).unwrap();
+    let entry = index.get_path(Path::new("notes.txt"), 0).unwrap();
+    let blob = repo.find_blob(entry.id).unwrap();
+    String::from_utf8(blob.content().to_vec()).unwrap()
+}
+
+/// The index, within the first hunk, of the line with this origin and content.
+fn line_index(git: &GitManager, staged: bool, origin: char, content: &str) -> usize {
+    let hunks = git.get_file_diff(Path::new("notes.txt"), staged).unwrap();
+    hunks[0]
+        .lines
+        .iter()
+        .position(|line| line.origin == origin && line.content.trim_end() == content)
+        .unwrap()
+}
+
+#[test]
+fn test_stage_selected_lines_only() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let repo = mixed_hunk_repo(root);
+    let git = GitManager::new(root).unwrap();
+    let path = Path::new("notes.txt");
+
+    // Only the appended line; the replaced line stays as it was
+    let hunk = &git.get_file_diff(path, false).unwrap()[0];
+    assert_eq!(hunk.changed_lines().len(), 3);
+    git.stage_lines(path, hunk, &[line_index(&git, false, '+', "d")])
+        .unwrap();
+    assert_eq!(index_content(&repo), "a\nb\nc\nd\n");
+
+    // A removal without its replacement
+    let hunk = &git.get_file_diff(path, false).unwrap()[0];
+    git.stage_lines(path, hunk, &[line_index(&git, false, '-', "b")])
+        .unwrap();
+    assert_eq!(index_content(&repo), "a\nc\nd\n");
+    assert_eq!(
+        fs::read_to_string(root.join("notes.txt")).unwrap(),
+        "a\nB\nc\nd\n"
+    );
+}
+
+#[test]
+fn test_unstage_selected_lines() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let repo = mixed_hunk_repo(root);
+    let git = GitManager::new(root).unwrap();
+    let path = Path::new("notes.txt");
+    git.stage_file(path).unwrap();
+
+    let hunk = &git.get_file_diff(path, true).unwrap()[0];
+    git.unstage_lines(path, hunk, &[line_index(&git, true, '+', "d")])
+        .unwrap();
+    assert_eq!(index_content(&repo), "a\nB\nc\n");
+
+    let hunk = &git.get_file_diff(path, true).unwrap()[0];
+    let lines = [
+        line_index(&git, true, '-', "b"),
+        line_index(&git, true, '+', "B"),
+    ];
+    git.unstage_lines(path, hunk, &lines).unwrap();
+    assert_eq!(index_content(&repo), "a\nb\nc\n");
+}
+
+#[test]
+fn test_git_stage_tab_line_mode() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    let repo = mixed_hunk_repo(&root);
+    fs::write(root.join(".gitignore"), ".icaros\n.gitignore\n").unwrap();
+
+    let tree = file_tree::build_tree(&root, &[], false).unwrap();
+    let mut app = ui::App::new(tree, root.join(".icaros"), root.clone());
+    app.refresh_git_status();
+    app.load_git_diff();
+
+    app.enter_git_line_mode();
+    assert!(app.git_line_mode);
+    let changed = app.git_diff_hunks[0].changed_lines();
+    assert_eq!(app.git_line_cursor, changed[0]);
+    // The cursor skips context lines and stops at the last change
+    app.move_git_line_cursor(true);
+    assert_eq!(app.git_line_cursor, changed[1]);
+    app.move_git_line_cursor(true);
+    app.move_git_line_cursor(true);
+    assert_eq!(app.git_line_cursor, changed[2]);
+
+    app.toggle_git_line_selected();
+    app.toggle_selected_hunk_stage(true);
+    assert_eq!(
+        app.git_message.as_deref(),
+        Some("Staged 1 line(s) of hunk 1 of notes.txt")
+    );
+    assert!(!app.git_line_mode);
+    assert_eq!(index_content(&repo), "a\nb\nc\nd\n");
+}
@@ -0,0 +1,163 @@
+mod common;
+
+use common::commit_all;
+use crossterm::event::KeyCode;
+use git2::Repository;
+use icaros::config::EnforcementMode;
+use icaros::git::GitManager;
+use icaros::state::AppState;
+use icaros::ui::LockViolation;
+use icaros::{file_tree, ui};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// `src/` is locked, with new files allowed in `src/plugins/`. An agent then
+/// edits `src/main.rs`, deletes `src/old.rs`, and adds files in both
+/// directories and outside them.
+fn locked_repo(root: &Path) -> ui::App {
+    let repo = Repository::init(root).unwrap();
+    fs::write(root.join(".gitignore"), ".icaros\n").unwrap();
+    fs::create_dir_all(root.join("src/plugins")).unwrap();
+    fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+    fs::write(root.join("src/old.rs"), "// old\n").unwrap();
+    fs::write(root.join("src/plugins/mod.rs"), "\n").unwrap();
+    fs::write(root.join("notes.txt"), "notes\n").unwrap();
+    commit_all(&repo, "Initial commit");
+
+    let mut state = AppState::new(root.to_path_buf());
+    state.locked_patterns = vec!["src/**".to_string()];
+    state.allow_create_patterns = vec!["src/plugins".to_string()];
+    state.save_to_file(&root.join(".icaros")).unwrap();
+
+    fs::write(root.join("src/main.rs"), "fn main() { run(); }\n").unwrap();
+    fs::remove_file(root.join("src/old.rs")).unwrap();
+    fs::write(root.join("src/new.rs"), "// new\n").unwrap();
+    fs::write(root.join("src/plugins/extra.rs"), "// extra\n").unwrap();
+    fs::write(root.join("notes.txt"), "more notes\n").unwrap();
+
+    let tree = file_tree::build_tree(root, &[], false).unwrap();
+    let mut app = ui::App::new(tree, root.join(".icaros"), root.to_path_buf());
+    app.restore_state(&state);
+    app.refresh_git_status();
+    app
+}
+
+fn select(app: &mut ui::App, path: &str) {
+    let index = app
+        .git_files
+        .iter()
+        .position(|file| file.path == Path::new(path))
+        .unwrap();
+    app.select_git_file(index);
+}
+
+fn violations(app: &ui::App) -> Vec<(String, Option<LockViolation>)> {
+    app.git_files
+        .iter()
+        .zip(&app.git_violations)
+        .map(|(file, violation)| (file.path.display().to_string(), *violation))
+        .collect()
+}
+
+#[test]
+fn test_changed_files_are_checked_against_locks() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    let app = locked_repo(&root);
+
+    assert_eq!(
+        violations(&app),
+        vec![
+            ("notes.txt".to_string(), None),
+            ("src/main.rs".to_string(), Some(LockViolation::Modified)),
+            ("src/new.rs".to_string(), Some(LockViolation::Created)),
+            ("src/old.rs".to_string(), Some(LockViolation::Deleted)),
+            ("src/plugins/extra.rs".to_string(), None),
+        ]
+    );
+    assert_eq!(
+        LockViolation::Created.label(),
+        "created in a locked directory"
+    );
+}
+
+#[test]
+fn test_revert_or_unlock_a_locked_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    let mut app = locked_repo(&root);
+
+    // Reverting brings back the committed file, and deletes new ones
+    select(&mut app, "src/old.rs");
+    app.start_locked_revert();
+    assert_eq!(
+        app.discard_prompt().as_deref(),
+        Some("Revert src/old.rs to HEAD, staged and unstaged, to keep its lock?")
+    );
+    app.handle_discard_key(KeyCode::Char('y'));
+    assert_eq!(
+        fs::read_to_string(root.join("src/old.rs")).unwrap(),
+        "// old\n"
+    );
+    select(&mut app, "src/new.rs");
+    app.start_locked_revert();
+    app.handle_discard_key(KeyCode::Char('y'));
+    assert!(!root.join("src/new.rs").exists());
+
+    select(&mut app, "notes.txt");
+    app.start_locked_revert();
+    assert!(app.git_pending_discard.is_none());
+    assert_eq!(app.git_message.as_deref(), Some("notes.txt breaks no lock"));
+
+    // Accepting unlocks just that file and stages it
+    select(&mut app, "src/main.rs");
+    app.unlock_and_accept();
+    assert_eq!(
+        app.git_message.as_deref(),
+        Some("Unlocked src/main.rs and staged it")
+    );
+    let state = AppState::load_from_file(&root.join(".icaros")).unwrap();
+    let policy = state.current_policy();
+    assert!(!policy.is_locked(Path::new("src/main.rs")));
+    assert!(policy.is_locked(Path::new("src/old.rs")));
+    let staged = &app.git_files[app.git_selected_file];
+    assert!(staged.staged && staged.path == Path::new("src/main.rs"));
+    assert_eq!(app.selected_violation(), None);
+}
+
+#[test]
+fn test_staging_a_locked_file_asks_first() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    let mut app = locked_repo(&root);
+    let git = GitManager::new(&root).unwrap();
+
+    select(&mut app, "src/main.rs");
+    app.toggle_git_file_stage();
+    assert_eq!(
+        app.locked_stage_prompt().as_deref(),
+        Some("src/main.rs breaks a lock (locked file modified). Stage it anyway?")
+    );
+    app.handle_locked_stage_key(KeyCode::Esc);
+    assert!(app.git_pending_locked_stage.is_none());
+    assert!(!git.has_staged_changes().unwrap());
+
+    app.toggle_selected_hunk_stage(true);
+    assert!(app.git_pending_locked_stage.is_some());
+    app.handle_locked_stage_key(KeyCode::Char('y'));
+    assert!(git.has_staged_changes().unwrap());
+
+    // Files that break no lock stage straight away; with warn so do locked ones
+    select(&mut app, "notes.txt");
+    app.toggle_git_file_stage();
+    assert!(app.git_pending_locked_stage.is_none());
+    app.settings.enforcement = EnforcementMode::Warn;
+    select(&mut app, "src/new.rs");
+    app.toggle_git_file_stage();
+    assert!(app.git_pending_locked_stage.is_none());
+    assert_eq!(
+        app.git_message.as_deref(),
+        Some("Staged src/new.rs, which breaks a lock (created in a locked directory)")
+    );
+}
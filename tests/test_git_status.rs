@@ -0,0 +1,135 @@
+mod common;
+
+use common::commit_all;
+use git2::Repository;
+use icaros::git::{DiffTarget, GitFileStatus, GitManager};
+use icaros::{file_tree, ui};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// `notes.txt` has a staged edit and a further unstaged one, `new.txt` is added
+/// then edited, and `scratch.txt` is untracked.
+fn partially_staged_repo(root: &Path) -> GitManager {
+    let repo = Repository::init(root).unwrap();
+    fs::write(root.join(".gitignore"), ".icaros\n").unwrap();
+    fs::write(root.join("notes.txt"), "one\ntwo\n").unwrap();
+    commit_all(&repo, "Initial commit");
+
+    let git = GitManager::new(root).unwrap();
+    fs::write(root.join("notes.txt"), "one, staged\ntwo\n").unwrap();
+    fs::write(root.join("new.txt"), "new\n").unwrap();
+    git.stage_file(Path::new("notes.txt")).unwrap();
+    git.stage_file(Path::new("new.txt")).unwrap();
+    fs::write(root.join("notes.txt"), "one, staged\ntwo, unstaged\n").unwrap();
+    fs::write(root.join("new.txt"), "new, edited\n").unwrap();
+    fs::write(root.join("scratch.txt"), "").unwrap();
+    git
+}
+
+fn added_lines(git: &GitManager, target: DiffTarget) -> Vec<String> {
+    git.get_diff(Path::new("notes.txt"), target)
+        .unwrap()
+        .iter()
+        .flat_map(|hunk| hunk.lines.iter())
+        .filter(|line| line.origin == '+')
+        .map(|line| line.content.trim_end().to_string())
+        .collect()
+}
+
+#[test]
+fn test_index_and_worktree_status_kept_apart() {
+    let temp_dir = TempDir::new().unwrap();
+    let git = partially_staged_repo(temp_dir.path());
+
+    let mut codes: Vec<(String, String)> = git
+        .get_path_statuses()
+        .unwrap()
+        .iter()
+        .map(|status| (status.path.display().to_string(), status.code()))
+        .collect();
+    codes.sort();
+    assert_eq!(
+        codes,
+        vec![
+            ("new.txt".to_string(), "AM".to_string()),
+            ("notes.txt".to_string(), "MM".to_string()),
+            ("scratch.txt".to_string(), "??".to_string()),
+        ]
+    );
+
+    // Staged entries come first; a file changed on both sides is in both
+    let mut files: Vec<(bool, String, GitFileStatus)> = git
+        .get_status_files()
+        .unwrap()
+        .into_iter()
+        .map(|file| (file.staged, file.path.display().to_string(), file.status))
+        .collect();
+    assert!(files.iter().take(2).all(|(staged, ..)| *staged));
+    files.sort_by(|a, b| (!a.0, &a.1).cmp(&(!b.0, &b.1)));
+    assert_eq!(
+        files,
+        vec![
+            (true, "new.txt".to_string(), GitFileStatus::Added),
+            (true, "notes.txt".to_string(), GitFileStatus::Modified),
+            (false, "new.txt".to_string(), GitFileStatus::Modified),
+            (false, "notes.txt".to_string(), GitFileStatus::Modified),
+            (false, "scratch.txt".to_string(), GitFileStatus::Untracked),
+        ]
+    );
+}
+
+#[test]
+fn test_diff_targets() {
+    let temp_dir = TempDir::new().unwrap();
+    let git = partially_staged_repo(temp_dir.path());
+
+    assert_eq!(added_lines(&git, DiffTarget::Staged), vec!["one, staged"]);
+    assert_eq!(
+        added_lines(&git, DiffTarget::Unstaged),
+        vec!["two, unstaged"]
+    );
+    assert_eq!(
+        added_lines(&git, DiffTarget::All),
+        vec!["one, staged", "two, unstaged"]
+    );
+}
+
+#[test]
+fn test_git_stage_tab_sections() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    let _git = partially_staged_repo(&root);
+
+    let tree = file_tree::build_tree(&root, &[], false).unwrap();
+    let mut app = ui::App::new(tree, root.join(".icaros"), root.clone());
+    app.refresh_git_status();
+    let unstaged_notes = app
+        .git_files
+        .iter()
+        .position(|file| !file.staged && file.path == Path::new("notes.txt"))
+        .unwrap();
+
+    // Each entry shows the diff for its own section
+    app.select_git_file(unstaged_notes);
+    assert_eq!(app.git_diff_target, DiffTarget::Unstaged);
+    app.cycle_git_diff_target();
+    assert_eq!(app.git_diff_target, DiffTarget::All);
+    app.toggle_selected_hunk_stage(true);
+    assert_eq!(
+        app.git_message.as_deref(),
+        Some("Switch to the staged or unstaged diff to stage hunks")
+    );
+
+    // Staging the last unstaged hunk follows the file into the Staged section
+    app.select_git_file(unstaged_notes);
+    app.toggle_selected_hunk_stage(true);
+    let file = &app.git_files[app.git_selected_file];
+    assert!(file.staged);
+    assert_eq!(file.path, Path::new("notes.txt"));
+    assert_eq!(app.git_diff_target, DiffTarget::Staged);
+    assert_eq!(
+        app.git_message.as_deref(),
+        Some("Staged hunk 1 of notes.txt")
+    );
+}
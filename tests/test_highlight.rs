@@ -0,0 +1,112 @@
+mod common;
+
+use common::commit_all;
+use git2::Repository;
+use icaros::config::ThemeName;
+use icaros::diff_layout::word_diff;
+use icaros::git::{DiffTarget, GitManager};
+use icaros::highlight::{highlight_file, mark_changes, Token};
+use icaros::{file_tree, ui};
+use ratatui::style::Color;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn text(tokens: &[Token]) -> String {
+    tokens.iter().map(|token| token.text.as_str()).collect()
+}
+
+#[test]
+fn test_whole_file_highlighting() {
+    let source = "/* a comment\nfn inside() {} */\nfn outside() {}\n";
+    let lines = highlight_file(Path::new("lib.rs"), source, ThemeName::Desert).unwrap();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(text(&lines[1]), "fn inside() {} */");
+
+    // The second line is still in the comment opened on the first
+    let comment = lines[0][0].color;
+    assert!(lines[1].iter().all(|token| token.color == comment));
+    let keyword = lines[2].iter().find(|token| token.text == "fn").unwrap();
+    assert_ne!(keyword.color, comment);
+
+    let sql = highlight_file(Path::new("q.sql"), "SELECT 1;\n", ThemeName::Ocean).unwrap();
+    assert!(sql[0].len() > 1);
+    assert!(highlight_file(Path::new("app.ts"), "const a = 1;\n", ThemeName::Desert).is_some());
+    assert!(highlight_file(Path::new("lib.rs"), source, ThemeName::Mono).is_none());
+    assert!(highlight_file(Path::new("notes.unknown"), source, ThemeName::Desert).is_none());
+}
+
+#[test]
+fn test_word_changes_split_syntax_tokens() {
+    let tokens = vec![
+        Token {
+            text: "let".to_string(),
+            color: Color::Red,
+        },
+        Token {
+            text: " x = 10;".to_string(),
+            color: Color::Blue,
+        },
+    ];
+    let (_, segments) = word_diff("let x = 1;", "let x = 10;");
+    let marked: Vec<(String, Color, bool)> = mark_changes(&tokens, &segments)
+        .into_iter()
+        .map(|(token, changed)| (token.text, token.color, changed))
+        .collect();
+    let piece = |text: &str, color, changed| (text.to_string(), color, changed);
+    assert_eq!(
+        marked,
+        vec![
+            piece("let", Color::Red, false),
+            piece(" x = ", Color::Blue, false),
+            piece("10", Color::Blue, true),
+            piece(";", Color::Blue, false),
+        ]
+    );
+}
+
+#[test]
+fn test_diff_lines_take_colors_from_their_version() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    let repo = Repository::init(&root).unwrap();
+    fs::write(root.join(".gitignore"), ".icaros\n").unwrap();
+    fs::write(root.join("main.rs"), "fn main() {\n    let x = 1;\n}\n").unwrap();
+    commit_all(&repo, "Initial commit");
+    fs::write(root.join("main.rs"), "fn main() {\n    let x = 2;\n}\n").unwrap();
+
+    let git = GitManager::new(&root).unwrap();
+    let file = &git.get_status_files().unwrap()[0];
+    let (old, new) = git.file_versions(file, DiffTarget::Unstaged);
+    assert_eq!(old.as_deref(), Some("fn main() {\n    let x = 1;\n}\n"));
+    assert_eq!(new.as_deref(), Some("fn main() {\n    let x = 2;\n}\n"));
+
+    let tree = file_tree::build_tree(&root, &[], false).unwrap();
+    let mut app = ui::App::new(tree, root.join(".icaros"), root.clone());
+    app.settings.theme = ThemeName::Desert;
+    app.refresh_git_status();
+    app.select_git_file(0);
+    let lines = &app.git_diff_hunks[0].lines;
+    let colored: Vec<(char, String)> = lines
+        .iter()
+        .map(|line| {
+            let tokens = app.git_diff_highlights.line(line).unwrap();
+            (line.origin, text(tokens))
+        })
+        .collect();
+    assert_eq!(
+        colored,
+        vec![
+            (' ', "fn main() {".to_string()),
+            ('-', "    let x = 1;".to_string()),
+            ('+', "    let x = 2;".to_string()),
+            (' ', "}".to_string()),
+        ]
+    );
+
+    // Lines that no longer match the file read for highlighting stay plain
+    assert!(app
+        .git_diff_highlights
+        .new_line(2, "    let x = 3;")
+        .is_none());
+}
@@ -0,0 +1,187 @@
+use icaros::git::GitManager;
+use icaros::merge::{merge_states, ConflictKind, MergeSide};
+use icaros::state::{AppState, LockProfile};
+use std::fs;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+fn state_with(locked: &[&str], unlocked: &[&str]) -> AppState {
+    let mut state = AppState::new(PathBuf::from("/test/root"));
+    state.locked_patterns = locked.iter().map(|p| p.to_string()).collect();
+    state.unlocked_patterns = unlocked.iter().map(|p| p.to_string()).collect();
+    state
+}
+
+fn profile(locked: &[&str]) -> LockProfile {
+    LockProfile {
+        locked_patterns: locked.iter().map(|p| p.to_string()).collect(),
+        ..LockProfile::default()
+    }
+}
+
+#[test]
+fn test_merge_unions_locks_from_both_sides() {
+    let base = state_with(&["README.md"], &[]);
+    let ours = state_with(&["README.md", "src/**"], &[]);
+    let theirs = state_with(&["README.md", "migrations/**"], &[]);
+
+    let outcome = merge_states(&base, &ours, &theirs);
+
+    assert!(outcome.conflicts.is_empty());
+    assert_eq!(
+        outcome.state.locked_patterns,
+        vec!["README.md", "src/**", "migrations/**"]
+    );
+}
+
+#[test]
+fn test_merge_honours_one_sided_removal() {
+    let base = state_with(&["README.md", "src/**"], &[]);
+    let ours = state_with(&["README.md"], &[]);
+    let theirs = state_with(&["README.md", "src/**"], &[]);
+
+    let outcome = merge_states(&base, &ours, &theirs);
+
+    assert!(outcome.conflicts.is_empty());
+    assert_eq!(outcome.state.locked_patterns, vec!["README.md"]);
+}
+
+#[test]
+fn test_merge_flags_lock_unlock_contradiction() {
+    let base = state_with(&["**"], &[]);
+    let ours = state_with(&["**", "src/**"], &[]);
+    let theirs = state_with(&["**"], &["src/**"]);
+
+    let outcome = merge_states(&base, &ours, &theirs);
+
+    assert_eq!(outcome.conflicts.len(), 1);
+    assert_eq!(
+        outcome.conflicts[0].kind,
+        ConflictKind::Lock {
+            pattern: "src/**".to_string(),
+            locked_by: MergeSide::Ours,
+        }
+    );
+    assert_eq!(outcome.conflicts[0].profile, None);
+    assert!(outcome
+        .state
+        .locked_patterns
+        .contains(&"src/**".to_string()));
+    assert!(outcome.state.unlocked_patterns.is_empty());
+}
+
+#[test]
+fn test_merge_profiles_by_name() {
+    let mut base = state_with(&[], &[]);
+    base.profiles
+        .insert("shared".to_string(), profile(&["a/**"]));
+    base.profiles
+        .insert("stale".to_string(), profile(&["old/**"]));
+
+    let mut ours = base.clone();
+    ours.profiles
+        .insert("frontend".to_string(), profile(&["api/**"]));
+    ours.profiles
+        .insert("shared".to_string(), profile(&["a/**", "b/**"]));
+    ours.profiles.remove("stale");
+
+    let mut theirs = base.clone();
+    theirs
+        .profiles
+        .insert("backend".to_string(), profile(&["web/**"]));
+    theirs
+        .profiles
+        .insert("shared".to_string(), profile(&["a/**", "c/**"]));
+
+    let outcome = merge_states(&base, &ours, &theirs);
+    let profiles = &outcome.state.profiles;
+
+    assert!(outcome.conflicts.is_empty());
+    assert!(profiles.contains_key("frontend"));
+    assert!(profiles.contains_key("backend"));
+    assert!(!profiles.contains_key("stale"));
+    assert_eq!(
+        profiles["shared"].locked_patterns,
+        vec!["a/**", "b/**", "c/**"]
+    );
+}
+
+#[test]
+fn test_merge_reports_conflicts_inside_profiles() {
+    let mut base = state_with(&[], &[]);
+    base.profiles.insert("task".to_string(), profile(&["**"]));
+
+    let mut ours = base.clone();
+    ours.profiles
+        .insert("task".to_string(), profile(&["**", "docs/**"]));
+
+    let mut theirs = base.clone();
+    let mut unlocked_docs = profile(&["**"]);
+    unlocked_docs.unlocked_patterns = vec!["docs/**".to_string()];
+    theirs.profiles.insert("task".to_string(), unlocked_docs);
+
+    let outcome = merge_states(&base, &ours, &theirs);
+
+    assert_eq!(outcome.conflicts.len(), 1);
+    assert_eq!(outcome.conflicts[0].profile.as_deref(), Some("task"));
+    assert_eq!(
+        outcome.state.profiles["task"].locked_patterns,
+        vec!["**", "docs/**"]
+    );
+}
+
+#[test]
+fn test_merge_reports_quest_and_extends_changed_on_both_sides() {
+    let mut base = state_with(&[], &[]);
+    base.profiles.insert("base".to_string(), profile(&["**"]));
+    base.profiles
+        .insert("other".to_string(), profile(&["docs/**"]));
+    base.profiles
+        .insert("task".to_string(), profile(&["src/**"]));
+
+    let mut ours = base.clone();
+    ours.profiles.get_mut("task").unwrap().extends = vec!["base".to_string()];
+    ours.start_quest("ours-quest", vec!["src/**".to_string()], Vec::new())
+        .unwrap();
+
+    let mut theirs = base.clone();
+    theirs.profiles.get_mut("task").unwrap().extends = vec!["other".to_string()];
+    theirs
+        .start_quest("theirs-quest", vec!["docs/**".to_string()], Vec::new())
+        .unwrap();
+
+    let outcome = merge_states(&base, &ours, &theirs);
+
+    let messages: Vec<String> = outcome.conflicts.iter().map(|c| c.to_string()).collect();
+    assert_eq!(
+        messages,
+        vec![
+            "profile 'task': extends changed to 'base' by ours and 'other' by theirs (kept ours)",
+            "quest changed to 'ours-quest' by ours and 'theirs-quest' by theirs (kept ours)",
+        ]
+    );
+    assert_eq!(outcome.state.quest.unwrap().name, "ours-quest");
+    assert_eq!(outcome.state.profiles["task"].extends, vec!["base"]);
+}
+
+#[test]
+fn test_install_merge_driver() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    git2::Repository::init(root).unwrap();
+    fs::write(root.join(".gitattributes"), "*.png binary").unwrap();
+
+    let git = GitManager::new(root).unwrap();
+    git.install_merge_driver(".icaros").unwrap();
+    // Installing twice must not duplicate the attribute line
+    git.install_merge_driver(".icaros").unwrap();
+
+    let attributes = fs::read_to_string(root.join(".gitattributes")).unwrap();
+    assert_eq!(attributes, "*.png binary\n.icaros merge=icaros\n");
+
+    let config = git2::Repository::open(root).unwrap().config().unwrap();
+    assert_eq!(
+        config.get_string("merge.icaros.driver").unwrap(),
+        "icaros merge-driver %O %A %B"
+    );
+}
@@ -0,0 +1,147 @@
+use assert_cmd::Command;
+use icaros::policy_diff::{self, PathChange, PathDiff};
+use icaros::state::{AppState, LockProfile};
+use icaros::{file_tree, ui};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+fn create_project(root: &Path) {
+    fs::create_dir_all(root.join("src/api")).unwrap();
+    fs::create_dir_all(root.join("docs")).unwrap();
+    fs::write(root.join("src/main.rs"), "").unwrap();
+    fs::write(root.join("src/api/mod.rs"), "").unwrap();
+    fs::write(root.join("src/api/routes.rs"), "").unwrap();
+    fs::write(root.join("docs/guide.md"), "").unwrap();
+}
+
+fn policy(locked: &[&str], unlocked: &[&str], allow_create: &[&str]) -> LockProfile {
+    let strings = |patterns: &[&str]| patterns.iter().map(|p| p.to_string()).collect();
+    LockProfile {
+        locked_patterns: strings(locked),
+        unlocked_patterns: strings(unlocked),
+        allow_create_patterns: strings(allow_create),
+        ..LockProfile::default()
+    }
+}
+
+fn change(path: &str, is_dir: bool, change: PathChange, files: usize) -> PathDiff {
+    PathDiff {
+        path: PathBuf::from(path),
+        is_dir,
+        change,
+        files,
+    }
+}
+
+#[test]
+fn test_diff_collapses_uniform_directories() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    create_project(&root);
+    let tree = file_tree::build_tree(&root, &[], false).unwrap();
+
+    let from = policy(&["docs/**"], &[], &[]);
+    let to = policy(&["src/**"], &["src/main.rs"], &["src/api"]);
+    let diff = policy_diff::diff_policies(&tree, &root, &from, &to);
+
+    assert_eq!(
+        diff.changes,
+        vec![
+            change("docs", true, PathChange::Unlocked, 1),
+            change("src/api", true, PathChange::Locked, 2),
+            change("src/api", true, PathChange::CreateAllowed, 0),
+        ]
+    );
+    assert_eq!(diff.count(PathChange::Locked), 2);
+    assert_eq!(
+        diff.summary(),
+        "2 locked, 1 unlocked, 1 create allowed, 0 create revoked"
+    );
+
+    let lines: Vec<(usize, String)> = diff
+        .tree_lines()
+        .into_iter()
+        .map(|line| (line.depth, line.name))
+        .collect();
+    assert_eq!(
+        lines,
+        vec![
+            (0, "docs/".to_string()),
+            (0, "src/".to_string()),
+            (1, "api/".to_string()),
+            (1, "api/".to_string()),
+        ]
+    );
+
+    // Locking everything from nothing is a single entry for the root
+    let diff = policy_diff::diff_policies(
+        &tree,
+        &root,
+        &LockProfile::default(),
+        &policy(&["**"], &[], &[]),
+    );
+    assert_eq!(diff.changes, vec![change(".", true, PathChange::Locked, 4)]);
+}
+
+#[test]
+fn test_profiles_tab_previews_selected_profile() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    create_project(&root);
+    let mut state = AppState::new(root.clone());
+    state.locked_patterns = vec!["docs/**".to_string()];
+    state
+        .profiles
+        .insert("docs-only".to_string(), policy(&["**"], &["docs/**"], &[]));
+    state.save_to_file(&root.join(".icaros")).unwrap();
+
+    let tree = file_tree::build_tree(&root, &[], false).unwrap();
+    let mut app = ui::App::new(tree, root.join(".icaros"), root.clone());
+    app.load_profiles();
+
+    let preview = app.selected_profile_preview.as_ref().unwrap();
+    assert_eq!(
+        preview.changes,
+        vec![
+            change("docs", true, PathChange::Unlocked, 1),
+            change("src", true, PathChange::Locked, 3),
+        ]
+    );
+}
+
+#[test]
+fn test_cli_profile_diff_json() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    create_project(root);
+    let mut state = AppState::new(root.to_path_buf());
+    state
+        .profiles
+        .insert("api".to_string(), policy(&["src/api/**"], &[], &[]));
+    state
+        .profiles
+        .insert("all".to_string(), policy(&["**"], &[], &[]));
+    state.save_to_file(&root.join(".icaros")).unwrap();
+
+    let output = Command::cargo_bin("icaros")
+        .unwrap()
+        .current_dir(root)
+        .env("ICAROS_CONFIG", root.join("no-user-config.yaml"))
+        .args(["profile", "diff", "api", "all", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    // The command sees hidden files, the state file included
+    assert_eq!(result["counts"]["locked"], 3);
+    assert_eq!(result["counts"]["unlocked"], 0);
+    assert_eq!(
+        result["changes"],
+        serde_json::json!([
+            {"path": ".icaros", "is_dir": false, "change": "locked", "files": 1},
+            {"path": "docs", "is_dir": true, "change": "locked", "files": 1},
+            {"path": "src/main.rs", "is_dir": false, "change": "locked", "files": 1},
+        ])
+    );
+}
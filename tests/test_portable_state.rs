@@ -0,0 +1,135 @@
+use icaros::state::{self, AppState};
+use std::fs;
+use tempfile::TempDir;
+
+#[test]
+fn test_state_file_stores_relative_paths() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+
+    let mut state = AppState::new(root.to_path_buf());
+    state.expanded_dirs = vec![root.to_path_buf(), root.join("src")];
+
+    let state_file = root.join(".icaros");
+    state.save_to_file(&state_file).unwrap();
+
+    let content = fs::read_to_string(&state_file).unwrap();
+    assert!(content.contains("root_path: ."), "{content}");
+    assert!(content.contains("- src"), "{content}");
+    assert!(!content.contains(&*root.to_string_lossy()), "{content}");
+
+    let loaded = AppState::load_from_file(&state_file).unwrap();
+    assert_eq!(loaded.root_path, root);
+    assert_eq!(
+        loaded.expanded_dirs,
+        vec![root.to_path_buf(), root.join("src")]
+    );
+}
+
+#[test]
+fn test_moved_project_keeps_expanded_dirs() {
+    let temp_dir = TempDir::new().unwrap();
+    let original = temp_dir.path().join("original");
+    let moved = temp_dir.path().join("moved");
+    fs::create_dir_all(original.join("src")).unwrap();
+
+    let mut state = AppState::new(original.clone());
+    state.locked_patterns = vec!["src/**".to_string()];
+    state.expanded_dirs = vec![original.join("src")];
+    state.save_to_file(&original.join(".icaros")).unwrap();
+
+    fs::rename(&original, &moved).unwrap();
+
+    let loaded = AppState::load_from_file(&moved.join(".icaros")).unwrap();
+    assert_eq!(loaded.root_path, moved);
+    assert_eq!(loaded.expanded_dirs, vec![moved.join("src")]);
+    assert_eq!(loaded.locked_patterns, vec!["src/**"]);
+}
+
+#[test]
+fn test_state_file_outside_root() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("project");
+    let config_dir = temp_dir.path().join("config");
+    fs::create_dir_all(&root).unwrap();
+    fs::create_dir_all(&config_dir).unwrap();
+
+    let state_file = config_dir.join("project.icaros");
+    AppState::new(root.clone())
+        .save_to_file(&state_file)
+        .unwrap();
+
+    let content = fs::read_to_string(&state_file).unwrap();
+    assert!(content.contains("root_path: ../project"), "{content}");
+    assert_eq!(
+        AppState::load_from_file(&state_file).unwrap().root_path,
+        root
+    );
+}
+
+#[test]
+fn test_legacy_absolute_state_is_rebased() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let state_file = root.join(".icaros");
+
+    // Written by an older version on a machine where the project lived elsewhere
+    fs::write(
+        &state_file,
+        r#"root_path: /old/machine/project
+active_profile: frontend
+profiles:
+  frontend:
+    locked_patterns:
+    - /old/machine/project/api/**
+    unlocked_patterns: []
+    allow_create_patterns: []
+locked_patterns:
+- /old/machine/project/**
+unlocked_patterns:
+- /old/machine/project/web/**
+allow_create_patterns: []
+expanded_dirs:
+- /old/machine/project
+- /old/machine/project/web
+"#,
+    )
+    .unwrap();
+
+    let loaded = AppState::load_from_file(&state_file).unwrap();
+    assert_eq!(loaded.root_path, root);
+    assert_eq!(
+        loaded.expanded_dirs,
+        vec![root.to_path_buf(), root.join("web")]
+    );
+    assert_eq!(loaded.locked_patterns, vec!["**"]);
+    assert_eq!(loaded.unlocked_patterns, vec!["web/**"]);
+    assert_eq!(loaded.profiles["frontend"].locked_patterns, vec!["api/**"]);
+
+    // Saving rewrites the legacy entries in portable form
+    loaded.save_to_file(&state_file).unwrap();
+    let content = fs::read_to_string(&state_file).unwrap();
+    assert!(!content.contains("/old/machine"), "{content}");
+}
+
+#[test]
+fn test_relative_path_helpers() {
+    use std::path::{Path, PathBuf};
+
+    assert_eq!(
+        state::relative_path(Path::new("/a/b/c"), Path::new("/a/b")),
+        PathBuf::from("c")
+    );
+    assert_eq!(
+        state::relative_path(Path::new("/a/x"), Path::new("/a/b")),
+        PathBuf::from("../x")
+    );
+    assert_eq!(
+        state::relative_path(Path::new("/a/b"), Path::new("/a/b")),
+        PathBuf::from(".")
+    );
+    assert_eq!(
+        state::normalize_path(Path::new("/a/b/../c/./d")),
+        PathBuf::from("/a/c/d")
+    );
+}
@@ -0,0 +1,160 @@
+use crossterm::event::KeyCode;
+use icaros::file_tree;
+use icaros::state::AppState;
+use icaros::text_input::TextInput;
+use icaros::ui::{App, ProfileAction};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn new_app(root: &Path) -> App {
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::write(root.join("src/main.rs"), "").unwrap();
+    fs::write(root.join("README.md"), "").unwrap();
+    let tree = file_tree::build_tree(root, &[], false).unwrap();
+    App::new(tree, root.join(".icaros"), root.to_path_buf())
+}
+
+fn type_text(app: &mut App, text: &str) {
+    for c in text.chars() {
+        app.handle_profile_key(KeyCode::Char(c));
+    }
+}
+
+fn load_state(root: &Path) -> AppState {
+    AppState::load_from_file(&root.join(".icaros")).unwrap()
+}
+
+#[test]
+fn test_text_input_edits_at_the_cursor() {
+    let mut input = TextInput::new("añb");
+    assert_eq!(input.cursor(), 3);
+
+    input.handle_key(KeyCode::Left);
+    input.handle_key(KeyCode::Backspace);
+    input.insert('x');
+    assert_eq!(input.value(), "axb");
+    assert_eq!(input.split_at_cursor(), ("ax", Some('b'), ""));
+
+    input.handle_key(KeyCode::Home);
+    input.handle_key(KeyCode::Delete);
+    input.handle_key(KeyCode::End);
+    input.handle_key(KeyCode::Right);
+    input.insert('!');
+    assert_eq!(input.value(), "xb!");
+    assert!(!input.handle_key(KeyCode::Enter));
+}
+
+#[test]
+fn test_prompt_validates_names_and_keeps_typed_keys() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    let mut app = new_app(&root);
+    app.write_state().unwrap();
+
+    // 'q' is part of the name rather than quitting
+    app.start_profile_action(ProfileAction::Save);
+    type_text(&mut app, "quick");
+    app.handle_profile_key(KeyCode::Enter);
+    assert!(!app.profile_input_mode);
+    assert_eq!(app.profile_names, vec!["quick"]);
+
+    app.start_profile_action(ProfileAction::Save);
+    assert_eq!(
+        app.profile_input_problem().as_deref(),
+        Some("Name cannot be empty")
+    );
+    type_text(&mut app, "quick");
+    assert_eq!(
+        app.profile_input_problem().as_deref(),
+        Some("Profile 'quick' already exists")
+    );
+    // Enter leaves an invalid prompt open
+    app.handle_profile_key(KeyCode::Enter);
+    assert!(app.profile_input_mode);
+    type_text(&mut app, "/x");
+    assert_eq!(
+        app.profile_input_problem().as_deref(),
+        Some("Name cannot contain '/' or '\\'")
+    );
+    app.handle_profile_key(KeyCode::Esc);
+    assert!(!app.profile_input_mode);
+    assert_eq!(load_state(&root).profiles.len(), 1);
+}
+
+#[test]
+fn test_rename_duplicate_and_describe() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    let mut app = new_app(&root);
+    app.write_state().unwrap();
+    app.start_profile_action(ProfileAction::Save);
+    type_text(&mut app, "docs");
+    app.handle_profile_key(KeyCode::Enter);
+
+    // The rename prompt starts from the current name
+    app.start_profile_action(ProfileAction::Rename);
+    assert_eq!(app.profile_input_problem(), None);
+    type_text(&mut app, "-only");
+    app.handle_profile_key(KeyCode::Enter);
+    assert_eq!(app.profile_names, vec!["docs-only"]);
+    assert_eq!(app.active_profile_name.as_deref(), Some("docs-only"));
+
+    app.start_profile_action(ProfileAction::Duplicate);
+    assert_eq!(app.profile_input.value(), "docs-only-copy");
+    app.handle_profile_key(KeyCode::Enter);
+    assert_eq!(app.selected_profile_name().unwrap(), "docs-only-copy");
+
+    app.start_profile_action(ProfileAction::Describe);
+    assert!(app.profile_input.value().starts_with("Saved on "));
+    app.profile_input = TextInput::new("  Copy for experiments ");
+    app.handle_profile_key(KeyCode::Enter);
+
+    let state = load_state(&root);
+    assert_eq!(
+        state.profiles["docs-only-copy"].description,
+        "Copy for experiments"
+    );
+    assert!(state.profiles["docs-only"]
+        .description
+        .starts_with("Saved on "));
+}
+
+#[test]
+fn test_overwrite_clears_unsaved_changes() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    let mut app = new_app(&root);
+    app.toggle_path(&root.join("src"), true);
+    app.write_state().unwrap();
+    app.start_profile_action(ProfileAction::Save);
+    type_text(&mut app, "src");
+    app.handle_profile_key(KeyCode::Enter);
+    assert!(!app.active_profile_modified);
+
+    app.start_profile_action(ProfileAction::Describe);
+    app.profile_input = TextInput::new("Sources");
+    app.handle_profile_key(KeyCode::Enter);
+
+    app.toggle_path(&root.join("README.md"), false);
+    app.write_state().unwrap();
+    app.load_profiles();
+    assert!(app.active_profile_modified);
+
+    // Typing does nothing in the confirmation; Esc leaves the profile alone
+    app.start_profile_action(ProfileAction::Overwrite);
+    type_text(&mut app, "y");
+    assert_eq!(app.profile_input.value(), "");
+    app.handle_profile_key(KeyCode::Esc);
+    assert_eq!(
+        load_state(&root).profiles["src"].locked_patterns,
+        vec!["src/**"]
+    );
+
+    app.start_profile_action(ProfileAction::Overwrite);
+    app.handle_profile_key(KeyCode::Enter);
+    assert!(!app.active_profile_modified);
+    let profile = &load_state(&root).profiles["src"];
+    assert_eq!(profile.locked_patterns, vec!["README.md", "src/**"]);
+    assert_eq!(profile.description, "Sources");
+}
@@ -0,0 +1,227 @@
+use assert_cmd::Command;
+use icaros::state::{AppState, LockProfile, ResolvedRule, RuleKind};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn strings(patterns: &[&str]) -> Vec<String> {
+    patterns.iter().map(|p| p.to_string()).collect()
+}
+
+fn profile(extends: &[&str], locked: &[&str], unlocked: &[&str]) -> LockProfile {
+    LockProfile {
+        extends: strings(extends),
+        locked_patterns: strings(locked),
+        unlocked_patterns: strings(unlocked),
+        ..LockProfile::default()
+    }
+}
+
+fn rule(kind: RuleKind, pattern: &str, origin: &str) -> ResolvedRule {
+    ResolvedRule {
+        kind,
+        pattern: pattern.to_string(),
+        origin: origin.to_string(),
+    }
+}
+
+fn layered_state(root: &Path) -> AppState {
+    let mut state = AppState::new(root.to_path_buf());
+    state.profiles.insert(
+        "baseline".to_string(),
+        profile(&[], &[".github/**", "Cargo.lock", "migrations/**"], &["**"]),
+    );
+    state.profiles.insert(
+        "ui".to_string(),
+        profile(&["baseline"], &["**"], &["web/**"]),
+    );
+    state.profiles.insert(
+        "schema".to_string(),
+        profile(&["baseline"], &[], &["migrations/**"]),
+    );
+    state
+}
+
+#[test]
+fn test_extends_layers_base_rules_first() {
+    let state = layered_state(Path::new("/test/root"));
+
+    let resolved = state.resolve_profile("ui").unwrap();
+
+    assert_eq!(resolved.layers, vec!["baseline", "ui"]);
+    // ui locks `**`, replacing baseline's unlock of it
+    assert_eq!(
+        resolved.rules,
+        vec![
+            rule(RuleKind::Locked, ".github/**", "baseline"),
+            rule(RuleKind::Locked, "Cargo.lock", "baseline"),
+            rule(RuleKind::Locked, "migrations/**", "baseline"),
+            rule(RuleKind::Locked, "**", "ui"),
+            rule(RuleKind::Unlocked, "web/**", "ui"),
+        ]
+    );
+}
+
+#[test]
+fn test_stacked_profiles_compose_in_order() {
+    let mut state = layered_state(Path::new("/test/root"));
+
+    state.activate(&strings(&["ui", "schema"])).unwrap();
+
+    // baseline is shared and applied once; schema's unlock replaces its lock
+    assert_eq!(state.active_stack(), vec!["ui", "schema"]);
+    assert_eq!(state.active_profile.as_deref(), Some("schema"));
+    assert_eq!(
+        state.locked_patterns,
+        vec![".github/**", "Cargo.lock", "**"]
+    );
+    assert_eq!(state.unlocked_patterns, vec!["web/**", "migrations/**"]);
+    assert!(!state.is_locked(Path::new("migrations/001.sql")));
+    assert!(state.is_locked(Path::new("Cargo.lock")));
+
+    assert!(state.switch_to_profile("baseline"));
+    assert!(state.profile_stack.is_empty());
+    assert_eq!(state.active_stack(), vec!["baseline"]);
+}
+
+#[test]
+fn test_cycles_and_unknown_bases_are_reported() {
+    let mut state = layered_state(Path::new("/test/root"));
+    state
+        .profiles
+        .get_mut("baseline")
+        .unwrap()
+        .extends
+        .push("ui".to_string());
+    state
+        .profiles
+        .insert("broken".to_string(), profile(&["missing"], &[], &[]));
+
+    let error = state.resolve_profile("ui").unwrap_err().to_string();
+    assert_eq!(error, "Profile inheritance cycle: ui -> baseline -> ui");
+    assert_eq!(
+        state.resolve_profile("broken").unwrap_err().to_string(),
+        "Profile 'broken' extends unknown profile 'missing'"
+    );
+    assert!(!state.switch_to_profile("ui"));
+}
+
+#[test]
+fn test_saving_over_a_derived_profile_keeps_only_its_own_rules() {
+    let mut state = layered_state(Path::new("/test/root"));
+    assert!(state.switch_to_profile("ui"));
+
+    state.locked_patterns.push("web/vendor/**".to_string());
+    assert_eq!(
+        state.save_current_as_profile("ui".to_string(), "UI work".to_string()),
+        None
+    );
+
+    let ui = &state.profiles["ui"];
+    assert_eq!(ui.extends, vec!["baseline"]);
+    assert_eq!(ui.locked_patterns, vec!["**", "web/vendor/**"]);
+    assert_eq!(ui.unlocked_patterns, vec!["web/**"]);
+
+    // References follow a rename, and a base can't be deleted from under its children
+    assert!(state.rename_profile("baseline", "base"));
+    assert_eq!(state.profiles["schema"].extends, vec!["base"]);
+    assert_eq!(state.profiles_extending("base"), vec!["schema", "ui"]);
+    assert!(!state.delete_profile("base"));
+}
+
+#[test]
+fn test_saving_reports_bases_it_cannot_keep() {
+    let mut state = layered_state(Path::new("/test/root"));
+    assert!(state.switch_to_profile("ui"));
+    // As after a merge that dropped the base
+    state.profiles.remove("baseline");
+    state.profiles.remove("schema");
+
+    let warning = state.save_current_as_profile("ui".to_string(), "UI work".to_string());
+    assert_eq!(
+        warning.as_deref(),
+        Some("Profile 'ui' no longer extends baseline: Profile 'baseline' not found")
+    );
+    assert!(state.profiles["ui"].extends.is_empty());
+    assert!(state.switch_to_profile("ui"));
+}
+
+#[test]
+fn test_derived_profile_keeps_unlocks_of_inherited_locks() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    for dir in ["migrations", "ci", "src"] {
+        fs::create_dir_all(root.join(dir)).unwrap();
+        fs::write(root.join(dir).join("file"), "").unwrap();
+    }
+    let icaros = |args: &[&str]| {
+        Command::cargo_bin("icaros")
+            .unwrap()
+            .current_dir(root)
+            .env("ICAROS_CONFIG", root.join("no-user-config.yaml"))
+            .args(args)
+            .assert()
+            .success();
+    };
+
+    icaros(&["lock", "migrations", "ci"]);
+    icaros(&["profile", "save", "baseline"]);
+    icaros(&["unlock", "migrations"]);
+    icaros(&["lock", "src"]);
+    icaros(&["profile", "save", "task", "--extends", "baseline"]);
+    icaros(&["profile", "switch", "baseline"]);
+    icaros(&["profile", "switch", "task"]);
+
+    let state = AppState::load_from_file(&root.join(".icaros")).unwrap();
+    assert_eq!(
+        state.profiles["task"].removed_patterns,
+        vec!["migrations/**"]
+    );
+    assert_eq!(state.locked_patterns, vec!["ci/**", "src/**"]);
+    assert!(!state.is_locked(Path::new("migrations/file")));
+}
+
+#[test]
+fn test_cli_show_and_switch_stack() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::create_dir_all(root.join("web")).unwrap();
+    fs::write(root.join("web/app.ts"), "").unwrap();
+    layered_state(root)
+        .save_to_file(&root.join(".icaros"))
+        .unwrap();
+
+    let icaros = || {
+        let mut cmd = Command::cargo_bin("icaros").unwrap();
+        cmd.current_dir(root)
+            .env("ICAROS_CONFIG", root.join("no-user-config.yaml"));
+        cmd
+    };
+
+    let output = icaros()
+        .args(["profile", "show", "schema", "--json"])
+        .output()
+        .unwrap();
+    let shown: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(shown["extends"], serde_json::json!(["baseline"]));
+    assert_eq!(
+        shown["resolved"].as_array().unwrap().last().unwrap(),
+        &serde_json::json!({"kind": "unlocked", "pattern": "migrations/**", "origin": "schema"})
+    );
+
+    icaros()
+        .args(["profile", "switch", "ui", "schema"])
+        .assert()
+        .success()
+        .stdout("Switched to profiles 'ui' + 'schema'\n");
+    let state = AppState::load_from_file(&root.join(".icaros")).unwrap();
+    assert_eq!(state.profile_stack, vec!["ui", "schema"]);
+
+    icaros()
+        .args(["profile", "delete", "baseline"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "Profile 'baseline' is extended by schema, ui",
+        ));
+}
@@ -0,0 +1,162 @@
+use assert_cmd::Command;
+use icaros::state::{AppState, LockProfile};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn strings(patterns: &[&str]) -> Vec<String> {
+    patterns.iter().map(|p| p.to_string()).collect()
+}
+
+/// icaros with its user config, and so the profile library, inside `home`.
+fn icaros(root: &Path, home: &Path) -> Command {
+    let mut cmd = Command::cargo_bin("icaros").unwrap();
+    cmd.current_dir(root)
+        .env("ICAROS_CONFIG", home.join("config.yaml"));
+    cmd
+}
+
+#[test]
+fn test_unresolved_patterns() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::write(root.join("Cargo.lock"), "").unwrap();
+
+    let profile = LockProfile {
+        locked_patterns: strings(&["**", "src/**", "Cargo.lock", "infra/**"]),
+        unlocked_patterns: strings(&["../outside/**", "/etc/passwd"]),
+        allow_create_patterns: strings(&["src", "migrations"]),
+        ..LockProfile::default()
+    };
+
+    let unresolved = profile.unresolved_patterns(root);
+    assert_eq!(
+        unresolved,
+        vec!["infra/**", "../outside/**", "/etc/passwd", "migrations"]
+    );
+
+    let mut profile = profile;
+    profile.remove_patterns(&unresolved);
+    assert_eq!(profile.locked_patterns, vec!["**", "src/**", "Cargo.lock"]);
+    assert!(profile.unlocked_patterns.is_empty());
+    assert_eq!(profile.allow_create_patterns, vec!["src"]);
+}
+
+#[test]
+fn test_library_shares_profiles_across_repos() {
+    let temp_dir = TempDir::new().unwrap();
+    let home = temp_dir.path().join("home");
+    let repo_a = temp_dir.path().join("a");
+    let repo_b = temp_dir.path().join("b");
+    fs::create_dir_all(repo_a.join("infra")).unwrap();
+    fs::create_dir_all(repo_a.join("src")).unwrap();
+    fs::create_dir_all(repo_b.join("src")).unwrap();
+    fs::write(repo_a.join("infra/main.tf"), "").unwrap();
+    fs::write(repo_a.join("src/lib.rs"), "").unwrap();
+    fs::write(repo_b.join("src/lib.rs"), "").unwrap();
+
+    let mut state = AppState::new(repo_a.clone());
+    state.profiles.insert(
+        "baseline".to_string(),
+        LockProfile {
+            locked_patterns: strings(&["infra/**"]),
+            ..LockProfile::default()
+        },
+    );
+    state.profiles.insert(
+        "protect".to_string(),
+        LockProfile {
+            extends: strings(&["baseline"]),
+            locked_patterns: strings(&["src/**"]),
+            description: "Protect infra and sources".to_string(),
+            ..LockProfile::default()
+        },
+    );
+    state.save_to_file(&repo_a.join(".icaros")).unwrap();
+
+    icaros(&repo_a, &home)
+        .args(["profile", "export", "protect", "--library"])
+        .assert()
+        .success();
+    // The exported profile stands alone: inherited rules are folded in
+    let exported = LockProfile::load_from_file(&home.join("profiles/protect.yaml")).unwrap();
+    assert!(exported.extends.is_empty());
+    assert_eq!(exported.locked_patterns, vec!["infra/**", "src/**"]);
+
+    icaros(&repo_b, &home)
+        .args(["profile", "library"])
+        .assert()
+        .success()
+        .stdout("protect  Protect infra and sources\n");
+
+    icaros(&repo_b, &home)
+        .args(["profile", "import", "protect"])
+        .assert()
+        .success()
+        .stderr(predicates::str::contains(
+            "pattern 'infra/**' matches nothing in this repository",
+        ));
+    let state = AppState::load_from_file(&repo_b.join(".icaros")).unwrap();
+    assert_eq!(state.profiles["protect"].locked_patterns, vec!["src/**"]);
+
+    icaros(&repo_b, &home)
+        .args(["profile", "import", "protect"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "Profile 'protect' already exists",
+        ));
+}
+
+#[test]
+fn test_export_to_file_and_import_under_new_name() {
+    let temp_dir = TempDir::new().unwrap();
+    let home = temp_dir.path().join("home");
+    let root = temp_dir.path().join("repo");
+    fs::create_dir_all(root.join("infra")).unwrap();
+    fs::write(root.join("infra/main.tf"), "").unwrap();
+
+    icaros(&root, &home)
+        .args(["lock", "infra"])
+        .assert()
+        .success();
+    icaros(&root, &home)
+        .args(["profile", "save", "infra-only"])
+        .assert()
+        .success();
+    icaros(&root, &home)
+        .args(["profile", "export", "infra-only", "infra.json"])
+        .assert()
+        .success();
+    icaros(&root, &home)
+        .args(["profile", "export", "infra-only", "infra.json"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("use --force to overwrite it"));
+
+    fs::write(
+        root.join("extra.yaml"),
+        "locked_patterns: [infra/**, terraform/**]\n",
+    )
+    .unwrap();
+    let output = icaros(&root, &home)
+        .args(["profile", "import", "extra.yaml", "--as", "infra-plus"])
+        .args(["--keep-unresolved", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        result["unresolved_patterns"],
+        serde_json::json!(["terraform/**"])
+    );
+
+    let state = AppState::load_from_file(&root.join(".icaros")).unwrap();
+    assert_eq!(
+        state.profiles["infra-plus"].locked_patterns,
+        vec!["infra/**", "terraform/**"]
+    );
+    let exported = LockProfile::load_from_file(&root.join("infra.json")).unwrap();
+    assert_eq!(exported.locked_patterns, vec!["infra/**"]);
+}
@@ -0,0 +1,109 @@
+use icaros::state::{AppState, LockProfile};
+use icaros::text_input::TextInput;
+use icaros::{file_tree, ui};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+fn create_project(root: &Path) {
+    fs::create_dir_all(root.join("src/generated")).unwrap();
+    fs::create_dir_all(root.join("tests")).unwrap();
+    fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+    fs::write(root.join("src/generated/schema.rs"), "").unwrap();
+    fs::write(root.join("tests/cli.rs"), "").unwrap();
+    fs::write(root.join("README.md"), "# Test").unwrap();
+}
+
+fn new_app(root: &Path) -> ui::App {
+    let tree = file_tree::build_tree(root, &[], false).unwrap();
+    ui::App::new(tree, root.join(".icaros"), root.to_path_buf())
+}
+
+fn save_profile(app: &mut ui::App, name: &str) {
+    app.start_profile_action(ui::ProfileAction::Save);
+    app.profile_input = TextInput::new(name);
+    app.handle_profile_input();
+}
+
+fn find_node<'a>(node: &'a file_tree::TreeNode, path: &Path) -> Option<&'a file_tree::TreeNode> {
+    if node.path == path {
+        return Some(node);
+    }
+    node.children
+        .iter()
+        .find_map(|child| find_node(child, path))
+}
+
+#[test]
+fn test_profile_round_trip_reproduces_state_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let root: PathBuf = temp_dir.path().canonicalize().unwrap();
+    create_project(&root);
+    let state_file = root.join(".icaros");
+
+    // Everything locked except tests, with new files allowed in src/generated
+    let mut app = new_app(&root);
+    app.toggle_path(&root, true);
+    app.toggle_path(&root.join("tests"), true);
+    assert!(app.set_allow_create(&root.join("src/generated"), true));
+    app.write_state().unwrap();
+    save_profile(&mut app, "careful");
+
+    let profile = &AppState::load_from_file(&state_file).unwrap().profiles["careful"];
+    assert_eq!(profile.locked_patterns, vec!["**"]);
+    assert_eq!(profile.unlocked_patterns, vec!["tests/**"]);
+    assert_eq!(profile.allow_create_patterns, vec!["src/generated"]);
+
+    // A second profile with a different policy
+    app.toggle_path(&root, true);
+    app.toggle_path(&root.join("README.md"), false);
+    app.write_state().unwrap();
+    save_profile(&mut app, "docs");
+
+    // Save -> switch away -> switch back gives the identical file
+    app.switch_to_profile("careful");
+    let saved = fs::read_to_string(&state_file).unwrap();
+    app.switch_to_profile("docs");
+    assert_ne!(fs::read_to_string(&state_file).unwrap(), saved);
+    app.switch_to_profile("careful");
+    assert_eq!(fs::read_to_string(&state_file).unwrap(), saved);
+
+    // The tree shows the restored allow-create exception
+    let generated = find_node(&app.tree, &root.join("src/generated")).unwrap();
+    assert!(generated.is_locked);
+    assert!(generated.allow_create_in_locked);
+    assert!(!find_node(&app.tree, &root.join("tests")).unwrap().is_locked);
+
+    // A fresh session sees the same policy
+    let mut reopened = new_app(&root);
+    reopened.restore_state(&AppState::load_from_file(&state_file).unwrap());
+    reopened.write_state().unwrap();
+    assert_eq!(fs::read_to_string(&state_file).unwrap(), saved);
+}
+
+#[test]
+fn test_switch_to_profile_applies_whole_policy() {
+    let mut state = AppState::new(PathBuf::from("/test/root"));
+    state.profiles.insert(
+        "generated".to_string(),
+        LockProfile {
+            locked_patterns: vec!["src/**".to_string()],
+            unlocked_patterns: vec!["src/bin/**".to_string()],
+            allow_create_patterns: vec!["src/generated".to_string()],
+            description: "Only generated code".to_string(),
+            ..LockProfile::default()
+        },
+    );
+
+    assert!(state.switch_to_profile("generated"));
+
+    let policy = state.current_policy();
+    assert_eq!(
+        LockProfile {
+            description: "Only generated code".to_string(),
+            ..policy
+        },
+        state.profiles["generated"]
+    );
+    assert_eq!(state.active_profile.as_deref(), Some("generated"));
+}
@@ -0,0 +1,176 @@
+use assert_cmd::Command;
+use icaros::state::{AppState, BranchRule, LockProfile};
+use icaros::{file_tree, ui};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn create_project(root: &Path) {
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::create_dir_all(root.join("tests")).unwrap();
+    fs::write(root.join("src/parser.rs"), "").unwrap();
+    fs::write(root.join("src/main.rs"), "").unwrap();
+    fs::write(root.join("tests/parser_test.rs"), "").unwrap();
+    fs::write(root.join("README.md"), "").unwrap();
+}
+
+#[test]
+fn test_quest_restores_previous_policy_and_profiles() {
+    let mut state = AppState::new("/test/root".into());
+    state.profiles.insert(
+        "docs".to_string(),
+        LockProfile {
+            locked_patterns: vec!["README.md".to_string()],
+            ..LockProfile::default()
+        },
+    );
+    state.branch_profiles.push(BranchRule {
+        branch: "*".to_string(),
+        profile: "docs".to_string(),
+    });
+    assert!(state.switch_to_profile("docs"));
+    let before = state.current_policy();
+
+    state
+        .start_quest("parser", vec!["src/parser.rs".to_string()], Vec::new())
+        .unwrap();
+    assert!(state.is_locked(Path::new("src/main.rs")));
+    assert!(!state.is_locked(Path::new("src/parser.rs")));
+    assert_eq!(state.active_profile, None);
+    // Branch rules don't pull the policy out from under a quest
+    assert_eq!(state.follow_branch("main"), None);
+    // Nor do profile switches
+    assert!(!state.switch_to_profile("docs"));
+    assert_eq!(
+        state
+            .activate(&["docs".to_string()])
+            .unwrap_err()
+            .to_string(),
+        "Quest 'parser' is in progress; end it first"
+    );
+    assert!(state.is_locked(Path::new("src/main.rs")));
+    assert_eq!(
+        state
+            .start_quest("again", vec!["README.md".to_string()], Vec::new())
+            .unwrap_err()
+            .to_string(),
+        "Quest 'parser' is already in progress; end it first"
+    );
+
+    let quest = state.end_quest().unwrap();
+    assert_eq!(quest.name, "parser");
+    assert_eq!(state.current_policy(), before);
+    assert_eq!(state.active_profile.as_deref(), Some("docs"));
+    assert_eq!(
+        state.end_quest().unwrap_err().to_string(),
+        "No quest is in progress"
+    );
+    assert!(state.start_quest("empty", Vec::new(), Vec::new()).is_err());
+}
+
+#[test]
+fn test_tui_quest_from_marked_paths() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    create_project(&root);
+    let tree = file_tree::build_tree(&root, &[], false).unwrap();
+    let mut app = ui::App::new(tree, root.join(".icaros"), root.clone());
+    app.toggle_path(&root.join("README.md"), false);
+    app.write_state().unwrap();
+    let before = fs::read_to_string(root.join(".icaros")).unwrap();
+
+    app.toggle_quest_mark(&root.join("src/parser.rs"));
+    app.toggle_quest_mark(&root.join("tests"));
+    app.toggle_quest_mark(&root.join("README.md"));
+    app.toggle_quest_mark(&root.join("README.md"));
+    app.start_quest(None).unwrap();
+
+    let quest = app.active_quest.clone().unwrap();
+    assert_eq!(quest.name, "parser.rs");
+    assert_eq!(quest.scope, vec!["src/parser.rs", "tests/**"]);
+    assert!(app.quest_marks.is_empty());
+    assert!(app.is_path_effectively_locked(&root.join("src/main.rs")));
+    assert!(!app.is_path_effectively_locked(&root.join("tests/parser_test.rs")));
+
+    app.end_quest().unwrap();
+    assert!(app.active_quest.is_none());
+    assert!(app.is_path_effectively_locked(&root.join("README.md")));
+    assert!(!app.is_path_effectively_locked(&root.join("src/main.rs")));
+    assert_eq!(fs::read_to_string(root.join(".icaros")).unwrap(), before);
+}
+
+#[test]
+fn test_cli_quest_start_status_and_end() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    create_project(root);
+
+    let icaros = || {
+        let mut cmd = Command::cargo_bin("icaros").unwrap();
+        cmd.current_dir(root)
+            .env("ICAROS_CONFIG", root.join("no-user-config.yaml"))
+            .env_remove("ICAROS_AGENT");
+        cmd
+    };
+
+    icaros().args(["lock", "README.md"]).assert().success();
+    icaros()
+        .args(["profile", "save", "docs"])
+        .assert()
+        .success();
+
+    icaros()
+        .args([
+            "quest",
+            "start",
+            "src/parser.rs",
+            "tests",
+            "--name",
+            "parser",
+        ])
+        .assert()
+        .success()
+        .stdout("Quest 'parser' started; only these may change:\n  src/parser.rs\n  tests/**\n");
+    icaros().args(["check", "src/main.rs"]).assert().code(1);
+    icaros()
+        .args(["check", "src/parser.rs", "tests/new_test.rs"])
+        .assert()
+        .success();
+    icaros()
+        .args(["status"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            "Quest:          parser (only src/parser.rs, tests/**)",
+        ));
+    icaros()
+        .args(["quest", "start", "README.md"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("already in progress"));
+    for args in [&["profile", "switch", "docs"][..], &["profile", "sync"]] {
+        icaros()
+            .args(args)
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains(
+                "Quest 'parser' is in progress; end it first",
+            ));
+    }
+
+    let output = icaros().args(["quest", "end", "--json"]).output().unwrap();
+    assert!(output.status.success());
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(result["quest"], serde_json::Value::Null);
+    assert_eq!(result["ended"]["name"], "parser");
+
+    let state = AppState::load_from_file(&root.join(".icaros")).unwrap();
+    assert_eq!(state.active_profile.as_deref(), Some("docs"));
+    assert!(state.quest.is_none());
+    icaros().args(["check", "src/main.rs"]).assert().success();
+    icaros()
+        .args(["quest", "status"])
+        .assert()
+        .success()
+        .stdout("No quest in progress\n");
+}
@@ -0,0 +1,231 @@
+mod common;
+
+use common::commit_all;
+use crossterm::event::KeyCode;
+use git2::{BranchType, Repository};
+use icaros::git::{GitFileStatus, GitManager, ReviewBase};
+use icaros::{file_tree, ui};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// `main` has `a.txt` and `b.txt`. An agent branch then edits `a.txt` in one
+/// commit, deletes `b.txt` in another, and leaves `c.txt` uncommitted.
+fn agent_branch_repo(root: &Path) -> Repository {
+    let repo = Repository::init(root).unwrap();
+    fs::write(root.join(".gitignore"), ".icaros\n").unwrap();
+    fs::write(root.join("a.txt"), "one\ntwo\nthree\n").unwrap();
+    fs::write(root.join("b.txt"), "gone\n").unwrap();
+    commit_all(&repo, "Initial commit");
+    {
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        if repo.find_branch("main", BranchType::Local).is_err() {
+            repo.branch("main", &head, false).unwrap();
+        }
+        repo.branch("agent", &head, false).unwrap();
+    }
+    repo.set_head("refs/heads/agent").unwrap();
+
+    fs::write(root.join("a.txt"), "one\n2\nthree\nfour\n").unwrap();
+    commit_all(&repo, "Edit a");
+    fs::remove_file(root.join("b.txt")).unwrap();
+    let mut index = repo.index().unwrap();
+    index.remove_path(Path::new("b.txt")).unwrap();
+    index.write().unwrap();
+    commit_all(&repo, "Remove b");
+    fs::write(root.join("c.txt"), "new\n").unwrap();
+    repo
+}
+
+fn stats(git: &GitManager, base: &ReviewBase) -> Vec<(String, GitFileStatus, usize, usize)> {
+    let commit = git.resolve_review_base(base).unwrap();
+    git.review_files(commit)
+        .unwrap()
+        .into_iter()
+        .map(|changed| {
+            (
+                changed.file.path.display().to_string(),
+                changed.file.status,
+                changed.additions,
+                changed.deletions,
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_review_across_commits_with_stats() {
+    let temp_dir = TempDir::new().unwrap();
+    let _repo = agent_branch_repo(temp_dir.path());
+    let git = GitManager::new(temp_dir.path()).unwrap();
+
+    assert_eq!(
+        ReviewBase::parse("main..."),
+        ReviewBase::MergeBase("main".to_string())
+    );
+    assert_eq!(ReviewBase::parse(" "), ReviewBase::Checkpoint);
+    assert_eq!(
+        ReviewBase::parse("HEAD~1"),
+        ReviewBase::Rev("HEAD~1".to_string())
+    );
+
+    let since_main = vec![
+        ("a.txt".to_string(), GitFileStatus::Modified, 2, 1),
+        ("b.txt".to_string(), GitFileStatus::Deleted, 0, 1),
+        ("c.txt".to_string(), GitFileStatus::Added, 1, 0),
+    ];
+    assert_eq!(
+        stats(&git, &ReviewBase::MergeBase("main".to_string())),
+        since_main
+    );
+    assert_eq!(
+        stats(&git, &ReviewBase::Rev("main".to_string())),
+        since_main
+    );
+    assert_eq!(
+        stats(&git, &ReviewBase::Rev("HEAD~1".to_string())),
+        since_main[1..]
+    );
+
+    let commit = git
+        .resolve_review_base(&ReviewBase::Rev("main".to_string()))
+        .unwrap();
+    let a = &git.review_files(commit).unwrap()[0].file;
+    let diff = git.review_diff(a, commit, false).unwrap();
+    assert_eq!(diff.hunks.len(), 1);
+    assert_eq!(
+        git.review_versions(a, commit).0.as_deref(),
+        Some("one\ntwo\nthree\n")
+    );
+
+    assert!(git
+        .resolve_review_base(&ReviewBase::Rev("nope".to_string()))
+        .is_err());
+    assert!(git.resolve_review_base(&ReviewBase::Checkpoint).is_err());
+}
+
+#[test]
+fn test_checkpoint_snapshots_the_working_tree() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let _repo = agent_branch_repo(root);
+    let git = GitManager::new(root).unwrap();
+    let before = git.get_path_statuses().unwrap();
+
+    // Uncommitted and untracked changes are in the snapshot, and left alone
+    git.save_checkpoint().unwrap();
+    assert!(git.checkpoint().is_some());
+    assert_eq!(git.get_path_statuses().unwrap(), before);
+    assert!(stats(&git, &ReviewBase::Checkpoint).is_empty());
+
+    fs::write(root.join("c.txt"), "new\nand more\n").unwrap();
+    fs::write(root.join("d.txt"), "d\n").unwrap();
+    assert_eq!(
+        stats(&git, &ReviewBase::Checkpoint),
+        vec![
+            ("c.txt".to_string(), GitFileStatus::Modified, 1, 0),
+            ("d.txt".to_string(), GitFileStatus::Added, 1, 0),
+        ]
+    );
+
+    // Each process keeps its own checkpoint, removed when it exits
+    let repo = Repository::open(root).unwrap();
+    let name = format!("refs/icaros/checkpoints/{}", std::process::id());
+    assert_eq!(repo.refname_to_id(&name).ok(), git.checkpoint());
+    git.drop_checkpoint().unwrap();
+    assert!(git.checkpoint().is_none());
+    git.drop_checkpoint().unwrap();
+
+    // Checkpoints of sessions that are gone are pruned, running ones kept
+    let head = repo.head().unwrap().target().unwrap();
+    let mut exited = std::process::Command::new("true").spawn().unwrap();
+    exited.wait().unwrap();
+    let stale = format!("refs/icaros/checkpoints/{}", exited.id());
+    repo.reference(&stale, head, true, "test").unwrap();
+    git.save_checkpoint().unwrap();
+    assert_eq!(git.prune_stale_checkpoints().unwrap(), 1);
+    assert!(repo.refname_to_id(&stale).is_err());
+    assert!(git.checkpoint().is_some());
+}
+
+#[test]
+fn test_session_checkpoint_removed_when_app_is_dropped() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    let _repo = agent_branch_repo(&root);
+    let git = GitManager::new(&root).unwrap();
+
+    let tree = file_tree::build_tree(&root, &[], false).unwrap();
+    let mut app = ui::App::new(tree, root.join(".icaros"), root.clone());
+    app.take_session_checkpoint();
+    app.wait_for_checkpoint();
+    assert!(git.checkpoint().is_some());
+
+    // As when the UI stops with an error before removing it
+    drop(app);
+    assert!(git.checkpoint().is_none());
+}
+
+#[test]
+fn test_git_stage_review_mode() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    let _repo = agent_branch_repo(&root);
+
+    let tree = file_tree::build_tree(&root, &[], false).unwrap();
+    let mut app = ui::App::new(tree, root.join(".icaros"), root.clone());
+    app.take_session_checkpoint();
+    app.wait_for_checkpoint();
+    fs::write(root.join("a.txt"), "one\n2\nthree\nfour\nfive\n").unwrap();
+
+    app.toggle_review();
+    let review = app.git_review.as_ref().unwrap();
+    assert_eq!(review.base, ReviewBase::Checkpoint);
+    assert_eq!(review.files.len(), 1);
+    assert_eq!(app.git_files[0].path, Path::new("a.txt"));
+    assert_eq!(app.git_diff_hunks.len(), 1);
+
+    // Review mode is read-only
+    app.toggle_git_file_stage();
+    assert_eq!(
+        app.git_message.as_deref(),
+        Some("Review is read-only; R goes back to staging")
+    );
+
+    app.start_review_base_input();
+    for c in "main...".chars() {
+        app.handle_review_base_key(KeyCode::Char(c));
+    }
+    app.handle_review_base_key(KeyCode::Enter);
+    assert!(app.git_review_input.is_none());
+    let review = app.git_review.as_ref().unwrap();
+    assert_eq!(review.base, ReviewBase::MergeBase("main".to_string()));
+    assert_eq!(review.files.len(), 3);
+
+    app.toggle_review();
+    assert!(app.git_review.is_none());
+    assert!(app
+        .git_files
+        .iter()
+        .all(|file| file.path != Path::new("b.txt")));
+
+    // Without a checkpoint, R reviews since the default branch, whatever its name
+    app.drop_session_checkpoint();
+    let repo = Repository::open(&root).unwrap();
+    repo.find_branch("main", BranchType::Local)
+        .unwrap()
+        .rename("trunk", false)
+        .unwrap();
+    if let Ok(mut master) = repo.find_branch("master", BranchType::Local) {
+        master.delete().unwrap();
+    }
+    let git = GitManager::new(&root).unwrap();
+    repo.config()
+        .unwrap()
+        .set_str("init.defaultBranch", "trunk")
+        .unwrap();
+    assert_eq!(git.default_branch().as_deref(), Some("trunk"));
+    app.toggle_review();
+    let review = app.git_review.as_ref().unwrap();
+    assert_eq!(review.base, ReviewBase::MergeBase("trunk".to_string()));
+}
@@ -0,0 +1,184 @@
+mod common;
+
+use assert_cmd::Command;
+use common::commit_all;
+use git2::Repository;
+use icaros::git::{GitFile, GitFileStatus, GitManager};
+use icaros::scope::{self, Scope, ScopeOptions};
+use icaros::state::AppState;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+fn change(path: &str, status: GitFileStatus) -> GitFile {
+    GitFile {
+        path: PathBuf::from(path),
+        status,
+        staged: false,
+        renamed_from: None,
+    }
+}
+
+/// A repository with `main`, and a `feature` branch that adds, changes and
+/// deletes files.
+fn feature_repo(root: &Path) -> Repository {
+    let repo = Repository::init(root).unwrap();
+    repo.set_head("refs/heads/main").unwrap();
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::create_dir_all(root.join("docs")).unwrap();
+    fs::write(root.join(".gitignore"), ".icaros\n").unwrap();
+    fs::write(root.join("src/lib.rs"), "").unwrap();
+    fs::write(root.join("src/main.rs"), "").unwrap();
+    fs::write(root.join("docs/old.md"), "# Old notes\n").unwrap();
+    commit_all(&repo, "Initial commit");
+
+    let head = repo.head().unwrap().peel_to_commit().unwrap();
+    repo.branch("feature", &head, false).unwrap();
+    repo.set_head("refs/heads/feature").unwrap();
+    fs::create_dir_all(root.join("src/parser")).unwrap();
+    fs::write(root.join("src/lib.rs"), "pub mod parser;").unwrap();
+    fs::write(root.join("src/parser/mod.rs"), "pub fn parse() {}\n").unwrap();
+    fs::remove_file(root.join("docs/old.md")).unwrap();
+    commit_all(&repo, "Add parser");
+    drop(head);
+    repo
+}
+
+#[test]
+fn test_scope_from_changes() {
+    let root = Path::new("/repo");
+    let files = vec![
+        change("src/lib.rs", GitFileStatus::Modified),
+        change("src/parser/mod.rs", GitFileStatus::Added),
+        change("src/parser/lexer.rs", GitFileStatus::Untracked),
+        change("docs/old.md", GitFileStatus::Deleted),
+        change("Cargo.toml", GitFileStatus::Modified),
+        change("NOTES.md", GitFileStatus::Untracked),
+        change("scratch/", GitFileStatus::Untracked),
+    ];
+
+    let exact = scope::scope_from_changes(&files, root, root, ScopeOptions::default());
+    assert_eq!(
+        exact,
+        Scope {
+            unlocked: vec![
+                "Cargo.toml",
+                "NOTES.md",
+                "scratch/**",
+                "src/lib.rs",
+                "src/parser/lexer.rs",
+                "src/parser/mod.rs",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+            allow_create: Vec::new(),
+        }
+    );
+
+    let options = ScopeOptions {
+        widen: true,
+        allow_create: true,
+    };
+    let widened = scope::scope_from_changes(&files, root, root, options);
+    // Root-level files stay exact, and unlocked directories need no allow-create
+    assert_eq!(
+        widened.unlocked,
+        vec!["Cargo.toml", "NOTES.md", "scratch/**", "src/**"]
+    );
+    assert!(widened.allow_create.is_empty());
+
+    let options = ScopeOptions {
+        widen: false,
+        allow_create: true,
+    };
+    let created = scope::scope_from_changes(&files, root, root, options);
+    assert_eq!(created.allow_create, vec!["src/parser"]);
+
+    // Only changes under a sub-root count, relative to it
+    let sub = scope::scope_from_changes(&files, root, &root.join("src"), options);
+    assert_eq!(
+        sub.unlocked,
+        vec!["lib.rs", "parser/lexer.rs", "parser/mod.rs"]
+    );
+    assert_eq!(sub.allow_create, vec!["parser"]);
+}
+
+#[test]
+fn test_range_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let _repo = feature_repo(root);
+    let git = GitManager::new(root).unwrap();
+
+    let mut files: Vec<(String, GitFileStatus)> = git
+        .get_range_files("main..feature")
+        .unwrap()
+        .into_iter()
+        .map(|file| (file.path.display().to_string(), file.status))
+        .collect();
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        files,
+        vec![
+            ("docs/old.md".to_string(), GitFileStatus::Deleted),
+            ("src/lib.rs".to_string(), GitFileStatus::Modified),
+            ("src/parser/mod.rs".to_string(), GitFileStatus::Added),
+        ]
+    );
+    // A single revision is compared with HEAD
+    assert_eq!(git.get_range_files("main").unwrap().len(), 3);
+    assert!(git.get_range_files("nope..HEAD").is_err());
+}
+
+#[test]
+fn test_cli_scope_to_profile_and_quest() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let _repo = feature_repo(root);
+
+    let icaros = || {
+        let mut cmd = Command::cargo_bin("icaros").unwrap();
+        cmd.current_dir(root)
+            .env("ICAROS_CONFIG", root.join("no-user-config.yaml"))
+            .env_remove("ICAROS_AGENT");
+        cmd
+    };
+
+    icaros()
+        .args(["scope", "main..HEAD", "--allow-create"])
+        .assert()
+        .success()
+        .stdout(
+            "unlock        src/lib.rs\nunlock        src/parser/mod.rs\nallow-create  src/parser\n",
+        );
+
+    icaros()
+        .args(["scope", "main..HEAD", "--widen", "--profile", "review"])
+        .assert()
+        .success()
+        .stdout("Saved profile 'review' from main..HEAD\n");
+    let state = AppState::load_from_file(&root.join(".icaros")).unwrap();
+    let review = &state.profiles["review"];
+    assert_eq!(review.locked_patterns, vec!["**"]);
+    assert_eq!(review.unlocked_patterns, vec!["src/**"]);
+    icaros()
+        .args(["scope", "main..HEAD", "--profile", "review"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("already exists"));
+
+    // Nothing uncommitted yet; then a working-tree change becomes a quest
+    icaros()
+        .arg("scope")
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("No changed files"));
+    fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+    icaros()
+        .args(["scope", "--quest", "continue"])
+        .assert()
+        .success();
+    icaros().args(["check", "src/main.rs"]).assert().success();
+    icaros().args(["check", "src/lib.rs"]).assert().code(1);
+}
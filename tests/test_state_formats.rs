@@ -0,0 +1,111 @@
+use icaros::state::{self, AppState, LockProfile, StateFormat};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn sample_state(root: &Path) -> AppState {
+    let mut state = AppState::new(root.to_path_buf());
+    state.locked_patterns = vec!["**".to_string()];
+    state.unlocked_patterns = vec!["tests/**".to_string()];
+    state.expanded_dirs = vec![root.join("src")];
+    state.profiles.insert(
+        "frontend".to_string(),
+        LockProfile {
+            locked_patterns: vec!["api/**".to_string()],
+            description: "UI work only".to_string(),
+            ..LockProfile::default()
+        },
+    );
+    state.active_profile = Some("frontend".to_string());
+    state
+}
+
+fn assert_round_trip(state: &AppState, loaded: &AppState) {
+    assert_eq!(loaded.root_path, state.root_path);
+    assert_eq!(loaded.locked_patterns, state.locked_patterns);
+    assert_eq!(loaded.unlocked_patterns, state.unlocked_patterns);
+    assert_eq!(loaded.expanded_dirs, state.expanded_dirs);
+    assert_eq!(loaded.active_profile, state.active_profile);
+    assert_eq!(loaded.profiles, state.profiles);
+}
+
+#[test]
+fn test_json_state_file_by_extension() {
+    let temp_dir = TempDir::new().unwrap();
+    let state_file = temp_dir.path().join("icaros.json");
+    let state = sample_state(temp_dir.path());
+
+    state.save_to_file(&state_file).unwrap();
+
+    let content = fs::read_to_string(&state_file).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&content).unwrap();
+    assert_eq!(json["root_path"], ".");
+    assert_round_trip(&state, &AppState::load_from_file(&state_file).unwrap());
+}
+
+#[test]
+fn test_toml_state_file_by_extension() {
+    let temp_dir = TempDir::new().unwrap();
+    let state_file = temp_dir.path().join("icaros.toml");
+    let state = sample_state(temp_dir.path());
+
+    state.save_to_file(&state_file).unwrap();
+
+    let content = fs::read_to_string(&state_file).unwrap();
+    assert!(content.contains("root_path = \".\""), "{content}");
+    assert!(content.contains("[profiles.frontend]"), "{content}");
+    assert_round_trip(&state, &AppState::load_from_file(&state_file).unwrap());
+}
+
+#[test]
+fn test_extensionless_state_file_keeps_its_format() {
+    let temp_dir = TempDir::new().unwrap();
+    let state_file = temp_dir.path().join(".icaros");
+    let state = sample_state(temp_dir.path());
+
+    state
+        .save_to_file_as(&state_file, StateFormat::Toml)
+        .unwrap();
+    let loaded = AppState::load_from_file(&state_file).unwrap();
+    assert_round_trip(&state, &loaded);
+
+    // A plain save must not silently switch the file back to YAML
+    loaded.save_to_file(&state_file).unwrap();
+    let content = fs::read_to_string(&state_file).unwrap();
+    assert_eq!(StateFormat::sniff(&content), StateFormat::Toml);
+
+    state
+        .save_to_file_as(&state_file, StateFormat::Json)
+        .unwrap();
+    let content = fs::read_to_string(&state_file).unwrap();
+    assert!(content.starts_with('{'));
+    assert_round_trip(&state, &AppState::load_from_file(&state_file).unwrap());
+}
+
+#[test]
+fn test_sniff_formats() {
+    assert_eq!(StateFormat::sniff("root_path: .\n"), StateFormat::Yaml);
+    assert_eq!(
+        StateFormat::sniff("{\n  \"root_path\": \".\"\n}"),
+        StateFormat::Json
+    );
+    assert_eq!(
+        StateFormat::sniff("# comment\nroot_path = \".\"\n"),
+        StateFormat::Toml
+    );
+    assert_eq!(StateFormat::sniff("[profiles.a]\n"), StateFormat::Toml);
+    assert_eq!("JSON".parse::<StateFormat>(), Ok(StateFormat::Json));
+    assert!("xml".parse::<StateFormat>().is_err());
+}
+
+#[test]
+fn test_state_json_schema() {
+    let schema = serde_json::to_value(state::state_json_schema()).unwrap();
+
+    assert_eq!(schema["title"], "AppState");
+    assert_eq!(schema["required"], serde_json::json!(["root_path"]));
+    assert!(schema["properties"]["locked_patterns"].is_object());
+    assert!(
+        schema["definitions"]["LockProfile"]["properties"]["allow_create_patterns"].is_object()
+    );
+}
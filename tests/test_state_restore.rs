@@ -3,7 +3,6 @@ use std::fs;
 use tempfile::TempDir;
 
 #[test]
-#[ignore = "This test requires access to internal restore_state function which is not exposed"]
 fn test_restore_everything_locked_except_tests() {
     // Create a temporary directory structure
     let temp_dir = TempDir::new().unwrap();
@@ -29,7 +28,7 @@ fn test_restore_everything_locked_except_tests() {
   "unlocked_patterns": [
     "tests/**"
   ],
-  "allow_create_patterns": [],
+  "allow_create_patterns": ["src"],
   "expanded_dirs": ["ROOT_PATH"]
 }"#
     .replace("ROOT_PATH", &root.to_string_lossy());
@@ -37,24 +36,24 @@ fn test_restore_everything_locked_except_tests() {
     fs::write(&state_file, state_content).unwrap();
 
     // Create app and restore state
-    let app = ui::App::new(tree, state_file.clone(), root.to_path_buf());
-    let _state = state::AppState::load_from_file(&state_file).unwrap();
+    let mut app = ui::App::new(tree, state_file.clone(), root.to_path_buf());
+    let state = state::AppState::load_from_file(&state_file).unwrap();
+    app.restore_state(&state);
 
-    // Manually call restore_state (since we can't import it from main)
-    // Instead, let's check the tree directly after loading
-
-    // For now, let's print the tree state to verify
     print_tree_state(&app.tree, 0);
 
     // Verify the root is locked
     assert!(app.tree.is_locked, "Root should be locked");
+    assert!(app.tree.is_expanded, "Root should be expanded");
 
     // Find and verify tests directory is unlocked
-    let tests_node = find_node(&app.tree, "tests");
-    assert!(tests_node.is_some(), "Tests directory should exist");
+    let tests_node = find_node(&app.tree, "tests").expect("Tests directory should exist");
+    assert!(!tests_node.is_locked, "Tests directory should be unlocked");
 
-    // Note: This test currently fails because restore_state is not public
-    // and we need to test the actual restoration logic
+    // The allow-create exception survives reapplying the locks
+    let src_node = find_node(&app.tree, "src").unwrap();
+    assert!(src_node.is_locked);
+    assert!(src_node.allow_create_in_locked);
 }
 
 fn find_node<'a>(node: &'a file_tree::TreeNode, name: &str) -> Option<&'a file_tree::TreeNode> {